@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+
+use syn::{
+    ext::IdentExt as _,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token, Attribute, Ident, Token,
+};
+
+type Handler<'a> = Box<dyn FnMut(&Ident, ParseStream) -> syn::Result<()> + 'a>;
+
+/// A value in `#[fsmentry(...)]`.
+pub trait Value: Parse {
+    /// Shown to the user in errors.
+    const SHAPE: &'static str;
+}
+
+macro_rules! value {
+    ($($ty:ty => $shape:literal),* $(,)?) => {
+        $(
+            impl Value for $ty {
+                const SHAPE: &'static str = $shape;
+            }
+        )*
+    };
+}
+
+value! {
+    syn::Expr => "<expression>",
+    syn::Ident => "<identifier>",
+    syn::LitBool => "true | false",
+    syn::LitInt => "<integer>",
+    syn::LitStr => "\"...\"",
+    syn::Path => "<path>",
+    syn::Visibility => "<visibility>",
+}
+
+/// Parses `#[fsmentry(key = value, other(value))]` attributes,
+/// writing each value to the destination registered for its key.
+///
+/// Values may follow `=` or `:`, or be wrapped in parentheses.
+pub struct Parser<'a> {
+    /// Each key has a description of its expected shape.
+    handlers: BTreeMap<&'static str, (String, Handler<'a>)>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+        }
+    }
+    /// Accept `key = value` or `key(value)`, at most once.
+    pub fn once<T: Value + 'a>(self, key: &'static str, dst: &'a mut Option<T>) -> Self {
+        let shape = format!("{} = {}", key, T::SHAPE);
+        self.on(key, shape, move |ident, input| {
+            if dst.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("duplicate argument `{}`", key),
+                ));
+            }
+            *dst = Some(scalar(input)?);
+            Ok(())
+        })
+    }
+    /// Accept a bare `key`, or `key = true | false`, at most once.
+    pub fn flag(self, key: &'static str, dst: &'a mut bool) -> Self {
+        let mut seen = false;
+        self.on(key, String::from(key), move |ident, input| {
+            if seen {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("duplicate argument `{}`", key),
+                ));
+            }
+            seen = true;
+            *dst = match input.is_empty() || input.peek(Token![,]) {
+                true => true,
+                false => scalar::<syn::LitBool>(input)?.value,
+            };
+            Ok(())
+        })
+    }
+    /// Accept `key = [value, ...]` or `key(value, ...)`, appending to `dst`.
+    pub fn list<T: Value + 'a>(self, key: &'static str, dst: &'a mut Vec<T>) -> Self {
+        let shape = format!("{} = [{}, ..]", key, T::SHAPE);
+        self.on(key, shape, move |_, input| {
+            dst.extend(list(input)?);
+            Ok(())
+        })
+    }
+    /// Accept `key(nested = value, ...)`, parsing the contents with `parser`.
+    pub fn group(self, key: &'static str, mut parser: Parser<'a>) -> Self {
+        let shapes = parser.handlers.values().map(|(shape, _)| &**shape);
+        let shape = format!("{}({})", key, shapes.collect::<Vec<_>>().join(", "));
+        self.on(key, shape, move |_, input| {
+            let content;
+            syn::parenthesized!(content in input);
+            parser.parse_args(&content)
+        })
+    }
+    fn on(
+        mut self,
+        key: &'static str,
+        shape: String,
+        handler: impl FnMut(&Ident, ParseStream) -> syn::Result<()> + 'a,
+    ) -> Self {
+        self.handlers.insert(key, (shape, Box::new(handler)));
+        self
+    }
+    /// Remove all the `#[fsmentry(..)]` attributes from `attrs`, parsing their contents.
+    pub fn extract(mut self, attrs: &mut Vec<Attribute>) -> syn::Result<()> {
+        let mut rest = vec![];
+        for attr in attrs.drain(..) {
+            match attr.path().is_ident("fsmentry") {
+                true => attr.parse_args_with(|input: ParseStream| self.parse_args(input))?,
+                false => rest.push(attr),
+            }
+        }
+        *attrs = rest;
+        Ok(())
+    }
+    /// Parse `key = value, ...` to the end of `input`.
+    fn parse_args(&mut self, input: ParseStream) -> syn::Result<()> {
+        while !input.is_empty() {
+            let key = input.call(Ident::parse_any)?;
+            match self.handlers.get_mut(&*key.to_string()) {
+                Some((_, handler)) => handler(&key, input)?,
+                None => return Err(syn::Error::new(key.span(), self.unknown(&key))),
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(())
+    }
+}
+
+impl Parser<'_> {
+    fn unknown(&self, key: &Ident) -> String {
+        let key = key.to_string();
+        let mut message = format!("unknown argument `{}`", key);
+        let closest = self
+            .handlers
+            .keys()
+            .map(|it| (edit_distance(it, &key), it))
+            .min();
+        if let Some((distance, closest)) = closest {
+            if distance <= closest.len() / 3 + 1 {
+                message.push_str(&format!(", did you mean `{}`?", closest))
+            }
+        }
+        message.push_str("\n\nsupported arguments are:");
+        for (shape, _) in self.handlers.values() {
+            message.push_str(&format!("\n- `{}`", shape))
+        }
+        message
+    }
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right = right.chars().collect::<Vec<_>>();
+    let mut prev = (0..=right.len()).collect::<Vec<_>>();
+    for (i, l) in left.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, r) in right.iter().enumerate() {
+            let substitute = prev[j] + usize::from(l != *r);
+            row.push(substitute.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[right.len()]
+}
+
+fn scalar<T: Parse>(input: ParseStream) -> syn::Result<T> {
+    match input.peek(token::Paren) {
+        true => {
+            let content;
+            syn::parenthesized!(content in input);
+            content.parse()
+        }
+        false => {
+            separator(input)?;
+            input.parse()
+        }
+    }
+}
+
+fn list<T: Parse>(input: ParseStream) -> syn::Result<Vec<T>> {
+    let content;
+    match input.peek(token::Paren) {
+        true => {
+            syn::parenthesized!(content in input);
+        }
+        false => {
+            separator(input)?;
+            syn::bracketed!(content in input);
+        }
+    }
+    let items = Punctuated::<T, Token![,]>::parse_terminated(&content)?;
+    Ok(items.into_iter().collect())
+}
+
+fn separator(input: ParseStream) -> syn::Result<()> {
+    let lookahead = input.lookahead1();
+    if lookahead.peek(Token![=]) {
+        input.parse::<Token![=]>()?;
+    } else if lookahead.peek(Token![:]) {
+        input.parse::<Token![:]>()?;
+    } else {
+        return Err(lookahead.error());
+    }
+    Ok(())
+}
+
+#[test]
+fn extract() {
+    use syn::{parse_quote, LitStr};
+    let mut attrs: Vec<Attribute> = parse_quote! {
+        #[derive(Clone)]
+        #[fsmentry(dot_path = "/usr/bin/dot")]
+        /// Documentation.
+    };
+    let mut dot_path = None::<LitStr>;
+    Parser::new()
+        .once("dot_path", &mut dot_path)
+        .extract(&mut attrs)
+        .unwrap();
+    assert_eq!(dot_path.unwrap().value(), "/usr/bin/dot");
+    assert_eq!(attrs.len(), 2);
+
+    let mut attrs: Vec<Attribute> = parse_quote! {
+        #[fsmentry(dot_path("dot"), dot_path("dot"))]
+    };
+    let mut dot_path = None::<LitStr>;
+    let e = Parser::new()
+        .once("dot_path", &mut dot_path)
+        .extract(&mut attrs)
+        .unwrap_err();
+    assert_eq!(e.to_string(), "duplicate argument `dot_path`");
+
+    let mut attrs: Vec<Attribute> = parse_quote! {
+        #[fsmentry(dto_path = "dot")]
+    };
+    let mut dot_path = None::<LitStr>;
+    let e = Parser::new()
+        .once("dot_path", &mut dot_path)
+        .extract(&mut attrs)
+        .unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "unknown argument `dto_path`, did you mean `dot_path`?
+
+supported arguments are:
+- `dot_path = \"...\"`"
+    );
+}
+
+#[test]
+fn nested() {
+    use syn::{parse_quote, Expr, LitStr, Path};
+    let mut attrs: Vec<Attribute> = parse_quote! {
+        #[fsmentry(
+            derive(entry: [Debug, Clone], state(PartialEq)),
+            diagram(format = mermaid, renderer = kroki("http://localhost")),
+        )]
+        #[fsmentry(derive(entry = [Hash]), dot_path: "dot")]
+    };
+    let (mut entry, mut state) = (vec![], vec![]);
+    let (mut format, mut renderer) = (None::<Ident>, None::<Expr>);
+    let mut dot_path = None::<LitStr>;
+    Parser::new()
+        .group(
+            "derive",
+            Parser::new()
+                .list::<Path>("entry", &mut entry)
+                .list::<Path>("state", &mut state),
+        )
+        .group(
+            "diagram",
+            Parser::new()
+                .once("format", &mut format)
+                .once("renderer", &mut renderer),
+        )
+        .once("dot_path", &mut dot_path)
+        .extract(&mut attrs)
+        .unwrap();
+    let names = |it: &[Path]| {
+        it.iter()
+            .map(|it| it.get_ident().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&entry), ["Debug", "Clone", "Hash"]);
+    assert_eq!(names(&state), ["PartialEq"]);
+    assert_eq!(format.unwrap(), "mermaid");
+    assert!(matches!(renderer, Some(Expr::Call(_))));
+    assert_eq!(dot_path.unwrap().value(), "dot");
+    assert!(attrs.is_empty());
+}
+
+#[test]
+fn unknown_nested() {
+    use syn::{parse_quote, Path};
+    let mut attrs: Vec<Attribute> = parse_quote! {
+        #[fsmentry(derive(states: [Clone]))]
+    };
+    let (mut state, mut entry) = (vec![], vec![]);
+    let e = Parser::new()
+        .group(
+            "derive",
+            Parser::new()
+                .list::<Path>("state", &mut state)
+                .list::<Path>("entry", &mut entry),
+        )
+        .extract(&mut attrs)
+        .unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "unknown argument `states`, did you mean `state`?
+
+supported arguments are:
+- `entry = [<path>, ..]`
+- `state = [<path>, ..]`"
+    );
+}
+
+#[test]
+fn edit_distances() {
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("dot_path", "dot_path"), 0);
+}
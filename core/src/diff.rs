@@ -0,0 +1,408 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use proc_macro2::Span;
+use quote::ToTokens as _;
+use syn::{parse_quote, token, Token};
+use syn_graphs::dot::{kw, Graph, GraphDirectedness, Stmt, StmtList, ID};
+
+use crate::{util::OuterDocString, Data, EdgeData, FSMGenerator, NodeData};
+
+/// How a state or transition differs between two state machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+impl Change {
+    fn of<T>(old: Option<T>, new: Option<T>, eq: impl Fn(&T, &T) -> bool) -> Self {
+        match (old, new) {
+            (None, Some(_)) => Self::Added,
+            (Some(_), None) => Self::Removed,
+            (Some(old), Some(new)) if !eq(&old, &new) => Self::Changed,
+            _ => Self::Unchanged,
+        }
+    }
+    fn color(self) -> Option<&'static str> {
+        match self {
+            Change::Added => Some("green"),
+            Change::Removed => Some("red"),
+            Change::Changed => Some("gold"),
+            Change::Unchanged => None,
+        }
+    }
+}
+
+impl FSMGenerator {
+    /// A diagram of both `self` and `new` in [`DOT`](https://en.wikipedia.org/wiki/DOT_%28graph_description_language%29),
+    /// for reviewing changes to a state machine.
+    ///
+    /// States and transitions that were added are green, removed are red,
+    /// and those whose data, documentation or callback changed are gold.
+    /// When the only transition between two states is renamed, it is gold,
+    /// and labelled with both names.
+    pub fn dot_diff(&self, new: &Self) -> Graph {
+        let span = Span::call_site();
+        let mut stmts = vec![];
+        let nodes = self.nodes.keys().chain(new.nodes.keys());
+        for node in nodes.collect::<BTreeSet<_>>() {
+            let change = Change::of(self.nodes.get(node), new.nodes.get(node), same_node);
+            let id = &node.inner;
+            let stmt: Stmt = match change.color() {
+                Some(color) => parse_quote!(#id [color = #color, fontcolor = #color]),
+                None => parse_quote!(#id),
+            };
+            stmts.push((stmt, Some(Token![;](span))))
+        }
+        let pairs = self
+            .edges
+            .keys()
+            .chain(new.edges.keys())
+            .map(|(from, to, _)| (from, to))
+            .collect::<BTreeSet<_>>();
+        let mut parallel = false;
+        for (from_id, to_id) in pairs {
+            let between = |it: &Self| {
+                it.edges
+                    .keys()
+                    .filter(|(from, to, _)| (from, to) == (from_id, to_id))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            let (from, to) = (&from_id.inner, &to_id.inner);
+            // a transition whose method was renamed is drawn once
+            if let ([old_key], [new_key]) = (&*between(self), &*between(new)) {
+                if old_key.2 != new_key.2 {
+                    let color = Change::Changed.color();
+                    let label = format!("{} → {}", old_key.2, new_key.2);
+                    stmts.push((
+                        parse_quote!(#from -> #to [color = #color, label = #label]),
+                        Some(Token![;](span)),
+                    ));
+                    continue;
+                }
+            }
+            let keys = between(self)
+                .into_iter()
+                .chain(between(new))
+                .collect::<BTreeSet<_>>();
+            parallel |= keys.len() > 1;
+            for key in keys {
+                let change = Change::of(self.edges.get(&key), new.edges.get(&key), same_edge);
+                let method = &key.2;
+                let label =
+                    (*method != to_id.transition_fn(&new.method_case)).then(|| method.to_string());
+                let stmt: Stmt = match (change.color(), label) {
+                    (Some(color), Some(label)) => {
+                        parse_quote!(#from -> #to [color = #color, label = #label])
+                    }
+                    (Some(color), None) => parse_quote!(#from -> #to [color = #color]),
+                    (None, Some(label)) => parse_quote!(#from -> #to [label = #label]),
+                    (None, None) => parse_quote!(#from -> #to),
+                };
+                stmts.push((stmt, Some(Token![;](span))))
+            }
+        }
+        Graph {
+            strict: (!parallel).then(|| kw::strict(span)),
+            directedness: GraphDirectedness::Digraph(kw::digraph(span)),
+            id: Some(ID::AnyIdent(new.ident.clone())),
+            brace_token: token::Brace(span),
+            stmt_list: StmtList { stmts },
+        }
+    }
+
+    /// Whether `self` and `other` have the same states and transitions,
+    /// with the same data, hooks and arguments, and the same context and type aliases.
+    ///
+    /// Documentation, options and layout hints are ignored.
+    pub fn semantic_eq(&self, other: &Self) -> bool {
+        self.types() == other.types()
+            && self.nodes.len() == other.nodes.len()
+            && self.edges.len() == other.edges.len()
+            && self.nodes.iter().all(|(node, left)| {
+                other
+                    .nodes
+                    .get(node)
+                    .is_some_and(|right| same_node_behavior(left, right))
+            })
+            && self.edges.iter().all(|(key, left)| {
+                other
+                    .edges
+                    .get(key)
+                    .is_some_and(|right| same_edge_behavior(left, right))
+            })
+    }
+
+    /// Whether the states of `self` can be renamed to those of `other`,
+    /// so that they have the same transitions between them.
+    ///
+    /// Like [`Self::semantic_eq`], but names of states, transitions, hooks,
+    /// fields and arguments are ignored, while the types of data and arguments must match.
+    pub fn is_isomorphic(&self, other: &Self) -> bool {
+        let (left, right) = (Shape::of(self), Shape::of(other));
+        if left.nodes.len() != right.nodes.len()
+            || left.edges.values().map(Vec::len).sum::<usize>()
+                != right.edges.values().map(Vec::len).sum::<usize>()
+            || self.types() != other.types()
+        {
+            return false;
+        }
+        let mut mapping = vec![];
+        let mut used = vec![false; right.nodes.len()];
+        left.map_onto(&right, &mut mapping, &mut used)
+    }
+
+    /// The context and type aliases, which data is written in terms of.
+    fn types(&self) -> Vec<String> {
+        let mut aliases = self
+            .aliases
+            .iter()
+            .map(|it| format!("{} = {}", it.ident, it.ty.to_token_stream()))
+            .collect::<Vec<_>>();
+        aliases.sort();
+        aliases.push(self.context.to_token_stream().to_string());
+        aliases
+    }
+}
+
+/// A state machine without its names, for [`FSMGenerator::is_isomorphic`].
+struct Shape {
+    /// A description of each state, with its in and out degree.
+    nodes: Vec<(String, usize, usize)>,
+    /// Descriptions of the transitions between each pair of states, sorted.
+    edges: BTreeMap<(usize, usize), Vec<String>>,
+}
+
+impl Shape {
+    fn of(generator: &FSMGenerator) -> Self {
+        let ixs = generator
+            .nodes
+            .keys()
+            .enumerate()
+            .map(|(ix, it)| (it, ix))
+            .collect::<BTreeMap<_, _>>();
+        let mut edges = BTreeMap::<_, Vec<_>>::new();
+        for ((from, to, _), data) in &generator.edges {
+            let params = data
+                .params
+                .iter()
+                .map(|(_, ty)| ty.to_token_stream().to_string());
+            let label = format!(
+                "{:?}",
+                (
+                    params.collect::<Vec<_>>(),
+                    data.callback.is_some(),
+                    data.cfg.to_token_stream().to_string(),
+                    data.ensures.to_token_stream().to_string(),
+                    data.requires.to_token_stream().to_string(),
+                )
+            );
+            edges.entry((ixs[from], ixs[to])).or_default().push(label)
+        }
+        edges.values_mut().for_each(|it| it.sort());
+        let nodes = generator
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(ix, (_, node))| {
+                let ty = match &node.ty {
+                    None => vec![],
+                    Some(Data::Tuple(ty)) => vec![ty.to_token_stream().to_string()],
+                    Some(Data::Record(fields)) => fields
+                        .iter()
+                        .map(|(_, ty)| ty.to_token_stream().to_string())
+                        .collect(),
+                };
+                let label = format!(
+                    "{:?}",
+                    (
+                        matches!(node.ty, Some(Data::Record(_))),
+                        ty,
+                        node.on_enter.is_some(),
+                        node.on_exit.is_some(),
+                        node.terminal,
+                    )
+                );
+                let degree = |f: fn(&(usize, usize)) -> usize| {
+                    edges
+                        .iter()
+                        .filter(|(it, _)| f(it) == ix)
+                        .map(|(_, it)| it.len())
+                        .sum()
+                };
+                (label, degree(|it| it.0), degree(|it| it.1))
+            })
+            .collect();
+        Self { nodes, edges }
+    }
+
+    fn between(&self, from: usize, to: usize) -> Option<&Vec<String>> {
+        self.edges.get(&(from, to))
+    }
+
+    /// Extend `mapping` from states of `self` to states of `other` until it covers every state,
+    /// backtracking when the transitions between mapped states differ.
+    fn map_onto(&self, other: &Self, mapping: &mut Vec<usize>, used: &mut [bool]) -> bool {
+        let ix = mapping.len();
+        if ix == self.nodes.len() {
+            return true;
+        }
+        for candidate in 0..other.nodes.len() {
+            if used[candidate] || self.nodes[ix] != other.nodes[candidate] {
+                continue;
+            }
+            mapping.push(candidate);
+            let consistent = mapping.iter().enumerate().all(|(left, &right)| {
+                self.between(ix, left) == other.between(candidate, right)
+                    && self.between(left, ix) == other.between(right, candidate)
+            });
+            if consistent {
+                used[candidate] = true;
+                if self.map_onto(other, mapping, used) {
+                    return true;
+                }
+                used[candidate] = false;
+            }
+            mapping.pop();
+        }
+        false
+    }
+}
+
+fn same_node(left: &&NodeData, right: &&NodeData) -> bool {
+    same_node_behavior(left, right)
+        && docs(&left.docs) == docs(&right.docs)
+        && left.label == right.label
+}
+
+fn same_node_behavior(left: &NodeData, right: &NodeData) -> bool {
+    let ty = |it: &NodeData| it.ty.to_token_stream().to_string();
+    ty(left) == ty(right)
+        && left.on_enter == right.on_enter
+        && left.on_exit == right.on_exit
+        && left.terminal == right.terminal
+}
+
+fn same_edge(left: &&EdgeData, right: &&EdgeData) -> bool {
+    let attrs = |it: &EdgeData| {
+        it.attrs
+            .iter()
+            .map(|it| it.to_token_stream().to_string())
+            .collect::<Vec<_>>()
+    };
+    same_edge_behavior(left, right)
+        && docs(&left.docs) == docs(&right.docs)
+        && attrs(left) == attrs(right)
+}
+
+fn same_edge_behavior(left: &EdgeData, right: &EdgeData) -> bool {
+    let cfg = |it: &EdgeData| it.cfg.to_token_stream().to_string();
+    let ensures = |it: &EdgeData| it.ensures.to_token_stream().to_string();
+    let requires = |it: &EdgeData| it.requires.to_token_stream().to_string();
+    let on_mismatch = |it: &EdgeData| {
+        it.on_mismatch
+            .as_ref()
+            .map(|it| it.diverge(Default::default()).to_string())
+    };
+    let params = |it: &EdgeData| {
+        it.params
+            .iter()
+            .map(|(name, ty)| (name.clone(), ty.to_token_stream().to_string()))
+            .collect::<Vec<_>>()
+    };
+    left.callback == right.callback
+        && params(left) == params(right)
+        && cfg(left) == cfg(right)
+        && ensures(left) == ensures(right)
+        && requires(left) == requires(right)
+        && on_mismatch(left) == on_mismatch(right)
+}
+
+fn docs(docs: &[OuterDocString]) -> Vec<String> {
+    docs.iter().map(|it| it.doc.value()).collect()
+}
+
+#[test]
+fn dot_diff() {
+    use syn::parse::Parser as _;
+    let parse = |tokens| FSMGenerator::parse_dsl.parse2(tokens).unwrap();
+    let old = parse(quote::quote! {
+        Machine {
+            A -> B -> C;
+            C -start-> A;
+        }
+    });
+    let new = parse(quote::quote! {
+        Machine {
+            A -on(log)-> B -> D;
+            C -restart-> A;
+        }
+    });
+    assert_eq!(
+        old.dot_diff(&new).into_token_stream().to_string(),
+        quote::quote! {
+            strict digraph Machine {
+                A;
+                B;
+                C;
+                D [color = "green", fontcolor = "green"];
+                A -> B [color = "gold"];
+                B -> C [color = "red"];
+                B -> D [color = "green"];
+                C -> A [color = "gold", label = "start → restart"];
+            }
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn equivalence() {
+    use syn::parse::Parser as _;
+    let parse = |tokens| FSMGenerator::parse_dsl.parse2(tokens).unwrap();
+    let machine = parse(quote::quote! {
+        Machine {
+            Idle: u8;
+            /// Start.
+            Idle -> Running -on(self.log)-> Done;
+            Running -fail(code: u16)-> Idle;
+        }
+    });
+    let documented = parse(quote::quote! {
+        #[fsmentry(rankdir = LR)]
+        Renamed {
+            /// Waiting.
+            Idle: u8;
+            Running -fail(code: u16)-> Idle -> Running -on(self.log)-> Done;
+        }
+    });
+    assert!(machine.semantic_eq(&documented));
+    let renamed = parse(quote::quote! {
+        Machine {
+            Waiting: u8;
+            Waiting -> Working -on(self.trace)-> Finished;
+            Working -abort(status: u16)-> Waiting;
+        }
+    });
+    assert!(!machine.semantic_eq(&renamed));
+    assert!(machine.is_isomorphic(&renamed));
+    let retyped = parse(quote::quote! {
+        Machine {
+            Waiting: u8;
+            Waiting -> Working -on(self.trace)-> Finished;
+            Working -abort(status: u32)-> Waiting;
+        }
+    });
+    assert!(!machine.is_isomorphic(&retyped));
+    let reversed = parse(quote::quote! {
+        Machine {
+            Waiting: u8;
+            Waiting -> Working -on(self.trace)-> Finished;
+            Waiting -abort(status: u16)-> Working;
+        }
+    });
+    assert!(!machine.is_isomorphic(&reversed));
+}
@@ -1,18 +1,29 @@
 use derive_syn_parse::Parse;
-use proc_macro2::Ident;
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::ToTokens;
 use syn::{
     braced,
-    parse::{Parse, ParseStream},
-    token, Attribute, LitStr, Token, Type, Visibility,
+    parse::{discouraged::Speculative as _, Parse, ParseStream},
+    punctuated::Punctuated,
+    token, Attribute, Expr, LitStr, Token, Type, Visibility,
 };
 
-use crate::util::OuterDocString;
-
 pub mod pun {
     // `-->` cannot be custom punctuation because the first Minus token is Alone
     syn::custom_punctuation!(ShortArrow, ->);
 }
 
+pub mod kw {
+    syn::custom_keyword!(on);
+    syn::custom_keyword!(ensures);
+    syn::custom_keyword!(requires);
+    syn::custom_keyword!(extends);
+    syn::custom_keyword!(remove);
+    syn::custom_keyword!(same_rank);
+    syn::custom_keyword!(rank);
+    syn::custom_keyword!(context);
+}
+
 #[test]
 fn parse_dsl() {
     let dsl: Dsl = syn::parse_quote! {
@@ -26,6 +37,9 @@ fn parse_dsl() {
             Foo;
             /// This node has data associated with it
             Bar: String;
+            /// This node has named fields
+            Qux { ttl: u64, reason: String }
+            Quux {};
 
             /// an edge
             Foo -> Bar;
@@ -33,10 +47,55 @@ fn parse_dsl() {
             /// many edges
             Foo --> Bar -> Baz; // the arrow length doesn't mean anything
 
+            /// edges from several states
+            Foo & Bar -> Baz -> Qux;
+
+            /// edges in both directions
+            Foo <-> Bar <-open/close-> Baz;
+
             Foo -"with inline docs"-> Bar;
 
             /// This documentation is shared among the edges
             And -"some"-> Inline -"documentation"-> Too;
+
+            Bar -on(self.log_bar)-> Baz -on(log_baz)-> Foo;
+
+            /// Checked with `debug_assert!` after transitioning
+            Foo -ensures(data.len() > 0)-> Bar;
+
+            /// Checked with `debug_assert!` before transitioning
+            Bar -requires(data.len() > 0)-> Foo;
+
+            /// Several transitions between the same states
+            Foo -approve-> Bar;
+            Foo -reject(reason: String)-> Baz;
+
+            /// Only with the feature
+            #[cfg(feature = "admin")]
+            Foo -> Admin;
+
+            /// From every other node
+            _ -> Fatal;
+            * -on(self.log_panic)-> Panicked;
+
+            extends Base;
+            /// A node may be called `extends`
+            extends;
+
+            context: Config;
+            /// Or `context`, without data
+            context;
+
+            /// A type alias
+            type Payload = Vec<u8>;
+
+            remove Foo -> Bar;
+            remove -> Foo;
+
+            same_rank(Foo, Bar);
+            rank(Foo = source, Baz = sink);
+            /// A node may be called `rank`
+            rank;
         }
     };
     dbg!(dsl);
@@ -70,15 +129,95 @@ impl Parse for Dsl {
     }
 }
 
+impl Dsl {
+    /// Like [`Parse::parse`], but statements which fail to parse are skipped
+    /// up to the next `;`, and their errors are added to `errors`.
+    pub fn parse_lenient(input: ParseStream, errors: &mut Vec<syn::Error>) -> syn::Result<Self> {
+        let content;
+        Ok(Self {
+            attrs: input.call(Attribute::parse_outer)?,
+            vis: input.parse()?,
+            name: input.parse()?,
+            brace_token: braced!(content in input),
+            stmts: {
+                let mut stmts = vec![];
+                while !content.is_empty() {
+                    let fork = content.fork();
+                    match fork.parse() {
+                        Ok(stmt) => {
+                            content.advance_to(&fork);
+                            stmts.push(stmt)
+                        }
+                        Err(e) => {
+                            errors.push(e);
+                            skip_past(
+                                &content,
+                                |it| matches!(it, TokenTree::Punct(p) if p.as_char() == ';'),
+                            )?
+                        }
+                    }
+                }
+                stmts
+            },
+        })
+    }
+}
+
+/// Skip token trees up to and including the first one matching `f`, or to the end of `input`.
+pub fn skip_past(input: ParseStream, f: impl Fn(&TokenTree) -> bool) -> syn::Result<()> {
+    input.step(|cursor| {
+        let mut rest = *cursor;
+        while let Some((tt, next)) = rest.token_tree() {
+            rest = next;
+            if f(&tt) {
+                break;
+            }
+        }
+        Ok(((), rest))
+    })
+}
+
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 #[allow(clippy::large_enum_variant)]
 pub enum Stmt {
     Edges(StmtEdges),
+    Wildcard(StmtWildcard),
     Node(StmtNode),
+    Extends(StmtExtends),
+    Context(StmtContext),
+    Type(StmtType),
+    Remove(StmtRemove),
+    SameRank(StmtSameRank),
+    Rank(StmtRank),
 }
 
 impl Parse for Stmt {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(kw::extends) && input.peek2(syn::Ident) {
+            return Ok(Self::Extends(input.parse()?));
+        }
+        if input.peek(kw::context) && input.peek2(Token![:]) {
+            return Ok(Self::Context(input.parse()?));
+        }
+        let fork = input.fork();
+        if fork.call(Attribute::parse_outer).is_ok() && fork.peek(Token![type]) {
+            return Ok(Self::Type(input.parse()?));
+        }
+        if input.peek(kw::remove) && input.peek2(syn::Ident) {
+            return Ok(Self::Remove(input.parse()?));
+        }
+        if input.peek(kw::same_rank) && input.peek2(token::Paren) {
+            return Ok(Self::SameRank(input.parse()?));
+        }
+        if input.peek(kw::rank) && input.peek2(token::Paren) {
+            return Ok(Self::Rank(input.parse()?));
+        }
+        let fork = input.fork();
+        if fork.call(Attribute::parse_outer).is_ok()
+            && (fork.peek(Token![_]) || fork.peek(Token![*]))
+        {
+            return Ok(Self::Wildcard(input.parse()?));
+        }
         // bounded fork
         if input.fork().parse::<StmtNode>().is_ok() {
             return Ok(Self::Node(input.parse()?));
@@ -90,9 +229,13 @@ impl Parse for Stmt {
 #[derive(Parse)]
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 pub struct StmtEdges {
-    #[call(OuterDocString::parse_many)]
-    pub attrs: Vec<OuterDocString>,
+    /// `#[doc]` and `#[cfg]` attributes.
+    #[call(Attribute::parse_outer)]
+    pub attrs: Vec<Attribute>,
     pub from: Ident,
+    /// `& Other`, further states with the same first transition.
+    #[call(Self::parse_fan_in)]
+    pub fan_in: Vec<(Token![&], Ident)>,
     pub edge: Edge,
     pub to: Ident,
     #[call(Self::parse_rest)]
@@ -101,6 +244,13 @@ pub struct StmtEdges {
 }
 
 impl StmtEdges {
+    fn parse_fan_in(input: ParseStream) -> syn::Result<Vec<(Token![&], Ident)>> {
+        let mut fan_in = vec![];
+        while input.peek(Token![&]) {
+            fan_in.push((input.parse()?, input.parse()?))
+        }
+        Ok(fan_in)
+    }
     fn parse_rest(input: ParseStream) -> syn::Result<Vec<(Edge, Ident)>> {
         let mut rest = vec![];
         while !input.peek(Token![;]) {
@@ -110,34 +260,256 @@ impl StmtEdges {
     }
 }
 
+/// `_ -> To;` or `* -> To;`, an edge from every other state.
 #[derive(Parse)]
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct StmtWildcard {
+    /// `#[doc]` and `#[cfg]` attributes, as for [`StmtEdges`].
+    #[call(Attribute::parse_outer)]
+    pub attrs: Vec<Attribute>,
+    pub wildcard: Wildcard,
+    pub edge: Edge,
+    pub to: Ident,
+    pub semi: Token![;],
+}
+
+#[derive(Parse, derive_quote_to_tokens::ToTokens)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub enum Wildcard {
+    #[peek(Token![_], name = "_")]
+    Underscore(Token![_]),
+    #[peek(Token![*], name = "*")]
+    Star(Token![*]),
+}
+
+/// `State;`, `State: Type;` or `State { name: Type }`, where the `;` is optional.
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 pub struct StmtNode {
-    #[call(OuterDocString::parse_many)]
-    pub attrs: Vec<OuterDocString>,
+    /// `#[doc]` and `#[fsmentry]` attributes.
+    pub attrs: Vec<Attribute>,
     pub ident: Ident,
     pub colon: Option<Token![:]>,
-    #[parse_if(colon.is_some())]
     pub ty: Option<Type>,
+    pub fields: Option<Fields>,
+    pub semi: Option<Token![;]>,
+}
+
+impl Parse for StmtNode {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ident = input.parse()?;
+        let colon = input.parse::<Option<Token![:]>>()?;
+        let ty = match colon {
+            Some(_) => Some(input.parse()?),
+            None => None,
+        };
+        let fields = match colon.is_none() && input.peek(token::Brace) {
+            true => Some(input.parse()?),
+            false => None,
+        };
+        let semi = match fields {
+            Some(_) => input.parse()?,
+            None => Some(input.parse()?),
+        };
+        Ok(Self {
+            attrs,
+            ident,
+            colon,
+            ty,
+            fields,
+            semi,
+        })
+    }
+}
+
+/// `{ ttl: Duration, reason: String }`
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct Fields {
+    #[brace]
+    pub brace: token::Brace,
+    #[inside(brace)]
+    #[call(Punctuated::parse_terminated)]
+    pub fields: Punctuated<Param, Token![,]>,
+}
+
+/// `extends Base;`
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct StmtExtends {
+    pub extends: kw::extends,
+    pub base: Ident,
     pub semi: Token![;],
 }
 
-#[derive(Parse, derive_quote_to_tokens::ToTokens)]
+/// `context: Type;`, data belonging to the whole machine rather than one state.
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct StmtContext {
+    pub context: kw::context,
+    pub colon: Token![:],
+    pub ty: Type,
+    pub semi: Token![;],
+}
+
+/// `type Name = Type;`, which states may refer to.
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct StmtType {
+    /// `#[doc]` attributes.
+    #[call(Attribute::parse_outer)]
+    pub attrs: Vec<Attribute>,
+    pub type_token: Token![type],
+    pub ident: Ident,
+    pub eq: Token![=],
+    pub ty: Type,
+    pub semi: Token![;],
+}
+
+/// `remove From -> To;`
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct StmtRemove {
+    pub remove: kw::remove,
+    pub from: Ident,
+    pub edge: Edge,
+    pub to: Ident,
+    pub semi: Token![;],
+}
+
+/// `same_rank(A, B, C);`, a layout hint for diagrams.
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct StmtSameRank {
+    pub same_rank: kw::same_rank,
+    #[paren]
+    pub paren: token::Paren,
+    #[inside(paren)]
+    #[call(Punctuated::parse_terminated)]
+    pub nodes: Punctuated<Ident, Token![,]>,
+    pub semi: Token![;],
+}
+
+/// `rank(A = source, B = sink);`, a layout hint for diagrams.
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct StmtRank {
+    pub rank: kw::rank,
+    #[paren]
+    pub paren: token::Paren,
+    #[inside(paren)]
+    #[call(Punctuated::parse_terminated)]
+    pub hints: Punctuated<RankHint, Token![,]>,
+    pub semi: Token![;],
+}
+
+/// `A = source`, where the rank is one of `min`, `max`, `source` or `sink`.
+#[derive(Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct RankHint {
+    pub node: Ident,
+    pub eq: Token![=],
+    pub rank: Ident,
+}
+
+#[derive(Clone, Parse, derive_quote_to_tokens::ToTokens)]
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 pub enum Edge {
     #[peek(pun::ShortArrow, name = "->")]
     Short(pun::ShortArrow),
     #[peek_with(minus_then_arrow, name = "-->")]
     Long(Token![-], pun::ShortArrow),
+    #[peek_with(minus_then_on, name = "-on(..)->")]
+    Callback(CallbackArrow),
+    #[peek_with(minus_then_ensures, name = "-ensures(..)->")]
+    Ensures(EnsuresArrow),
+    #[peek_with(minus_then_requires, name = "-requires(..)->")]
+    Requires(RequiresArrow),
+    #[peek_with(minus_then_ident, name = "-method->")]
+    Named(NamedArrow),
+    #[peek_with(larrow_then_gt, name = "<->")]
+    Both(BothArrow),
+    #[peek(Token![<-], name = "<-forward/backward->")]
+    NamedBoth(NamedBothArrow),
     #[peek(Token![-], name = r#"-"..."->"#)]
     Documented(DocumentedArrow),
 }
 
+impl Edge {
+    /// The transition method, if it was named explicitly with `-method->`.
+    pub fn method(&self) -> Option<Ident> {
+        match self {
+            Edge::Named(NamedArrow { method, .. })
+            | Edge::NamedBoth(NamedBothArrow {
+                forward: method, ..
+            }) => Some(method.clone()),
+            Edge::Short(_)
+            | Edge::Long(..)
+            | Edge::Callback(_)
+            | Edge::Ensures(_)
+            | Edge::Requires(_)
+            | Edge::Documented(_)
+            | Edge::Both(_) => None,
+        }
+    }
+    /// Splits `<->` into arrows for each direction, with the names given to them.
+    pub fn split(self) -> (Edge, Option<Edge>) {
+        match self {
+            Edge::Both(BothArrow { left, gt }) => {
+                let spans = [left.spans[1], gt.span];
+                (
+                    Edge::Short(pun::ShortArrow { spans }),
+                    Some(Edge::Short(pun::ShortArrow { spans })),
+                )
+            }
+            Edge::NamedBoth(NamedBothArrow {
+                left,
+                forward,
+                slash: _,
+                backward,
+                arrow,
+            }) => {
+                let named = |method| {
+                    Edge::Named(NamedArrow {
+                        minus: Token![-](left.spans[1]),
+                        method,
+                        params: None,
+                        arrow,
+                    })
+                };
+                (named(forward), Some(named(backward)))
+            }
+            edge => (edge, None),
+        }
+    }
+}
+
 fn minus_then_arrow(input: ParseStream) -> bool {
     input.peek(Token![-]) && input.peek2(pun::ShortArrow)
 }
 
-#[derive(Parse, derive_quote_to_tokens::ToTokens)]
+fn minus_then_on(input: ParseStream) -> bool {
+    input.peek(Token![-]) && input.peek2(kw::on) && input.peek3(token::Paren)
+}
+
+fn minus_then_ensures(input: ParseStream) -> bool {
+    input.peek(Token![-]) && input.peek2(kw::ensures) && input.peek3(token::Paren)
+}
+
+fn minus_then_requires(input: ParseStream) -> bool {
+    input.peek(Token![-]) && input.peek2(kw::requires) && input.peek3(token::Paren)
+}
+
+fn minus_then_ident(input: ParseStream) -> bool {
+    input.peek(Token![-]) && input.peek2(syn::Ident)
+}
+
+fn larrow_then_gt(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Token![<-]>().is_ok() && fork.peek(Token![>])
+}
+
+#[derive(Clone, Parse, derive_quote_to_tokens::ToTokens)]
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 pub struct DocumentedArrow {
     pub minus: Token![-],
@@ -152,6 +524,164 @@ fn not_short_arrow(input: ParseStream) -> bool {
     !input.peek(pun::ShortArrow)
 }
 
+/// `-method->`, naming the transition method, so there may be several between two states.
+///
+/// `-method(reason: String)->` also takes arguments, which are passed to a hook of the same name.
+#[derive(Clone, Parse, derive_quote_to_tokens::ToTokens)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct NamedArrow {
+    pub minus: Token![-],
+    pub method: Ident,
+    #[peek(token::Paren)]
+    pub params: Option<Params>,
+    pub arrow: pun::ShortArrow,
+}
+
+/// `<->`, transitions in both directions between two states.
+///
+/// Not punctuation, because `quote!` separates the `<-`.
+#[derive(Clone, Parse, derive_quote_to_tokens::ToTokens)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct BothArrow {
+    pub left: Token![<-],
+    pub gt: Token![>],
+}
+
+/// `<-forward/backward->`, naming the transition method in each direction.
+#[derive(Clone, Parse, derive_quote_to_tokens::ToTokens)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct NamedBothArrow {
+    pub left: Token![<-],
+    pub forward: Ident,
+    pub slash: Token![/],
+    pub backward: Ident,
+    pub arrow: pun::ShortArrow,
+}
+
+/// `(reason: String, code: u16)`
+#[derive(Clone, Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct Params {
+    #[paren]
+    pub paren: token::Paren,
+    #[inside(paren)]
+    #[call(Punctuated::parse_terminated)]
+    pub params: Punctuated<Param, Token![,]>,
+}
+
+impl ToTokens for Params {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self { paren, params } = self;
+        paren.surround(tokens, |tokens| params.to_tokens(tokens));
+    }
+}
+
+#[derive(Clone, Parse, derive_quote_to_tokens::ToTokens)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct Param {
+    pub name: Ident,
+    pub colon: Token![:],
+    pub ty: Type,
+}
+
+/// `-on(self.method)->`, where the `self.` is optional.
+#[derive(Clone, Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct CallbackArrow {
+    pub minus: Token![-],
+    pub on: kw::on,
+    #[paren]
+    pub paren: token::Paren,
+    #[inside(paren)]
+    pub self_token: Option<Token![self]>,
+    #[inside(paren)]
+    #[parse_if(self_token.is_some())]
+    pub dot: Option<Token![.]>,
+    #[inside(paren)]
+    pub method: Ident,
+    pub arrow: pun::ShortArrow,
+}
+
+impl ToTokens for CallbackArrow {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self {
+            minus,
+            on,
+            paren,
+            self_token,
+            dot,
+            method,
+            arrow,
+        } = self;
+        minus.to_tokens(tokens);
+        on.to_tokens(tokens);
+        paren.surround(tokens, |tokens| {
+            self_token.to_tokens(tokens);
+            dot.to_tokens(tokens);
+            method.to_tokens(tokens);
+        });
+        arrow.to_tokens(tokens);
+    }
+}
+
+/// `-ensures(data.len() > 0)->`, a postcondition on the destination's data.
+#[derive(Clone, Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct EnsuresArrow {
+    pub minus: Token![-],
+    pub ensures: kw::ensures,
+    #[paren]
+    pub paren: token::Paren,
+    #[inside(paren)]
+    pub expr: Expr,
+    pub arrow: pun::ShortArrow,
+}
+
+impl ToTokens for EnsuresArrow {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self {
+            minus,
+            ensures,
+            paren,
+            expr,
+            arrow,
+        } = self;
+        minus.to_tokens(tokens);
+        ensures.to_tokens(tokens);
+        paren.surround(tokens, |tokens| expr.to_tokens(tokens));
+        arrow.to_tokens(tokens);
+    }
+}
+
+/// `-requires(data.len() > 0)->`, a precondition on the source's data.
+#[derive(Clone, Parse)]
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct RequiresArrow {
+    pub minus: Token![-],
+    pub requires: kw::requires,
+    #[paren]
+    pub paren: token::Paren,
+    #[inside(paren)]
+    pub expr: Expr,
+    pub arrow: pun::ShortArrow,
+}
+
+impl ToTokens for RequiresArrow {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self {
+            minus,
+            requires,
+            paren,
+            expr,
+            arrow,
+        } = self;
+        minus.to_tokens(tokens);
+        requires.to_tokens(tokens);
+        paren.surround(tokens, |tokens| expr.to_tokens(tokens));
+        arrow.to_tokens(tokens);
+    }
+}
+
 #[test]
 fn parse_arrow() {
     assert!(matches!(syn::parse_quote!(->), Edge::Short(_)));
@@ -160,4 +690,31 @@ fn parse_arrow() {
     assert!(matches!(syn::parse_quote!(--"ehlo"->), Edge::Documented(_)));
     assert!(matches!(syn::parse_quote!(-"ehlo"-->), Edge::Documented(_)));
     assert!(matches!(syn::parse_quote!(--"elo"-->), Edge::Documented(_)));
+    assert!(matches!(
+        syn::parse_quote!(-on(self.hello)->),
+        Edge::Callback(_)
+    ));
+    assert!(matches!(syn::parse_quote!(-on(hello)->), Edge::Callback(_)));
+    assert!(matches!(
+        syn::parse_quote!(-ensures(data.len() > 0)->),
+        Edge::Ensures(_)
+    ));
+    assert!(matches!(
+        syn::parse_quote!(-requires(self.retries < 3)->),
+        Edge::Requires(_)
+    ));
+    assert!(matches!(syn::parse_quote!(-approve->), Edge::Named(_)));
+    assert!(matches!(syn::parse_quote!(<->), Edge::Both(_)));
+    assert!(matches!(
+        syn::parse_quote!(<-open/close->),
+        Edge::NamedBoth(_)
+    ));
+    assert!(matches!(syn::parse_quote!(-on->), Edge::Named(_)));
+    assert!(matches!(
+        syn::parse_quote!(-close(reason: String, code: u16)->),
+        Edge::Named(NamedArrow {
+            params: Some(_),
+            ..
+        })
+    ));
 }
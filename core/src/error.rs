@@ -0,0 +1,144 @@
+//! Errors in the definition of a state machine, with stable codes for tools to match on,
+//! rather than on their messages, which may change.
+
+use std::fmt;
+
+use proc_macro2::Span;
+
+/// What kind of problem a [`CoreError`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Invalid syntax, or an invalid value for an option.
+    Syntax,
+    /// Options which may not be used together, or with the rest of the machine.
+    InvalidOption,
+    /// A state declared twice.
+    DuplicateState,
+    /// A transition declared twice.
+    DuplicateEdge,
+    /// Transitions from the same state with the same method.
+    DuplicateMethod,
+    /// A type alias, context or base machine declared twice.
+    DuplicateDeclaration,
+    /// A machine without any states.
+    NoStates,
+    /// A state, machine or transition which isn't defined.
+    Undefined,
+    /// Generated items or methods which would have the same name.
+    NameClash,
+    /// A name which is reserved for generated code.
+    Reserved,
+    /// A transition out of a `#[fsmentry(terminal)]` state.
+    TerminalTransition,
+    /// Transitions which must take the same arguments, but don't.
+    MismatchedArguments,
+    /// Input which means nothing for a state machine, like subgraphs in `DOT`.
+    Unsupported,
+}
+
+impl ErrorKind {
+    /// An identifier for this kind of error, like `E0004`, which won't change between releases.
+    pub const fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Syntax => "E0001",
+            ErrorKind::InvalidOption => "E0002",
+            ErrorKind::DuplicateState => "E0003",
+            ErrorKind::DuplicateEdge => "E0004",
+            ErrorKind::DuplicateMethod => "E0005",
+            ErrorKind::DuplicateDeclaration => "E0006",
+            ErrorKind::NoStates => "E0007",
+            ErrorKind::Undefined => "E0008",
+            ErrorKind::NameClash => "E0009",
+            ErrorKind::Reserved => "E0010",
+            ErrorKind::TerminalTransition => "E0011",
+            ErrorKind::MismatchedArguments => "E0012",
+            ErrorKind::Unsupported => "E0013",
+        }
+    }
+}
+
+/// A problem with the definition of a state machine, see [`FSMGenerator::check_dsl`].
+///
+/// The parsers return these as a [`syn::Error`], which they convert to,
+/// and syntax errors convert to them as [`ErrorKind::Syntax`].
+///
+/// [`FSMGenerator::check_dsl`]: crate::FSMGenerator::check_dsl
+#[derive(Debug, Clone)]
+pub struct CoreError {
+    kind: ErrorKind,
+    /// May hold several messages, for problems of the same kind in different places.
+    inner: syn::Error,
+}
+
+impl CoreError {
+    pub(crate) fn new(kind: ErrorKind, span: Span, message: impl fmt::Display) -> Self {
+        Self::with_kind(kind, syn::Error::new(span, message))
+    }
+    pub(crate) fn with_kind(kind: ErrorKind, inner: syn::Error) -> Self {
+        Self { kind, inner }
+    }
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+    /// See [`ErrorKind::code`].
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+    /// Where the (first) problem is.
+    pub fn span(&self) -> Span {
+        self.inner.span()
+    }
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl std::error::Error for CoreError {}
+
+impl From<syn::Error> for CoreError {
+    fn from(inner: syn::Error) -> Self {
+        Self::with_kind(ErrorKind::Syntax, inner)
+    }
+}
+
+impl From<CoreError> for syn::Error {
+    fn from(value: CoreError) -> Self {
+        value.inner
+    }
+}
+
+#[test]
+fn error_kinds() {
+    use crate::FSMGenerator;
+    let kinds = [
+        ("Machine", ErrorKind::Syntax),
+        ("Machine { A; A; }", ErrorKind::DuplicateState),
+        ("Machine { A -> B; A -> B; }", ErrorKind::DuplicateEdge),
+        (
+            "Machine { A -go-> B; A -go-> C; }",
+            ErrorKind::DuplicateMethod,
+        ),
+        ("Machine { }", ErrorKind::NoStates),
+        ("Machine { A; same_rank(A, B); }", ErrorKind::Undefined),
+        ("State { A -> B; }", ErrorKind::NameClash),
+        (
+            "Machine { #[fsmentry(terminal)] A; A -> B; }",
+            ErrorKind::TerminalTransition,
+        ),
+    ];
+    for (dsl, kind) in kinds {
+        let errors = FSMGenerator::check_dsl(dsl).unwrap_err();
+        assert_eq!(
+            errors.iter().map(CoreError::kind).collect::<Vec<_>>(),
+            [kind],
+            "{}",
+            dsl
+        );
+    }
+    assert_eq!(ErrorKind::DuplicateEdge.code(), "E0004");
+    assert!(FSMGenerator::check_dsl("Machine { A -> B; }").is_ok());
+}
@@ -0,0 +1,214 @@
+// A tree-sitter grammar for the fsmentry DSL, for highlighting standalone `.fsm` files.
+// Print it with `fsmentry --emit tree-sitter`.
+
+const sepBy = (sep, rule) => optional(seq(rule, repeat(seq(sep, rule))));
+
+module.exports = grammar({
+  name: 'fsmentry',
+
+  extras: $ => [/\s/, $.line_comment, $.block_comment],
+
+  word: $ => $.identifier,
+
+  rules: {
+    source_file: $ => seq(repeat($.inner_attribute), repeat($.machine)),
+
+    // #![fsmentry(..)], shared by every machine
+    inner_attribute: $ => choice($.inner_doc_comment, seq('#', '!', '[', $._tokens, ']')),
+
+    // #[fsmentry(..)]
+    // pub Machine { .. }
+    machine: $ => seq(
+      repeat($.attribute),
+      optional($.visibility),
+      field('name', $.identifier),
+      '{',
+      repeat($._statement),
+      '}',
+    ),
+
+    _statement: $ => choice(
+      $.extends,
+      $.context,
+      $.type_alias,
+      $.remove,
+      $.same_rank,
+      $.rank,
+      $.node,
+      $.edges,
+      $.wildcard_edge,
+    ),
+
+    // extends Base;
+    extends: $ => seq('extends', field('base', $.identifier), ';'),
+
+    // context: Type;
+    context: $ => seq('context', ':', field('type', $.type), ';'),
+
+    // type Name = Type;
+    type_alias: $ => seq(
+      repeat($.attribute),
+      'type',
+      field('name', $.identifier),
+      '=',
+      field('type', $.type),
+      ';',
+    ),
+
+    // remove From -> To;
+    remove: $ => seq(
+      'remove',
+      field('from', $.identifier),
+      $._arrow,
+      field('to', $.identifier),
+      ';',
+    ),
+
+    // same_rank(A, B, C);
+    same_rank: $ => seq(
+      'same_rank',
+      '(',
+      sepBy(',', $.identifier),
+      optional(','),
+      ')',
+      ';',
+    ),
+
+    // rank(A = source, B = sink);
+    rank: $ => seq('rank', '(', sepBy(',', $.rank_hint), optional(','), ')', ';'),
+
+    rank_hint: $ => seq(
+      field('node', $.identifier),
+      '=',
+      field('rank', choice('min', 'max', 'source', 'sink')),
+    ),
+
+    // State;
+    // State: Type;
+    // State { name: Type }
+    node: $ => seq(
+      repeat($.attribute),
+      field('name', $.identifier),
+      choice(
+        seq(optional(seq(':', field('type', $.type))), ';'),
+        seq($.fields, optional(';')),
+      ),
+    ),
+
+    fields: $ => seq('{', sepBy(',', $.parameter), optional(','), '}'),
+
+    // From -> To -> Further;
+    // From & Other -> To;
+    // From <-> To;
+    edges: $ => seq(
+      repeat($.attribute),
+      field('from', $.identifier),
+      repeat(seq('&', field('from', $.identifier))),
+      repeat1(seq($._arrow, field('to', $.identifier))),
+      ';',
+    ),
+
+    // _ -> To; or * -> To;
+    wildcard_edge: $ => seq(
+      repeat($.attribute),
+      field('from', choice('_', '*')),
+      $._arrow,
+      field('to', $.identifier),
+      ';',
+    ),
+
+    _arrow: $ => choice(
+      $.arrow,
+      $.documented_arrow,
+      $.callback_arrow,
+      $.ensures_arrow,
+      $.requires_arrow,
+      $.named_arrow,
+      $.both_arrow,
+    ),
+
+    // -> or -->
+    arrow: $ => choice('->', seq('-', '->')),
+
+    // -"docs"->
+    documented_arrow: $ => seq('-', optional('-'), $.string, optional('-'), '->'),
+
+    // -on(self.method)->
+    callback_arrow: $ => seq(
+      '-',
+      'on',
+      '(',
+      optional(seq($.self, '.')),
+      field('method', $.identifier),
+      ')',
+      '->',
+    ),
+
+    // -ensures(data.len() > 0)->
+    ensures_arrow: $ => seq('-', 'ensures', '(', field('condition', $._tokens), ')', '->'),
+
+    // -requires(data.len() > 0)->
+    requires_arrow: $ => seq('-', 'requires', '(', field('condition', $._tokens), ')', '->'),
+
+    // -method-> or -method(name: Type)->
+    named_arrow: $ => seq(
+      '-',
+      field('method', $.identifier),
+      optional($.parameters),
+      '->',
+    ),
+
+    // <-> or <-forward/backward->
+    both_arrow: $ => choice(
+      '<->',
+      seq('<-', field('forward', $.identifier), '/', field('backward', $.identifier), '->'),
+    ),
+
+    parameters: $ => seq('(', sepBy(',', $.parameter), optional(','), ')'),
+
+    parameter: $ => seq(field('name', $.identifier), ':', field('type', $.type)),
+
+    // Rust types are only recognised well enough to highlight them.
+    type: $ => prec.right(repeat1($._type_part)),
+
+    _type_part: $ => choice(
+      $.identifier,
+      $.lifetime,
+      $.integer,
+      $.self,
+      '::',
+      '&',
+      '*',
+      '!',
+      '+',
+      'mut',
+      'dyn',
+      'impl',
+      seq('<', sepBy(',', $.type), optional(','), '>'),
+      seq('(', sepBy(',', $.type), optional(','), ')'),
+      seq('[', $.type, optional(seq(';', $._tokens)), ']'),
+    ),
+
+    attribute: $ => choice($.doc_comment, seq('#', '[', $._tokens, ']')),
+
+    visibility: $ => seq('pub', optional(seq('(', $._tokens, ')'))),
+
+    _tokens: $ => repeat1(choice(
+      /[^()\[\]{}"]+/,
+      $.string,
+      seq('(', optional($._tokens), ')'),
+      seq('[', optional($._tokens), ']'),
+      seq('{', optional($._tokens), '}'),
+    )),
+
+    self: $ => 'self',
+    identifier: $ => /(r#)?[a-zA-Z_][a-zA-Z0-9_]*/,
+    lifetime: $ => /'[a-zA-Z_][a-zA-Z0-9_]*/,
+    integer: $ => /[0-9][0-9_]*/,
+    string: $ => /"([^"\\]|\\.)*"/,
+    doc_comment: $ => token(prec(1, seq('///', /.*/))),
+    inner_doc_comment: $ => token(prec(1, seq('//!', /.*/))),
+    line_comment: $ => token(seq('//', /.*/)),
+    block_comment: $ => token(seq('/*', /[^*]*\*+([^/*][^*]*\*+)*/, '/')),
+  },
+});
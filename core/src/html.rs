@@ -0,0 +1,217 @@
+use itertools::Itertools as _;
+
+use crate::{
+    util::{json, paragraphs, unparse, OuterDocString},
+    FSMGenerator, NodeData, NodeId,
+};
+
+impl FSMGenerator {
+    /// Render a self-contained HTML page for exploring the state machine.
+    ///
+    /// `svg` should be the output of `dot -Tsvg` on [`Self::dot`].
+    /// Clicking on a state in the diagram (or in the list of states) shows its
+    /// documentation, data, and the states reachable from it.
+    pub fn html(&self, svg: Option<&str>) -> String {
+        let mut states = vec![];
+        for (node, NodeData { ty, docs, .. }) in &self.nodes {
+            let names = |it: Vec<&NodeId>| {
+                let names = it.into_iter().map(|it| json(&it.inner.to_string()));
+                format!("[{}]", names.collect::<Vec<_>>().join(", "))
+            };
+            states.push(format!(
+                "{}: {{ data: {}, docs: {}, incoming: {}, outgoing: {}, reachable: {} }}",
+                json(&node.inner.to_string()),
+                match ty {
+                    Some(ty) => json(&unparse(ty)),
+                    None => String::from("null"),
+                },
+                json(&paragraphs(docs).join("\n\n")),
+                names(self.incoming(node).unwrap_or_default()),
+                names(
+                    self.outgoing(node)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(it, ..)| it)
+                        .dedup()
+                        .collect()
+                ),
+                names(self.reachable(node)),
+            ))
+        }
+        let docs = self
+            .attributes
+            .iter()
+            .filter_map(|attr| OuterDocString::try_from(attr.clone()).ok())
+            .collect::<Vec<_>>();
+        let docs = paragraphs(&docs)
+            .iter()
+            .map(|it| format!("<p>{}</p>", escape(it)))
+            .collect::<String>();
+        let list = self
+            .nodes
+            .keys()
+            .map(|it| format!("<li><button>{}</button></li>", it.inner))
+            .collect::<String>();
+        fill(
+            TEMPLATE,
+            &[
+                ("{name}", &self.ident.to_string()),
+                ("{docs}", &docs),
+                (
+                    "{diagram}",
+                    &svg.map(|it| self.label_svg(it)).unwrap_or_default(),
+                ),
+                ("{list}", &list),
+                ("{states}", &states.join(",\n")),
+            ],
+        )
+    }
+}
+
+/// Substitute each placeholder in a single pass,
+/// so substituted text is never itself substituted.
+fn fill(template: &str, substitutions: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    'outer: while let Some(ix) = rest.find('{') {
+        out.push_str(&rest[..ix]);
+        rest = &rest[ix..];
+        for (placeholder, substitution) in substitutions {
+            if let Some(after) = rest.strip_prefix(placeholder) {
+                out.push_str(substitution);
+                rest = after;
+                continue 'outer;
+            }
+        }
+        out.push('{');
+        rest = &rest[1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{name}</title>
+<style>
+body { font-family: sans-serif; margin: 0; display: flex; min-height: 100vh; }
+main { flex: 1; padding: 1em 2em; overflow: auto; }
+aside { width: 24em; padding: 1em 2em; border-left: 1px solid #ccc; background: #fafafa; }
+#diagram svg { max-width: 100%; height: auto; }
+#diagram g.node { cursor: pointer; }
+#diagram g.node.selected ellipse { fill: #ffe9a8; }
+#list button { border: none; background: none; color: #0645ad; cursor: pointer; padding: 0; font: inherit; }
+#list button.selected { font-weight: bold; }
+pre { white-space: pre-wrap; }
+</style>
+</head>
+<body>
+<main>
+<h1>{name}</h1>
+{docs}
+<div id="diagram">{diagram}</div>
+<h2>States</h2>
+<ul id="list">{list}</ul>
+</main>
+<aside id="details"><p>Select a state to see its details.</p></aside>
+<script>
+const STATES = {
+{states}
+};
+const details = document.getElementById("details");
+function link(name) {
+  const a = document.createElement("a");
+  a.href = "#";
+  a.textContent = name;
+  a.onclick = (event) => { event.preventDefault(); show(name); };
+  return a;
+}
+function section(title, names) {
+  const h = document.createElement("h3");
+  h.textContent = title;
+  details.append(h);
+  if (names.length === 0) {
+    const p = document.createElement("p");
+    p.textContent = "None";
+    details.append(p);
+    return;
+  }
+  const ul = document.createElement("ul");
+  for (const name of names) {
+    const li = document.createElement("li");
+    li.append(link(name));
+    ul.append(li);
+  }
+  details.append(ul);
+}
+function show(name) {
+  const state = STATES[name];
+  details.replaceChildren();
+  const h = document.createElement("h2");
+  h.textContent = name;
+  details.append(h);
+  const data = document.createElement("p");
+  if (state.data === null) {
+    data.textContent = "This state holds no data.";
+  } else {
+    data.append("Data: ");
+    const code = document.createElement("code");
+    code.textContent = state.data;
+    data.append(code);
+  }
+  details.append(data);
+  if (state.docs) {
+    const docs = document.createElement("pre");
+    docs.textContent = state.docs;
+    details.append(docs);
+  }
+  section("Transitions to", state.outgoing);
+  section("Transitions from", state.incoming);
+  section("Reachable states", state.reachable);
+  for (const el of document.querySelectorAll(".selected")) el.classList.remove("selected");
+  for (const el of document.querySelectorAll("#diagram g.node, #list button")) {
+    const label = el.querySelector("title") || el;
+    if (label.textContent === name) el.classList.add("selected");
+  }
+}
+for (const node of document.querySelectorAll("#diagram g.node")) {
+  node.onclick = () => show(node.querySelector("title").textContent);
+}
+for (const button of document.querySelectorAll("#list button")) {
+  button.onclick = () => show(button.textContent);
+}
+</script>
+</body>
+</html>
+"##;
+
+#[test]
+fn html() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            /// Cars <b>must</b> stop
+            pub TrafficLight {
+                Red -> Green -> Amber -> Red;
+                Green: Vec<u8>;
+                Off;
+            }
+        })
+        .unwrap();
+    let html = generator.html(None);
+    assert!(html.contains("<p>Cars &lt;b&gt;must&lt;/b&gt; stop</p>"));
+    assert!(html.contains(
+        r#""Green": { data: "Vec\u003cu8\u003e", docs: "", incoming: ["Red"], outgoing: ["Amber"], reachable: ["Amber", "Green", "Red"] }"#
+    ));
+    assert!(html
+        .contains(r#""Off": { data: null, docs: "", incoming: [], outgoing: [], reachable: [] }"#));
+}
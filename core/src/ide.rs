@@ -0,0 +1,329 @@
+//! Editor support for the DSL, used by the `fsmentry-lsp` language server.
+//!
+//! Requires the `ide` feature, which enables span locations in `proc-macro2`.
+
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use syn::parse::{ParseStream, Parser as _};
+
+use crate::{
+    util::{paragraphs, unparse},
+    ErrorKind, FSMGenerator, NodeId,
+};
+
+/// A zero-indexed line and column, in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+/// A half-open span of source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    fn of(span: Span) -> Self {
+        let position = |it: proc_macro2::LineColumn| Position {
+            line: it.line.saturating_sub(1),
+            character: it.column,
+        };
+        Self {
+            start: position(span.start()),
+            end: position(span.end()),
+        }
+    }
+    fn contains(&self, position: Position) -> bool {
+        self.start <= position && position < self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with the source, see [`FSMGenerator::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    /// See [`ErrorKind::code`], [`None`] for warnings.
+    pub code: Option<&'static str>,
+    pub message: String,
+}
+
+/// An action to show above a state machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeLens {
+    pub range: Range,
+    pub title: String,
+    /// A [`mermaid`](FSMGenerator::mermaid) diagram of the state machine.
+    pub mermaid: String,
+}
+
+/// A mention of a state or state machine in the source.
+#[derive(Debug)]
+struct Occurrence {
+    range: Range,
+    /// Index into [`Analysis::machines`].
+    machine: usize,
+    /// [`None`] if this mentions the machine itself.
+    state: Option<NodeId>,
+}
+
+/// The result of parsing a whole file of the DSL, as [`FSMGenerator::parse_dsl_lenient`] does.
+#[derive(Debug)]
+pub struct Analysis {
+    machines: Vec<FSMGenerator>,
+    occurrences: Vec<Occurrence>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let tokens = match source.parse::<TokenStream>() {
+            Ok(it) => it,
+            Err(e) => {
+                return Self {
+                    machines: vec![],
+                    occurrences: vec![],
+                    diagnostics: vec![Diagnostic {
+                        range: Range::of(e.span()),
+                        severity: Severity::Error,
+                        code: Some(ErrorKind::Syntax.code()),
+                        message: e.to_string(),
+                    }],
+                }
+            }
+        };
+        let mut errors = vec![];
+        let machines = (|input: ParseStream| FSMGenerator::parse_lenient(input, &mut errors))
+            .parse2(tokens.clone())
+            .expect("lenient parsing never fails");
+        let errors = errors.into_iter().flat_map(|it| {
+            let code = it.code();
+            syn::Error::from(it).into_iter().map(move |it| Diagnostic {
+                range: Range::of(it.span()),
+                severity: Severity::Error,
+                code: Some(code),
+                message: it.to_string(),
+            })
+        });
+        let warnings = machines
+            .iter()
+            .flat_map(FSMGenerator::warnings)
+            .map(|it| Diagnostic {
+                range: Range::of(it.span),
+                severity: Severity::Warning,
+                code: None,
+                message: it.message.clone(),
+            });
+        let diagnostics = errors.chain(warnings).collect();
+        let occurrences = occurrences(tokens, &machines);
+        Self {
+            machines,
+            occurrences,
+            diagnostics,
+        }
+    }
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+    /// Where the state or state machine at `position` is declared,
+    /// or first mentioned if it is declared implicitly.
+    pub fn definition(&self, position: Position) -> Option<Range> {
+        let Occurrence { machine, state, .. } = self.at(position)?;
+        let machine = &self.machines[*machine];
+        match state {
+            Some(state) => {
+                let (declared, _) = machine.nodes.get_key_value(state)?;
+                Some(Range::of(declared.inner.span()))
+            }
+            None => Some(Range::of(machine.ident.span())),
+        }
+    }
+    /// Markdown describing the state or state machine at `position`.
+    pub fn hover(&self, position: Position) -> Option<String> {
+        let Occurrence { machine, state, .. } = self.at(position)?;
+        let machine = &self.machines[*machine];
+        let Some(state) = state else {
+            let docs = machine
+                .attributes
+                .iter()
+                .filter_map(|it| crate::util::OuterDocString::try_from(it.clone()).ok())
+                .collect::<Vec<_>>();
+            let mut s = format!("State machine `{}`", machine.ident);
+            for paragraph in paragraphs(&docs) {
+                s.push_str(&format!("\n\n{}", paragraph))
+            }
+            return Some(s);
+        };
+        let data = &machine.nodes[state];
+        let mut s = match &data.ty {
+            Some(ty) => format!("State `{}` with data `{}`", state.inner, unparse(ty)),
+            None => format!("State `{}`", state.inner),
+        };
+        for paragraph in paragraphs(&data.docs) {
+            s.push_str(&format!("\n\n{}", paragraph))
+        }
+        let names = |nodes: Vec<&NodeId>| {
+            let names = nodes.iter().map(|it| format!("`{}`", it.inner));
+            names.collect::<Vec<_>>().join(", ")
+        };
+        if let Some(outgoing) = machine.outgoing(state) {
+            let mut outgoing = outgoing.into_iter().map(|(it, ..)| it).collect::<Vec<_>>();
+            outgoing.dedup();
+            s.push_str(&format!("\n\nTransitions to {}", names(outgoing)))
+        }
+        let reachable = machine.reachable(state);
+        match reachable.is_empty() {
+            true => s.push_str("\n\nNo other states are reachable"),
+            false => s.push_str(&format!("\n\nCan reach {}", names(reachable))),
+        }
+        Some(s)
+    }
+    /// A diagram preview for each state machine.
+    pub fn code_lenses(&self) -> Vec<CodeLens> {
+        self.machines
+            .iter()
+            .map(|it| CodeLens {
+                range: Range::of(it.ident.span()),
+                title: String::from("Preview diagram"),
+                mermaid: it.mermaid(),
+            })
+            .collect()
+    }
+    fn at(&self, position: Position) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|it| it.range.contains(position))
+    }
+}
+
+/// Find the states and machines mentioned in the top level of each machine definition.
+fn occurrences(tokens: TokenStream, machines: &[FSMGenerator]) -> Vec<Occurrence> {
+    let find = |name: &proc_macro2::Ident| machines.iter().position(|it| it.ident == *name);
+    let mut occurrences = vec![];
+    let mut name = None;
+    for tt in tokens {
+        match tt {
+            TokenTree::Ident(ident) => name = Some(ident),
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                let Some(machine) = name.take().as_ref().and_then(find) else {
+                    continue;
+                };
+                occurrences.push(Occurrence {
+                    range: Range::of(machines[machine].ident.span()),
+                    machine,
+                    state: None,
+                });
+                let body = group.stream().into_iter().collect::<Vec<_>>();
+                let mut in_type = false;
+                for (ix, tt) in body.iter().enumerate() {
+                    let prev = ix.checked_sub(1).map(|it| &body[it]);
+                    let punct = |tt: Option<&TokenTree>, c| matches!(tt, Some(TokenTree::Punct(p)) if p.as_char() == c);
+                    match tt {
+                        TokenTree::Punct(p) if p.as_char() == ';' => in_type = false,
+                        // `State: Type;`, but not a path
+                        TokenTree::Punct(p)
+                            if p.as_char() == ':'
+                                && !punct(prev, ':')
+                                && !punct(body.get(ix + 1), ':') =>
+                        {
+                            in_type = true
+                        }
+                        TokenTree::Ident(ident) if !in_type => {
+                            // `extends Base;`
+                            if ident == "extends"
+                                && matches!(body.get(ix + 1), Some(TokenTree::Ident(_)))
+                            {
+                                continue;
+                            }
+                            if let (Some(TokenTree::Ident(kw)), Some(base)) = (prev, find(ident)) {
+                                if kw == "extends" {
+                                    occurrences.push(Occurrence {
+                                        range: Range::of(ident.span()),
+                                        machine: base,
+                                        state: None,
+                                    });
+                                    continue;
+                                }
+                            }
+                            // `-method->`
+                            if punct(prev, '-') {
+                                continue;
+                            }
+                            let state = NodeId::from(ident.clone());
+                            if machines[machine].nodes.contains_key(&state) {
+                                occurrences.push(Occurrence {
+                                    range: Range::of(ident.span()),
+                                    machine,
+                                    state: Some(state),
+                                })
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => name = None,
+        }
+    }
+    occurrences
+}
+
+#[test]
+fn ide() {
+    let source = "\
+/// Docs
+Door {
+    /// Closed for business
+    Closed;
+    Open: Vec<u8>;
+    Open -> Closed -close-> Locked;
+}
+Vault {
+    extends Door;
+    Locked -> Open;
+}
+";
+    let analysis = Analysis::new(source);
+    assert_eq!(analysis.diagnostics(), []);
+    let at = |line, character| Position { line, character };
+    let range = |line, character, len| Range {
+        start: at(line, character),
+        end: at(line, character + len),
+    };
+
+    // `Closed` in the edge goes to its declaration
+    assert_eq!(analysis.definition(at(5, 13)), Some(range(3, 4, 6)));
+    // the machine in `extends`
+    assert_eq!(analysis.definition(at(8, 13)), Some(range(1, 0, 4)));
+    // inherited states go to the base
+    assert_eq!(analysis.definition(at(9, 14)), Some(range(4, 4, 4)));
+    // types and transition methods aren't states
+    assert_eq!(analysis.definition(at(4, 10)), None);
+    assert_eq!(analysis.definition(at(5, 20)), None);
+
+    assert_eq!(
+        analysis.hover(at(3, 4)).unwrap(),
+        "State `Closed`\n\nClosed for business\n\nTransitions to `Locked`\n\nCan reach `Locked`"
+    );
+    assert_eq!(
+        analysis.hover(at(1, 0)).unwrap(),
+        "State machine `Door`\n\nDocs"
+    );
+    assert_eq!(analysis.code_lenses().len(), 2);
+
+    let analysis = Analysis::new("Door { Open => Closed; Open -> Closed; Open -> ; }");
+    assert_eq!(analysis.diagnostics().len(), 2);
+    assert!(analysis
+        .diagnostics()
+        .iter()
+        .all(|it| it.code == Some(ErrorKind::Syntax.code())));
+    assert_eq!(analysis.code_lenses().len(), 1);
+}
@@ -2,17 +2,45 @@
 //!
 //! See the [`fsmentry` crate](https://docs.rs/fsmentry).
 
+mod args;
+mod diff;
 mod dsl;
+mod error;
+mod html;
+#[cfg(any(test, feature = "ide"))]
+pub mod ide;
+mod markdown;
+mod registry;
+mod report;
+mod svg;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(test)]
+mod tests;
+mod tree_sitter;
 mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod xstate;
 
-use heck::{ToSnakeCase as _, ToUpperCamelCase as _};
-use proc_macro2::{Ident, Span};
+use heck::{ToLowerCamelCase as _, ToSnakeCase as _, ToUpperCamelCase as _};
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::{quote, ToTokens};
-use std::{collections::BTreeMap, iter};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    iter,
+    time::Duration,
+};
 use syn::{
-    parse::ParseStream, parse_quote, punctuated::Punctuated, spanned::Spanned as _, token, Token,
+    ext::IdentExt as _, parse::ParseStream, parse_quote, punctuated::Punctuated,
+    spanned::Spanned as _, token, Token,
 };
-use util::OuterDocString;
+use util::{unparse, OuterDocString};
+
+pub use error::{CoreError, ErrorKind};
+pub use registry::RegistryEntry;
+pub use report::Report;
+pub use svg::{IncludeSvg, RenderError, RenderJob};
 
 #[derive(Hash, PartialEq, Eq, Debug, Clone, PartialOrd, Ord)]
 struct NodeId {
@@ -20,30 +48,604 @@ struct NodeId {
 }
 
 impl From<Ident> for NodeId {
+    /// `r#try` is the state `try`, so names are shown without the `r#`.
     fn from(inner: Ident) -> Self {
-        Self { inner }
+        Self {
+            inner: inner.unraw(),
+        }
     }
 }
 
 impl NodeId {
-    pub fn transition_fn(&self) -> Ident {
-        self.inner.snake_case()
+    /// The transition method into this node, unless the edge names it explicitly.
+    pub fn transition_fn(&self, case: &MethodCase) -> Ident {
+        match case {
+            MethodCase::Snake => self.inner.snake_case(),
+            MethodCase::Camel => self
+                .inner
+                .derived(self.inner.to_string().to_lower_camel_case()),
+            MethodCase::Preserve => self.inner.derived(self.inner.to_string()),
+            MethodCase::Format(format) => {
+                let name = self.inner.to_string();
+                self.inner.derived(
+                    format
+                        .replace("{snake}", &name.to_snake_case())
+                        .replace("{camel}", &name.to_lower_camel_case())
+                        .replace("{preserve}", &name),
+                )
+            }
+        }
     }
     pub fn variant(&self) -> Ident {
         self.inner.UpperCamelCase()
     }
+    pub fn visit_fn(&self) -> Ident {
+        ident(format!("visit_{}", self.inner.to_string().to_snake_case()))
+    }
 }
 
 fn ident(s: impl AsRef<str>) -> Ident {
     Ident::new(s.as_ref(), Span::call_site())
 }
 
+/// An identifier for a name derived from the user's, which is raw if the name is a keyword,
+/// or has a trailing `_` if it is a keyword which can't be raw.
+fn ident_at(name: &str, span: Span) -> Result<Ident, CoreError> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|it| it == '_' || unicode_ident::is_xid_start(it))
+        && chars.all(unicode_ident::is_xid_continue);
+    if !valid || name == "_" {
+        return Err(CoreError::new(
+            ErrorKind::Unsupported,
+            span,
+            format!("`{}` can't be used as a name in rust", name),
+        ));
+    }
+    Ok(match syn::parse_str::<Ident>(name) {
+        Ok(_) => Ident::new(name, span),
+        Err(_) if ["self", "Self", "super", "crate"].contains(&name) => {
+            Ident::new(&format!("{}_", name), span)
+        }
+        Err(_) => Ident::new_raw(name, span),
+    })
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A line of documentation, which is only present under `cfg` if given.
+fn doc_line(line: String, cfg: Option<&TokenStream>) -> TokenStream {
+    match cfg {
+        Some(cfg) => quote!(#[cfg_attr(#cfg, doc = #line)]),
+        None => quote!(#[doc = #line]),
+    }
+}
+
 #[derive(Debug, Clone)]
 struct NodeData {
-    /// Stored as a single tuple member in the state enum.
-    ty: Option<syn::Type>,
+    ty: Option<Data>,
     /// These are attached to each variant.
     docs: Vec<OuterDocString>,
+    /// A method on the hooks trait, called after entering this state.
+    on_enter: Option<Ident>,
+    /// A method on the hooks trait, called before leaving this state.
+    on_exit: Option<Ident>,
+    /// From `#[fsmentry(terminal)]`, this state may not be left.
+    terminal: bool,
+    /// From `#[fsmentry(label = "..")]`, shown in diagrams instead of the state's name.
+    label: Option<String>,
+    /// `#[serde(..)]` attributes, attached to the variant in `State` when it derives `serde` traits.
+    serde: Vec<syn::Attribute>,
+}
+
+/// The data stored in a state.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+enum Data {
+    /// Stored as a single tuple member in the state enum.
+    Tuple(syn::Type),
+    /// Stored as named fields in the state enum,
+    /// and passed in and out of transitions as a tuple.
+    Record(Vec<(Ident, syn::Type)>),
+}
+
+impl Data {
+    /// How transition callbacks receive the data,
+    /// with records as a tuple of references to their fields.
+    fn reference(&self) -> syn::Type {
+        match self {
+            Data::Tuple(ty) => parse_quote!(&#ty),
+            Data::Record(fields) => {
+                let tys = fields.iter().map(|(_, ty)| ty);
+                parse_quote!((#(&#tys,)*))
+            }
+        }
+    }
+}
+
+impl ToTokens for Data {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Data::Tuple(ty) => ty.to_tokens(tokens),
+            Data::Record(fields) => {
+                let fields = fields.iter().map(|(name, ty)| quote!(#name: #ty));
+                tokens.extend(quote!({ #(#fields),* }))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct EdgeData {
+    /// Passed through to the transition function.
+    docs: Vec<OuterDocString>,
+    /// A method on the hooks trait, called before the transition.
+    callback: Option<Ident>,
+    /// Extra arguments to the transition function, passed on to the callback.
+    params: Vec<(Ident, syn::Type)>,
+    /// The transition only exists under this `#[cfg(..)]` predicate.
+    cfg: Option<TokenStream>,
+    /// A postcondition on the destination's data, checked with `debug_assert!`.
+    ensures: Option<syn::Expr>,
+    /// A precondition on the source's data.
+    /// `handle` refuses the event when it doesn't hold,
+    /// and the transition method checks it with `debug_assert!`.
+    requires: Option<syn::Expr>,
+    /// Other attributes, like `#[deprecated]` or `#[inline]`, passed through to the transition function.
+    attrs: Vec<syn::Attribute>,
+    /// From `#[fsmentry(on_mismatch = ..)]` on the edge, overriding the machine's.
+    on_mismatch: Option<OnMismatch>,
+    /// The method was named in the definition, rather than after its destination.
+    named: bool,
+}
+
+/// From `#[fsmentry(derive(machine: [..], state: [..], entry: [..], event: [..]))]`.
+#[derive(Debug, Clone, Default)]
+struct Derives {
+    machine: Vec<syn::Path>,
+    state: Vec<syn::Path>,
+    entry: Vec<syn::Path>,
+    event: Vec<syn::Path>,
+}
+
+impl Derives {
+    fn attr(paths: &[syn::Path]) -> Option<syn::Attribute> {
+        match paths.is_empty() {
+            true => None,
+            false => Some(parse_quote!(#[derive(#(#paths),*)])),
+        }
+    }
+}
+
+/// From `#[fsmentry(defmt = true | false | kind)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Defmt {
+    /// Don't implement `defmt::Format`.
+    #[default]
+    Off,
+    /// Format the state's data, which must also implement `defmt::Format`.
+    Data,
+    /// Only format the name of the state.
+    Kind,
+}
+
+impl syn::parse::Parse for Defmt {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::LitBool) {
+            return match input.parse::<syn::LitBool>()?.value {
+                true => Ok(Self::Data),
+                false => Ok(Self::Off),
+            };
+        }
+        let ident = input.parse::<Ident>()?;
+        match ident == "kind" {
+            true => Ok(Self::Kind),
+            false => Err(syn::Error::new(
+                ident.span(),
+                "expected one of `true`, `false` or `kind`",
+            )),
+        }
+    }
+}
+
+impl args::Value for Defmt {
+    const SHAPE: &'static str = "true | false | kind";
+}
+
+/// From `#[fsmentry(serde = true | false | untagged)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Serde {
+    /// Don't derive `serde::Serialize` or `serde::Deserialize`.
+    #[default]
+    Off,
+    /// Tag each state with its name, as `serde` does by default,
+    /// or as given by `serde_tag` and `serde_content`.
+    Tagged,
+    /// Tell states apart by the shape of their data alone.
+    Untagged,
+}
+
+impl syn::parse::Parse for Serde {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::LitBool) {
+            return match input.parse::<syn::LitBool>()?.value {
+                true => Ok(Self::Tagged),
+                false => Ok(Self::Off),
+            };
+        }
+        let ident = input.parse::<Ident>()?;
+        match ident == "untagged" {
+            true => Ok(Self::Untagged),
+            false => Err(syn::Error::new(
+                ident.span(),
+                "expected one of `true`, `false` or `untagged`",
+            )),
+        }
+    }
+}
+
+impl args::Value for Serde {
+    const SHAPE: &'static str = "true | false | untagged";
+}
+
+/// From `#[fsmentry(conversions = [..])]`,
+/// traits to implement on transition types for the data in their state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Conversion {
+    AsRef,
+    AsMut,
+    Borrow,
+    /// Implies [`Conversion::Borrow`].
+    BorrowMut,
+    Deref,
+    /// Implies [`Conversion::Deref`].
+    DerefMut,
+}
+
+impl syn::parse::Parse for Conversion {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse::<Ident>()?;
+        let all = [
+            ("AsRef", Self::AsRef),
+            ("AsMut", Self::AsMut),
+            ("Borrow", Self::Borrow),
+            ("BorrowMut", Self::BorrowMut),
+            ("Deref", Self::Deref),
+            ("DerefMut", Self::DerefMut),
+        ];
+        match all.iter().find(|(name, _)| ident == name) {
+            Some((_, it)) => Ok(*it),
+            None => Err(syn::Error::new(
+                ident.span(),
+                format!("expected one of {}", <Self as args::Value>::SHAPE),
+            )),
+        }
+    }
+}
+
+impl args::Value for Conversion {
+    const SHAPE: &'static str = "AsRef | AsMut | Borrow | BorrowMut | Deref | DerefMut";
+}
+
+/// What to document instead of a diagram which isn't rendered,
+/// from `#[fsmentry(diagram_fallback = text | none)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum DiagramFallback {
+    /// List the transitions in plain text.
+    #[default]
+    Text,
+    /// Leave the docs without a diagram.
+    None,
+}
+
+impl syn::parse::Parse for DiagramFallback {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse::<Ident>()?;
+        match &*ident.to_string() {
+            "text" => Ok(Self::Text),
+            "none" => Ok(Self::None),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "expected one of `text` or `none`",
+            )),
+        }
+    }
+}
+
+impl args::Value for DiagramFallback {
+    const SHAPE: &'static str = "text | none";
+}
+
+/// From `#[fsmentry(rankdir = ..)]`, the direction diagrams are laid out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rankdir {
+    TB,
+    LR,
+    BT,
+    RL,
+}
+
+impl Rankdir {
+    fn as_str(self) -> &'static str {
+        match self {
+            Rankdir::TB => "TB",
+            Rankdir::LR => "LR",
+            Rankdir::BT => "BT",
+            Rankdir::RL => "RL",
+        }
+    }
+}
+
+impl syn::parse::Parse for Rankdir {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse::<Ident>()?;
+        let all = [Self::TB, Self::LR, Self::BT, Self::RL];
+        match all.iter().find(|it| ident == it.as_str()) {
+            Some(it) => Ok(*it),
+            None => Err(syn::Error::new(
+                ident.span(),
+                format!("expected one of {}", <Self as args::Value>::SHAPE),
+            )),
+        }
+    }
+}
+
+impl args::Value for Rankdir {
+    const SHAPE: &'static str = "TB | LR | BT | RL";
+}
+
+/// From `#[fsmentry(method_case = ..)]` or `#[fsmentry(method_format = "...")]`,
+/// how transition methods are named after their destination.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+enum MethodCase {
+    /// `red_amber`.
+    #[default]
+    Snake,
+    /// `redAmber`.
+    Camel,
+    /// `RedAmber`, as the state is written.
+    Preserve,
+    /// `to_red_amber` from `"to_{snake}"`, with `{camel}` and `{preserve}` as above.
+    Format(String),
+}
+
+impl syn::parse::Parse for MethodCase {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse::<Ident>()?;
+        match &*ident.to_string() {
+            "snake" => Ok(Self::Snake),
+            "camel" => Ok(Self::Camel),
+            "preserve" => Ok(Self::Preserve),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                format!("expected one of {}", <Self as args::Value>::SHAPE),
+            )),
+        }
+    }
+}
+
+impl args::Value for MethodCase {
+    const SHAPE: &'static str = "snake | camel | preserve";
+}
+
+/// From `#[fsmentry(on_mismatch = ..)]`, what the generated code does when a handle finds the
+/// machine in a state other than its own, which only `unsafe` code can cause.
+#[derive(Debug, Clone, Default)]
+enum OnMismatch {
+    /// Panic with `unreachable!`.
+    #[default]
+    Panic,
+    /// `unreachable_unchecked`, so a mismatch is undefined behaviour.
+    Unchecked,
+    /// Panic in debug builds, and `unreachable_unchecked` otherwise.
+    DebugUnchecked,
+    /// A diverging expression given by the user, like `{ ::std::process::abort() }`,
+    /// which may use the state the handle found as `found`.
+    Expr(Box<syn::Block>),
+}
+
+impl OnMismatch {
+    /// An expression which diverges when a handle is mismatched, using `panic` to panic.
+    fn diverge(&self, panic: TokenStream) -> TokenStream {
+        match self {
+            Self::Panic => panic,
+            Self::Unchecked => quote!(unsafe { ::core::hint::unreachable_unchecked() }),
+            Self::DebugUnchecked => quote! {
+                match ::core::cfg!(debug_assertions) {
+                    true => #panic,
+                    false => unsafe { ::core::hint::unreachable_unchecked() },
+                }
+            },
+            Self::Expr(block) => quote!(#block),
+        }
+    }
+}
+
+impl syn::parse::Parse for OnMismatch {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::token::Brace) {
+            return Ok(Self::Expr(Box::new(input.parse()?)));
+        }
+        let ident = input.parse::<Ident>()?;
+        match &*ident.to_string() {
+            "panic" => Ok(Self::Panic),
+            "unchecked" => Ok(Self::Unchecked),
+            "debug_unchecked" => Ok(Self::DebugUnchecked),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                format!("expected one of {}", <Self as args::Value>::SHAPE),
+            )),
+        }
+    }
+}
+
+impl args::Value for OnMismatch {
+    const SHAPE: &'static str = "panic | unchecked | debug_unchecked | { <expression> }";
+}
+
+/// From `#[fsmentry(emit = ..)]`, which halves of the generated code to emit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Emit {
+    #[default]
+    Full,
+    /// The state enum and the items describing it, for hand-written transitions.
+    StateOnly,
+    /// Everything else, for a hand-written state enum in the parent module.
+    EntryOnly,
+}
+
+impl syn::parse::Parse for Emit {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse::<Ident>()?;
+        match &*ident.to_string() {
+            "full" => Ok(Self::Full),
+            "state_only" => Ok(Self::StateOnly),
+            "entry_only" => Ok(Self::EntryOnly),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                format!("expected one of {}", <Self as args::Value>::SHAPE),
+            )),
+        }
+    }
+}
+
+impl args::Value for Emit {
+    const SHAPE: &'static str = "full | state_only | entry_only";
+}
+
+/// From `type Name = Type;`, emitted into the generated module.
+#[derive(Debug, Clone)]
+struct TypeAlias {
+    docs: Vec<OuterDocString>,
+    ident: Ident,
+    ty: syn::Type,
+}
+
+/// From `#[fsmentry(state_mod = pub state)]` or `#[fsmentry(entry_mod = ..)]`,
+/// a module beside the machine's own, re-exporting some of the generated items.
+#[derive(Debug, Clone)]
+struct ReexportMod {
+    vis: syn::Visibility,
+    ident: Ident,
+}
+
+impl syn::parse::Parse for ReexportMod {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            vis: input.parse()?,
+            ident: input.parse()?,
+        })
+    }
+}
+
+impl args::Value for ReexportMod {
+    const SHAPE: &'static str = "<visibility> <identifier>";
+}
+
+/// From `#[fsmentry(doc_templates(..))]`, the prose in generated documentation,
+/// with `{placeholders}` filled in.
+#[derive(Debug, Clone)]
+struct DocTemplates {
+    /// Before the methods of a transition type, with `{state}`.
+    handle: String,
+    /// Before the states with a transition into a state, with `{state}`.
+    reachable_from: String,
+    /// Before the states a state has a transition to, with `{state}`.
+    can_reach: String,
+    /// The postcondition of a transition, with `{condition}`.
+    ensures: String,
+    /// The precondition of a transition, with `{condition}`.
+    requires: String,
+}
+
+impl Default for DocTemplates {
+    fn default() -> Self {
+        Self {
+            handle: String::from("Transition the state machine by calling the following methods:"),
+            reachable_from: String::from("This node is reachable from the following states:"),
+            can_reach: String::from("This node can reach the following states:"),
+            ensures: String::from("Ensures `{condition}`, which is checked in debug builds."),
+            requires: String::from("Requires `{condition}`, which is checked in debug builds."),
+        }
+    }
+}
+
+impl DocTemplates {
+    /// A template from the user, which may only use the given placeholders.
+    fn check(lit: syn::LitStr, placeholders: &[&str]) -> syn::Result<String> {
+        let template = lit.value();
+        let mut rest = &*template;
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                return Err(syn::Error::new(lit.span(), "unclosed `{` in template"));
+            };
+            let placeholder = &rest[start + 1..start + len];
+            if !placeholders.contains(&placeholder) {
+                let expected = placeholders.iter().map(|it| format!("`{{{}}}`", it));
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!(
+                        "unknown placeholder `{{{}}}`, expected {}",
+                        placeholder,
+                        expected.collect::<Vec<_>>().join(" or ")
+                    ),
+                ));
+            }
+            rest = &rest[start + len + 1..];
+        }
+        Ok(template)
+    }
+    fn fill(template: &str, placeholder: &str, value: &str) -> String {
+        template.replace(&format!("{{{}}}", placeholder), value)
+    }
+}
+
+/// From `same_rank(..);` and `rank(..);` statements, constraints on the layout of diagrams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rank {
+    Same,
+    Min,
+    Max,
+    Source,
+    Sink,
+}
+
+impl Rank {
+    fn as_str(self) -> &'static str {
+        match self {
+            Rank::Same => "same",
+            Rank::Min => "min",
+            Rank::Max => "max",
+            Rank::Source => "source",
+            Rank::Sink => "sink",
+        }
+    }
+}
+
+/// Machines larger than this are slow to expand, so are warned about.
+///
+/// These limits, and [`DEFAULT_MAX_EDGES`], are round numbers which haven't been measured yet.
+/// `cargo test -- --ignored --nocapture compile_time` prints how long `dsl!` takes to expand
+/// and check for ever larger machines, so they can be checked.
+///
+/// See `#[fsmentry(max_states = ..)]`.
+const DEFAULT_MAX_STATES: usize = 256;
+/// See `#[fsmentry(max_edges = ..)]`.
+const DEFAULT_MAX_EDGES: usize = 1024;
+
+/// A problem with a state machine definition that doesn't prevent code generation.
+///
+/// See [`FSMGenerator::warnings`].
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub span: Span,
+    pub message: String,
 }
 
 /// A code generator for state machines with an entry API.
@@ -65,10 +667,116 @@ pub struct FSMGenerator {
     ident: Ident,
     /// All nodes must be in this map.
     nodes: BTreeMap<NodeId, NodeData>,
-    /// Directed L -> R.
-    ///
-    /// Documentation is passed through to the transition functions
-    edges: BTreeMap<(NodeId, NodeId), Vec<OuterDocString>>,
+    /// Directed L -> R, keyed by the name of the transition method too,
+    /// since there may be several transitions between two states.
+    edges: BTreeMap<(NodeId, NodeId, Ident), EdgeData>,
+    /// The keys of `nodes` and `edges` in the order they were first written,
+    /// for exports which keep the layout of the definition.
+    order: (Vec<NodeId>, Vec<(NodeId, NodeId, Ident)>),
+    derives: Derives,
+    /// From `#[fsmentry(svg = ...)]`.
+    include_svg: Option<IncludeSvg>,
+    /// From `#[fsmentry(dot_path = "...")]`.
+    dot_path: Option<syn::LitStr>,
+    /// From `#[fsmentry(dot_timeout = <seconds>)]`.
+    dot_timeout: Duration,
+    /// From `#[fsmentry(svg_max_size = <bytes>)]`.
+    svg_max_size: usize,
+    /// From `#[fsmentry(diagram_fallback = ..)]`.
+    diagram_fallback: DiagramFallback,
+    /// From `#[fsmentry(atomic_kind = path::to::STATIC)]`,
+    /// an `AtomicStateKind` which is updated on every transition.
+    atomic_kind: Option<syn::Path>,
+    defmt: Defmt,
+    /// From `#[fsmentry(serde = ...)]`.
+    serde: Serde,
+    /// From `#[fsmentry(serde_tag = "..", serde_content = "..")]`.
+    /// How `State` is tagged, which is externally by default.
+    serde_tagging: (Option<syn::LitStr>, Option<syn::LitStr>),
+    /// From `#[fsmentry(fmt = ...)]`.
+    /// Whether to implement `core::fmt` traits for the generated items.
+    fmt: bool,
+    /// From `#[fsmentry(mark_cfg = ...)]`.
+    /// Whether diagrams distinguish transitions behind a `#[cfg(..)]`.
+    mark_cfg: bool,
+    /// From `#[fsmentry(registry = ...)]`.
+    /// Whether this machine is listed in the [`registry`](Self::registry).
+    registry: bool,
+    /// From `#[fsmentry(events = ...)]`.
+    /// Whether to generate an event enum, and a method on the state enum to dispatch it.
+    events: bool,
+    /// From `#[fsmentry(entry_fn = "...")]`.
+    /// The name of the method on the state machine which returns the entry enum.
+    entry_fn: Ident,
+    /// From `#[fsmentry(entry_cell = ...)]`.
+    /// Whether to generate a guard for transitioning a machine in a `RefCell`.
+    entry_cell: bool,
+    /// From `#[fsmentry(state_accessors = ...)]`.
+    /// Whether to generate `is_*`, `as_*` and `try_into_*` methods on the state enum.
+    state_accessors: bool,
+    /// From `#[fsmentry(from_str = ...)]`.
+    /// Whether to parse `StateKind`, and `State`s without data, from their names.
+    from_str: bool,
+    /// From `#[fsmentry(entry_ref = ...)]`.
+    /// Whether to generate read-only access to each state, without transitions.
+    entry_ref: bool,
+    /// From `#[fsmentry(owned = ...)]`.
+    /// Whether to generate transitions which take and return `State` by value.
+    owned: bool,
+    /// From `#[fsmentry(transition_source = ...)]`.
+    /// Whether to generate a trait implemented by every transition type.
+    transition_source: bool,
+    /// From `#[fsmentry(chain = ...)]`.
+    /// Whether transition methods return the entry for the new state.
+    chain: bool,
+    /// From `#[fsmentry(pool = ...)]`.
+    /// Whether to generate a collection of machines, addressed by generational ids.
+    pool: bool,
+    /// From `#[fsmentry(handle_prefix = "...", handle_suffix = "...")]`,
+    /// added to the name of each transition type.
+    handle_affixes: (String, String),
+    /// From `#[fsmentry(conversions = [..])]`.
+    conversions: BTreeSet<Conversion>,
+    /// From `#[fsmentry(expose_handle_field = pub(crate))]`.
+    /// The visibility of the `inner` field of transition types, which also get a `from_raw` constructor.
+    expose_handle_field: Option<syn::Visibility>,
+    /// From `#[fsmentry(handle_vis = pub(crate))]`.
+    /// The visibility of the transition types, which defaults to `pub`.
+    handle_vis: syn::Visibility,
+    /// From `#[fsmentry(on_mismatch = ..)]`.
+    on_mismatch: OnMismatch,
+    /// From `#[fsmentry(mismatch_hook = path::to::function)]`,
+    /// called with the machine's name and the expected and found states before panicking.
+    mismatch_hook: Option<syn::Path>,
+    /// From `#[fsmentry(rankdir = ..)]`.
+    rankdir: Option<Rankdir>,
+    /// From `#[fsmentry(method_case = ..)]`.
+    method_case: MethodCase,
+    /// From `#[fsmentry(emit = ..)]`.
+    emit: Emit,
+    /// From `#[fsmentry(initial = ..)]`, the state created by `State::new`.
+    initial: Option<NodeId>,
+    /// From `#[fsmentry(module = ..)]`, the name of the module the machine is generated in,
+    /// which is otherwise the machine's name in `snake_case`.
+    module: Ident,
+    /// From `#[fsmentry(state_mod = ..)]`,
+    /// re-exports the machine, the state enum and the items describing states.
+    state_mod: Option<ReexportMod>,
+    /// From `#[fsmentry(entry_mod = ..)]`,
+    /// re-exports the entry enum, transition types and the traits used to transition.
+    entry_mod: Option<ReexportMod>,
+    /// From `context: Type;`, data belonging to the whole machine,
+    /// which transition types can reach.
+    context: Option<syn::Type>,
+    /// From `type Name = Type;`, in the order they were declared.
+    aliases: Vec<TypeAlias>,
+    /// From `#[fsmentry(allow(..))]`, lints allowed in the generated modules.
+    allow: Vec<syn::Path>,
+    /// From `#[fsmentry(doc_templates(..))]`.
+    doc_templates: DocTemplates,
+    /// Groups of nodes which diagrams should place at the given rank.
+    ranks: Vec<(Rank, Vec<NodeId>)>,
+    warnings: Vec<Warning>,
 }
 
 impl FSMGenerator {
@@ -77,21 +785,126 @@ impl FSMGenerator {
     /// The basic layout of the generated code is as follows:
     ///
     /// ```rust,ignore
+    /// // The prose in generated documentation may be replaced with
+    /// // `#[fsmentry(doc_templates(handle = "Leaves `{state}` with:", ..))]`,
+    /// // see `reachable_from`, `can_reach`, `ensures` and `requires` too
+    /// // With `#[fsmentry(allow(clippy::module_name_repetitions, ..))]`,
+    /// // the generated modules allow those lints
+    /// // The module is named after the machine, unless `#[fsmentry(module = my_mod)]`
     /// (pub) mod <name> {
+    ///     // From `type Name = Type;` in the definition
+    ///     pub type <alias> = ..;
     ///     // The actual state machine
     ///     pub struct <name> { .. }
     ///     // The possible states, including inner data
+    ///     // With `#[fsmentry(serde = true | untagged)]`, `State` and `StateKind` derive
+    ///     // `serde::Serialize` and `serde::Deserialize`, and `#[serde(..)]` on a state is kept
+    ///     // on its variant. `#[fsmentry(serde_tag = "..", serde_content = "..")]` tags it
+    ///     // internally or adjacently. The entry api borrows the machine, so never derives them
     ///     pub enum State { .. }
-    ///     // The entry api, which gives you handles to transition the machine
+    ///     // With `#[fsmentry(state_accessors = true)]`, `State::is_<state>`, and for states with
+    ///     // data `as_<state>`, `as_<state>_mut` and `try_into_<state>`
+    ///     // With `#[fsmentry(initial = ..)]`, `State::new` creates that state from its data,
+    ///     // and `State` implements `Default` if it has none
+    ///     // The possible states, without inner data, see `State::kind`
+    ///     pub enum StateKind { .. }
+    ///     // With `#[fsmentry(from_str = true)]`, `StateKind` and states without data in `State`
+    ///     // implement `FromStr`, and `State` implements `Display`, with the name of each state
+    ///     pub struct ParseStateError { .. }
+    ///     // With `#[fsmentry(entry_ref = true)]`, from `State::entry_ref`, shared references to
+    ///     // the data in each state, and which states it may transition to
+    ///     pub enum EntryRef { .. }
+    ///     // With `#[fsmentry(atomic_kind = path::to::STATIC)]`, a `StateKind` which may be
+    ///     // stored in that static, and is updated on every transition
+    ///     pub struct AtomicStateKind { .. }
+    ///     // With `#[fsmentry(defmt = true)]`, `defmt::Format` is implemented for
+    ///     // `State` and `StateKind`. `defmt = kind` formats only the name of each state,
+    ///     // for data which doesn't implement `defmt::Format`
+    ///     // `StateKind::as_str` is always available, but `#[fsmentry(fmt = false)]` omits the
+    ///     // generated `core::fmt::Debug` and `core::fmt::Display` implementations
+    ///     // The entry api, which gives you handles to transition the machine.
+    ///     // `#[fsmentry(entry_fn = "...")]` renames the `entry` method which returns it
     ///     pub enum Entry { .. }
+    ///     // With `#[fsmentry(transition_source = true)]`, implemented by every transition type,
+    ///     // with `kind`, `available` and `into_entry`, for code which works with any of them
+    ///     pub trait TransitionSource<'a> { .. }
+    ///     // With `#[fsmentry(entry_cell = true)]`, `<name>::entry_cell` borrows a machine in a
+    ///     // `RefCell`, returning a guard with the same `entry` method
+    ///     pub struct EntryGuard<'a> { .. }
+    ///     // With `#[fsmentry(pool = true)]`, many machines addressed by ids, which aren't
+    ///     // reused when a machine is removed. Transitions through `MachinePool::entry` keep
+    ///     // an index of the machines in each state. The pool requires the `alloc` crate
+    ///     pub struct MachinePool { .. }
+    ///     pub struct MachineId { .. }
+    ///     // An alternative to matching on the entry api, see `State::visit`
+    ///     pub trait Visitor { .. }
+    ///     // With `#[fsmentry(events = true)]`, a variant for each transition method,
+    ///     // holding its arguments, which may be dispatched with `State::handle`
+    ///     pub enum Event { .. }
+    ///     pub struct InvalidTransition { .. }
     ///
     ///     // additional structs are generated to perform the actual state transitions
+    ///     // with `#[fsmentry(conversions = [AsRef, Borrow, Deref, ..])]`, those traits are
+    ///     // implemented on them for the data in their state.
+    ///     // `#[fsmentry(expose_handle_field = pub(crate))]` makes their `&mut State` field visible,
+    ///     // and adds an `unsafe fn from_raw(&mut State)` for writing custom combinators.
+    ///     // With `#[fsmentry(chain = true)]`, their methods return the `Entry` for the new state,
+    ///     // after any data from the old one
+    ///     // `#[fsmentry(handle_vis = pub(crate))]` keeps the types themselves out of a library's
+    ///     // api, while `Entry` still holds them
+    ///     // A handle finding the machine in another state panics, which
+    ///     // `#[fsmentry(on_mismatch = unchecked | debug_unchecked | { <expression> })]` changes,
+    ///     // and `#[fsmentry(mismatch_hook = path::to::function)]` reports before panicking
     /// }
+    /// // `#[fsmentry(emit = state_only)]` emits only `State` and the items describing it,
+    /// // for hand-written transitions, and `emit = entry_only` everything else,
+    /// // for a hand-written `State` in the parent module
+    /// // With `#[fsmentry(state_mod = pub state, entry_mod = pub(crate) entry)]`, modules
+    /// // beside it re-export each layer of the api, so the module above may be private
+    /// (pub) mod <state_mod> { pub use super::<name>::{<name>, State, StateKind, ..}; }
+    /// (pub) mod <entry_mod> { pub use super::<name>::{Entry, Visitor, Hooks, ..}; }
     /// ```
+    ///
+    /// Any other items are internal, so are `#[doc(hidden)]`,
+    /// and prefixed with `__fsmentry_` so they don't clash with the user's.
     pub fn codegen(&self) -> syn::File {
         let state_machine_name = self.ident.UpperCamelCase();
         let state_enum_name = self.state_enum_name();
         let entry_enum_name = self.entry_enum_name();
+        let hooks_trait_name = self.hooks_trait_name();
+        let visitor_trait_name = self.visitor_trait_name();
+        let kind_enum_name = self.kind_enum_name();
+        let atomic_kind_name = self.atomic_kind_name();
+        let event_enum_name = self.event_enum_name();
+        let invalid_transition_name = self.invalid_transition_name();
+        let entry_guard_name = self.entry_guard_name();
+        let entry_ref_name = self.entry_ref_name();
+        let parse_state_error_name = self.parse_state_error_name();
+        let owned_entry_name = self.owned_entry_name();
+        let (pool_name, pool_id_name) = self.pool_names();
+        // threaded through to each transition type, when there is a context
+        let context_ty = self.context.as_ref();
+        let context_param = context_ty.map(|ty| quote!(, context: &mut #ty));
+        let context_arg = context_ty.map(|_| quote!(, context));
+        // with `pool`, handles from the pool update its index of states as they transition
+        let pool_index_name = ident("__fsmentry_PoolIndex");
+        let pool_field = self.pool.then(|| {
+            quote! {
+                #[doc(hidden)]
+                __fsmentry_pool: ::core::option::Option<(&'a mut #pool_index_name, u32)>,
+            }
+        });
+        let pool_arg = self
+            .pool
+            .then(|| quote!(, __fsmentry_pool: ::core::option::Option::None));
+        // keep the mirror in sync with the state
+        let store_kind = |kind: &Ident| {
+            self.atomic_kind.as_ref().map(|it| {
+                quote! {
+                    #it.store(#kind_enum_name::#kind, ::core::sync::atomic::Ordering::Release);
+                }
+            })
+        };
 
         let mut state_variants = Punctuated::<syn::Variant, Token![,]>::new();
         let mut entry_variants = Punctuated::<syn::Variant, Token![,]>::new();
@@ -99,76 +912,359 @@ impl FSMGenerator {
         let mut entry_construction = Vec::<syn::Arm>::new();
         let mut transition_tys = Vec::<syn::ItemStruct>::new();
         let mut transition_impls = Vec::<syn::ItemImpl>::new();
+        let mut owned_variants = Punctuated::<syn::Variant, Token![,]>::new();
+        let mut owned_arms = Vec::<syn::Arm>::new();
+        let mut owned_tys = Vec::<syn::ItemStruct>::new();
+        let mut owned_impls = Vec::<syn::ItemImpl>::new();
+        let mut callbacks = BTreeMap::<Ident, (Vec<(Ident, syn::Type)>, Vec<TokenStream>)>::new();
+        let mut state_hooks = BTreeMap::<&Ident, Vec<String>>::new();
+        let mut events = BTreeMap::<&Ident, (Vec<(Ident, syn::Type)>, Vec<TokenStream>)>::new();
+        let mut handle_arms = Vec::<syn::Arm>::new();
+        let mut handle_hooks = false;
+        let mut visitor_methods = Vec::<syn::TraitItemFn>::new();
+        let mut visit_arms = Vec::<syn::Arm>::new();
+        let mut kind_variants = Vec::<syn::Variant>::new();
+        let mut kind_arms = Vec::<syn::Arm>::new();
+        let mut kind_str_arms = Vec::<syn::Arm>::new();
+        let mut defmt_kind_arms = Vec::<syn::Arm>::new();
+        let mut defmt_state_arms = Vec::<syn::Arm>::new();
         for (
             node,
             NodeData {
                 ty: node_ty,
                 docs: node_docs,
+                on_enter,
+                on_exit,
+                terminal: _,
+                label: _,
+                serde: _,
             },
         ) in self.nodes.iter()
         {
             let node_variant_name = node.variant();
+            // the state cannot change underneath us while we hold a mutable reference
+            let mismatch = self.mismatch(&self.on_mismatch, &node_variant_name);
+            if let Some(hook) = on_enter {
+                state_hooks.entry(hook).or_default().push(format!(
+                    "- after entering [`{}::{}`]",
+                    state_enum_name, node_variant_name
+                ))
+            }
+            if let Some(hook) = on_exit {
+                state_hooks.entry(hook).or_default().push(format!(
+                    "- before leaving [`{}::{}`]",
+                    state_enum_name, node_variant_name
+                ))
+            }
+            // the entry enum gets a summary, since hovering it is the common case
+            let mut entry_docs = node_docs
+                .iter()
+                .take_while(|it| !it.doc.value().trim().is_empty())
+                .cloned()
+                .collect::<Vec<_>>();
+            let mut entry_line = match node_ty {
+                None => None,
+                Some(Data::Tuple(ty)) => Some(format!("Holds a `{}`.", unparse(ty))),
+                Some(Data::Record(fields)) => {
+                    let mut fields = fields
+                        .iter()
+                        .map(|(name, ty)| format!("`{}: {}`", name, unparse(ty)))
+                        .collect::<Vec<_>>();
+                    let last = fields.pop().expect("records have at least one field");
+                    Some(match fields.is_empty() {
+                        true => format!("Holds {}.", last),
+                        false => format!("Holds {} and {}.", fields.join(", "), last),
+                    })
+                }
+            };
+            if entry_docs.len() < node_docs.len() {
+                let more = format!(
+                    "See [`{}::{}`] for more.",
+                    state_enum_name, node_variant_name
+                );
+                entry_line = Some(match entry_line {
+                    Some(line) => format!("{} {}", line, more),
+                    None => more,
+                });
+            }
+            if let Some(line) = entry_line {
+                if !entry_docs.is_empty() {
+                    entry_docs.push(OuterDocString::new("", Span::call_site()))
+                }
+                entry_docs.push(OuterDocString::new(line, Span::call_site()))
+            }
             let mut node_docs = node_docs.clone();
             if let Some(reachability_docs) = self.reachability_docs(node) {
-                if !node_docs.is_empty() {
-                    node_docs.push(OuterDocString::new("", Span::call_site()))
+                for docs in [&mut node_docs, &mut entry_docs] {
+                    if !docs.is_empty() {
+                        docs.push(OuterDocString::new("", Span::call_site()))
+                    }
+                    docs.push(reachability_docs.clone())
                 }
-                node_docs.extend(reachability_docs)
             }
+            let kind_doc = format!("See [`{}::{}`].", state_enum_name, node_variant_name);
+            kind_variants.push(parse_quote!(#[doc = #kind_doc] #node_variant_name));
+            kind_arms.push(parse_quote!(#state_enum_name::#node_variant_name { .. } => #kind_enum_name::#node_variant_name,));
+            let name = node_variant_name.to_string();
+            kind_str_arms.push(parse_quote!(#kind_enum_name::#node_variant_name => #name,));
+            defmt_kind_arms.push(
+                parse_quote!(#kind_enum_name::#node_variant_name => ::defmt::write!(f, #name),),
+            );
+            defmt_state_arms.push(match node_ty {
+                Some(Data::Tuple(_)) => {
+                    let fmt = format!("{}({{}})", name);
+                    parse_quote!(#state_enum_name::#node_variant_name(data) => ::defmt::write!(f, #fmt, data),)
+                }
+                Some(Data::Record(fields)) => {
+                    let names = fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                    let fmt = names.iter().map(|it| format!("{}: {{}}", it));
+                    let fmt = format!("{} {{{{ {} }}}}", name, fmt.collect::<Vec<_>>().join(", "));
+                    parse_quote!(#state_enum_name::#node_variant_name { #(#names),* } => ::defmt::write!(f, #fmt #(, #names)*),)
+                }
+                None => parse_quote!(#state_enum_name::#node_variant_name => ::defmt::write!(f, #name),),
+            });
+            let visit_fn_name = node.visit_fn();
+            let visit_doc = format!(
+                "Called by [`{}::visit`] in [`{}::{}`].",
+                state_enum_name, state_enum_name, node_variant_name
+            );
 
+            if self.owned {
+                // like the entry enum, but holding the state itself
+                let (variant, arm): (syn::Variant, syn::Arm) = match (node_ty, self.outgoing(node))
+                {
+                    (_, Some(_)) => {
+                        let owned_ty = self.owned_ty(node);
+                        (
+                            parse_quote!(#(#entry_docs)* #node_variant_name(#owned_ty)),
+                            parse_quote!(state @ #state_enum_name::#node_variant_name { .. } => #owned_entry_name::#node_variant_name(#owned_ty(state)),),
+                        )
+                    }
+                    (None, None) => (
+                        parse_quote!(#(#entry_docs)* #node_variant_name),
+                        parse_quote!(#state_enum_name::#node_variant_name => #owned_entry_name::#node_variant_name,),
+                    ),
+                    (Some(Data::Tuple(ty)), None) => (
+                        parse_quote!(#(#entry_docs)* #node_variant_name(#ty)),
+                        parse_quote!(#state_enum_name::#node_variant_name(data) => #owned_entry_name::#node_variant_name(data),),
+                    ),
+                    (Some(Data::Record(fields)), None) => {
+                        let names = fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                        let tys = fields.iter().map(|(_, ty)| ty);
+                        (
+                            parse_quote!(#(#entry_docs)* #node_variant_name { #(#names: #tys),* }),
+                            parse_quote!(#state_enum_name::#node_variant_name { #(#names),* } => #owned_entry_name::#node_variant_name { #(#names),* },),
+                        )
+                    }
+                };
+                owned_variants.push(variant);
+                owned_arms.push(arm);
+            }
             match (node_ty, self.outgoing(node)) {
                 (None, None) => {
                     // This node has no data, and no transitions, so the entry and state enums are bare
                     state_variants.push(parse_quote!(#(#node_docs)* #node_variant_name));
-                    entry_variants.push(parse_quote!(#(#node_docs)* #node_variant_name));
-                    entry_construction.push(parse_quote!(#state_enum_name::#node_variant_name => #entry_enum_name::#node_variant_name,))
+                    entry_variants.push(parse_quote!(#(#entry_docs)* #node_variant_name));
+                    entry_construction.push(parse_quote!(#state_enum_name::#node_variant_name => #entry_enum_name::#node_variant_name,));
+                    visitor_methods.push(parse_quote! {
+                        #[doc = #visit_doc]
+                        fn #visit_fn_name(&mut self) {}
+                    });
+                    visit_arms.push(parse_quote!(#state_enum_name::#node_variant_name => visitor.#visit_fn_name(),));
+                }
+                (Some(Data::Record(fields)), None) => {
+                    // As below, with a reference to each field
+                    let names = fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                    let tys = fields.iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+                    let unused = names.iter().map(|it| ident(format!("_{}", it.unraw())));
+                    state_variants.push(
+                        parse_quote!(#(#node_docs)* #node_variant_name { #(#names: #tys),* }),
+                    );
+                    entry_has_lifetime = true;
+                    entry_variants.push(parse_quote!(#(#entry_docs)* #node_variant_name { #(#names: &'a mut #tys),* }));
+                    entry_construction.push(parse_quote!{
+                        #state_enum_name::#node_variant_name { .. } => {
+                            // need to reborrow to get the data
+                            match &mut self.state {
+                                #state_enum_name::#node_variant_name { #(#names),* } => #entry_enum_name::#node_variant_name { #(#names),* },
+                                found => #mismatch
+                            }
+                        }
+                    });
+                    visitor_methods.push(parse_quote! {
+                        #[doc = #visit_doc]
+                        fn #visit_fn_name(&mut self, #(#unused: &mut #tys),*) {}
+                    });
+                    visit_arms.push(parse_quote!(#state_enum_name::#node_variant_name { #(#names),* } => visitor.#visit_fn_name(#(#names),*),));
                 }
-                (Some(ty), None) => {
+                (Some(Data::Tuple(ty)), None) => {
                     // This node has data, but no transitions, so the entry and state enums just contain a reference to the data
                     state_variants.push(parse_quote!(#(#node_docs)* #node_variant_name(#ty)));
                     entry_has_lifetime = true;
                     entry_variants
-                        .push(parse_quote!(#(#node_docs)* #node_variant_name(&'a mut #ty)));
+                        .push(parse_quote!(#(#entry_docs)* #node_variant_name(&'a mut #ty)));
                     entry_construction.push(parse_quote!{
                         #state_enum_name::#node_variant_name(_) => {
                             // need to reborrow to get the data
                             match &mut self.state {
                                 #state_enum_name::#node_variant_name(data) => #entry_enum_name::#node_variant_name(data),
-                                _ => ::core::unreachable!("state cannot change underneath us while we hold a mutable reference")
+                                found => #mismatch
                             }
                         }
                     });
+                    visitor_methods.push(parse_quote! {
+                        #[doc = #visit_doc]
+                        fn #visit_fn_name(&mut self, _data: &mut #ty) {}
+                    });
+                    visit_arms.push(parse_quote!(#state_enum_name::#node_variant_name(data) => visitor.#visit_fn_name(data),));
                 }
                 (node_data_ty, Some(outgoing)) => {
                     // this node has transitions, so create a transition type
                     let transition_ty_name = self.transition_ty(node);
                     entry_has_lifetime = true;
                     transition_tys.push({
-                        let method_docs = outgoing.iter().map(|(node, _)| {
-                            OuterDocString::new(
-                                format!("- [`{}::{}`]", transition_ty_name, node.transition_fn()),
-                                Span::call_site(),
+                        let method_docs = outgoing.iter().map(|(_, method, data)| {
+                            doc_line(
+                                format!("- [`{}::{}`]", transition_ty_name, method),
+                                data.cfg.as_ref(),
                             )
                         });
+                        let vis = &self.expose_handle_field;
+                        let handle_vis = &self.handle_vis;
+                        let context_field = context_ty.map(|ty| quote!(#vis context: &'a mut #ty,));
+                        let doc = DocTemplates::fill(
+                            &self.doc_templates.handle,
+                            "state",
+                            &node_variant_name.to_string(),
+                        );
                         parse_quote!(
-                            /// Transition the state machine by calling the following methods:
+                            #[doc = #doc]
                             #(#method_docs)*
-                            pub struct #transition_ty_name<'a> {
-                                inner: &'a mut #state_enum_name,
+                            #handle_vis struct #transition_ty_name<'a> {
+                                #vis inner: &'a mut #state_enum_name,
+                                #context_field
+                                #pool_field
                             }
                         )
                     });
+                    if self.owned {
+                        let owned_ty = self.owned_ty(node);
+                        let handle_vis = &self.handle_vis;
+                        let doc = format!(
+                            "Takes [`{}::{}`] by value, returning the next state from each transition.",
+                            state_enum_name, node_variant_name
+                        );
+                        owned_tys.push(parse_quote! {
+                            #[doc = #doc]
+                            #handle_vis struct #owned_ty(#state_enum_name);
+                        });
+                        owned_impls.push(parse_quote! {
+                            impl #owned_ty {
+                                /// Give back the state, without transitioning it.
+                                pub fn into_state(self) -> #state_enum_name {
+                                    self.0
+                                }
+                            }
+                        });
+                    }
+                    if let Some(ty) = context_ty {
+                        transition_impls.push(parse_quote! {
+                            impl #transition_ty_name<'_> {
+                                /// Get a reference to the context of the state machine.
+                                pub fn context(&self) -> &#ty {
+                                    self.context
+                                }
+                                /// Get a mutable reference to the context of the state machine.
+                                pub fn context_mut(&mut self) -> &mut #ty {
+                                    self.context
+                                }
+                            }
+                        });
+                    }
+                    if let Some(vis) = &self.expose_handle_field {
+                        let context_param = context_ty.map(|ty| quote!(, context: &'a mut #ty));
+                        let comment = format!(
+                            "`inner` must be [`{}::{}`], which the methods on this type rely on.",
+                            state_enum_name, node_variant_name
+                        );
+                        transition_impls.push(parse_quote! {
+                            impl<'a> #transition_ty_name<'a> {
+                                /// Create a handle to transition the state machine from this state.
+                                ///
+                                /// # Safety
+                                #[doc = #comment]
+                                #vis unsafe fn from_raw(inner: &'a mut #state_enum_name #context_param) -> Self {
+                                    ::core::debug_assert!(::core::matches!(inner, #state_enum_name::#node_variant_name { .. }));
+                                    Self { inner #context_arg #pool_arg }
+                                }
+                            }
+                        });
+                    }
                     entry_variants.push(
-                        parse_quote!(#(#node_docs)* #node_variant_name(#transition_ty_name<'a>)),
+                        parse_quote!(#(#entry_docs)* #node_variant_name(#transition_ty_name<'a>)),
                     );
+                    let context_construction =
+                        context_ty.map(|_| quote!(context: &mut self.context,));
+                    let pool_construction = self.pool.then(|| quote!(__fsmentry_pool: pool,));
                     entry_construction.push(parse_quote!{
                         #state_enum_name::#node_variant_name{..} => #entry_enum_name::#node_variant_name(#transition_ty_name {
                             inner: &mut self.state,
+                            #context_construction
+                            #pool_construction
+                        }),
+                    });
+                    visitor_methods.push(parse_quote! {
+                        #[doc = #visit_doc]
+                        fn #visit_fn_name(&mut self, _transition: #transition_ty_name<'_>) {}
+                    });
+                    visit_arms.push(parse_quote! {
+                        #state_enum_name::#node_variant_name{..} => visitor.#visit_fn_name(#transition_ty_name {
+                            inner: self
+                            #context_arg
+                            #pool_arg
                         }),
                     });
-                    let msg = "this variant is only created when state is known to match, and we hold a mutable reference to state";
+                    // this variant is only created when state is known to match, and we hold a mutable reference to state
+                    let msg = self.mismatch(&self.on_mismatch, &node_variant_name);
                     match node_data_ty {
-                        Some(ty) => {
+                        Some(Data::Record(fields)) => {
+                            // this node has fields, so store them in the state enum, and add accessors for each
+                            let names = fields.iter().map(|(name, _)| name);
+                            let tys = fields.iter().map(|(_, ty)| ty);
+                            state_variants.push(
+                                parse_quote!(#(#node_docs)* #node_variant_name { #(#names: #tys),* }),
+                            );
+                            for (name, ty) in fields {
+                                let name_mut = ident(format!("{}_mut", name.unraw()));
+                                let doc = format!(
+                                    "Get a reference to the `{}` stored in this state",
+                                    name
+                                );
+                                let doc_mut = format!(
+                                    "Get a mutable reference to the `{}` stored in this state",
+                                    name
+                                );
+                                transition_impls.push(parse_quote! {
+                                    impl #transition_ty_name<'_> {
+                                        #[doc = #doc]
+                                        pub fn #name(&self) -> & #ty {
+                                            match &self.inner {
+                                                #state_enum_name::#node_variant_name { #name, .. } => #name,
+                                                found => #msg
+                                            }
+                                        }
+                                        #[doc = #doc_mut]
+                                        pub fn #name_mut(&mut self) -> &mut #ty {
+                                            match self.inner {
+                                                #state_enum_name::#node_variant_name { #name, .. } => #name,
+                                                ref found => #msg
+                                            }
+                                        }
+                                    }
+                                });
+                            }
+                        }
+                        Some(Data::Tuple(ty)) => {
                             // this node has data, so store it in the state enum, and add getters for the transition type
                             state_variants
                                 .push(parse_quote!(#(#node_docs)* #node_variant_name(#ty)));
@@ -179,133 +1275,1408 @@ impl FSMGenerator {
                                     pub fn #get(&self) -> & #ty {
                                         match &self.inner {
                                             #state_enum_name::#node_variant_name(data) => data,
-                                            _ => ::core::unreachable!(#msg)
+                                            found => #msg
                                         }
                                     }
                                     /// Get a mutable reference to the data stored in this state
                                     pub fn #get_mut(&mut self) -> &mut #ty {
                                         match self.inner {
                                             #state_enum_name::#node_variant_name(data) => data,
-                                            _ => ::core::unreachable!(#msg)
+                                            ref found => #msg
                                         }
                                     }
                                 }
                             });
+                            for conversion in &self.conversions {
+                                let target = quote!(#transition_ty_name<'_>);
+                                transition_impls.push(match conversion {
+                                    Conversion::AsRef => parse_quote! {
+                                        impl ::core::convert::AsRef<#ty> for #target {
+                                            fn as_ref(&self) -> &#ty {
+                                                self.#get()
+                                            }
+                                        }
+                                    },
+                                    Conversion::AsMut => parse_quote! {
+                                        impl ::core::convert::AsMut<#ty> for #target {
+                                            fn as_mut(&mut self) -> &mut #ty {
+                                                self.#get_mut()
+                                            }
+                                        }
+                                    },
+                                    Conversion::Borrow => parse_quote! {
+                                        impl ::core::borrow::Borrow<#ty> for #target {
+                                            fn borrow(&self) -> &#ty {
+                                                self.#get()
+                                            }
+                                        }
+                                    },
+                                    Conversion::BorrowMut => parse_quote! {
+                                        impl ::core::borrow::BorrowMut<#ty> for #target {
+                                            fn borrow_mut(&mut self) -> &mut #ty {
+                                                self.#get_mut()
+                                            }
+                                        }
+                                    },
+                                    Conversion::Deref => parse_quote! {
+                                        impl ::core::ops::Deref for #target {
+                                            type Target = #ty;
+                                            fn deref(&self) -> &#ty {
+                                                self.#get()
+                                            }
+                                        }
+                                    },
+                                    Conversion::DerefMut => parse_quote! {
+                                        impl ::core::ops::DerefMut for #target {
+                                            fn deref_mut(&mut self) -> &mut #ty {
+                                                self.#get_mut()
+                                            }
+                                        }
+                                    },
+                                })
+                            }
                         }
                         None => {
                             state_variants.push(parse_quote!(#(#node_docs)* #node_variant_name));
                         }
                     }
-                    for (outgoing, transition_docs) in outgoing {
-                        let transition_fn_name = outgoing.transition_fn();
+                    for (
+                        outgoing,
+                        transition_fn_name,
+                        EdgeData {
+                            docs: transition_docs,
+                            callback,
+                            params,
+                            cfg,
+                            ensures,
+                            requires,
+                            attrs: transition_attrs,
+                            on_mismatch,
+                            named: _,
+                        },
+                    ) in outgoing
+                    {
+                        let msg = self.mismatch(
+                            on_mismatch.as_ref().unwrap_or(&self.on_mismatch),
+                            &node_variant_name,
+                        );
                         let outgoing_variant_name = outgoing.variant();
-                        let methods: Vec<syn::ImplItemFn> = match (
-                            node_data_ty,
-                            &self.nodes[outgoing].ty,
-                        ) {
-                            // no data -> no data
-                            (None, None) => vec![parse_quote! {
-                                #(#transition_docs)*
-                                pub fn #transition_fn_name(self) {
-                                    let prev =
-                                    ::core::mem::replace(self.inner, #state_enum_name::#outgoing_variant_name);
-                                    ::core::debug_assert!(::core::matches!(prev, #state_enum_name::#node_variant_name));
+                        let mut transition_docs = transition_docs.to_vec();
+                        let on_enter = &self.nodes[outgoing].on_enter;
+                        let mut hook_lines = vec![];
+                        if let Some(callback) = callback {
+                            hook_lines.push(format!(
+                                "Calls [`{}::{}`] before transitioning.",
+                                hooks_trait_name, callback
+                            ));
+                            // the data of both states, if any, then the arguments
+                            let callback_params = [
+                                node_ty.as_ref().map(|it| (ident("from"), it.reference())),
+                                self.nodes[outgoing]
+                                    .ty
+                                    .as_ref()
+                                    .map(|it| (ident("to"), it.reference())),
+                            ]
+                            .into_iter()
+                            .flatten()
+                            .chain(params.iter().cloned())
+                            .collect();
+                            callbacks
+                                .entry(callback.clone())
+                                .or_insert((callback_params, vec![]))
+                                .1
+                                .push(doc_line(
+                                    format!("- [`{}::{}`]", transition_ty_name, transition_fn_name),
+                                    cfg.as_ref(),
+                                ));
+                        }
+                        if let Some(hook) = on_exit {
+                            hook_lines.push(format!(
+                                "Calls [`{}::{}`] before leaving [`{}::{}`].",
+                                hooks_trait_name, hook, state_enum_name, node_variant_name
+                            ));
+                        }
+                        if let Some(hook) = on_enter {
+                            hook_lines.push(format!(
+                                "Calls [`{}::{}`] after entering [`{}::{}`].",
+                                hooks_trait_name, hook, state_enum_name, outgoing_variant_name
+                            ));
+                        }
+                        if !hook_lines.is_empty() && !transition_docs.is_empty() {
+                            transition_docs.push(OuterDocString::new("", Span::call_site()))
+                        }
+                        for line in hook_lines {
+                            transition_docs.push(OuterDocString::new(line, Span::call_site()));
+                        }
+                        let needs_hooks =
+                            callback.is_some() || on_exit.is_some() || on_enter.is_some();
+                        let hooks_param = match needs_hooks {
+                            false => None,
+                            true => {
+                                let params = params.iter().map(|(name, ty)| quote!(#name: #ty));
+                                Some(quote!(#(, #params)*, hooks: &mut impl #hooks_trait_name))
+                            }
+                        };
+                        // the source's data for `requires`, bound like the fields of its variant
+                        let source_pattern = match node_ty {
+                            None => quote!(),
+                            Some(Data::Tuple(_)) => quote!((data)),
+                            Some(Data::Record(fields)) => {
+                                let names = fields.iter().map(|(name, _)| name);
+                                quote!({ #(#names),* })
+                            }
+                        };
+                        if self.events {
+                            let event_variant_name = transition_fn_name.UpperCamelCase();
+                            let args = self.transition_args(outgoing, params);
+                            let names = args.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                            let hooks = needs_hooks.then(|| quote!(hooks));
+                            let cfg_attr = cfg.as_ref().map(|it| quote!(#[cfg(#it)]));
+                            // events may still be dispatched to deprecated transitions
+                            let allow = transition_attrs
+                                .iter()
+                                .any(|it| it.path().is_ident("deprecated"))
+                                .then(|| quote!(#[allow(deprecated)]));
+                            handle_hooks |= needs_hooks;
+                            // an unmet precondition refuses the event, rather than asserting
+                            if let Some(expr) = requires {
+                                handle_arms.push(parse_quote! {
+                                    #cfg_attr
+                                    (#kind_enum_name::#node_variant_name, event @ #event_enum_name::#event_variant_name { .. })
+                                        if !match &*self {
+                                            #[allow(unused_variables)]
+                                            #state_enum_name::#node_variant_name #source_pattern => #expr,
+                                            found => #msg,
+                                        } =>
+                                    {
+                                        ::core::result::Result::Err(#invalid_transition_name { state: #kind_enum_name::#node_variant_name, event, precondition: true })
+                                    }
+                                });
+                            }
+                            handle_arms.push(parse_quote! {
+                                #cfg_attr
+                                #allow
+                                (#kind_enum_name::#node_variant_name, #event_enum_name::#event_variant_name { #(#names),* }) => {
+                                    #transition_ty_name { inner: self #context_arg #pool_arg }.#transition_fn_name(#(#names,)* #hooks);
+                                    Ok(())
                                 }
-                            }],
-                            // no data -> data
-                            (None, Some(out)) => vec![parse_quote! {
-                                #(#transition_docs)*
-                                pub fn #transition_fn_name(self, next: #out) {
-                                    let prev =
-                                    ::core::mem::replace(self.inner, #state_enum_name::#outgoing_variant_name(next));
-                                    ::core::debug_assert!(::core::matches!(prev, #state_enum_name::#node_variant_name));
+                            });
+                            events
+                                .entry(transition_fn_name)
+                                .or_insert((args, vec![]))
+                                .1
+                                .push(doc_line(
+                                    format!("- [`{}::{}`]", transition_ty_name, transition_fn_name),
+                                    cfg.as_ref(),
+                                ));
+                        }
+                        // checked against the old state's data before it is replaced
+                        let requires = requires.as_ref().map(|expr| {
+                            let line = DocTemplates::fill(
+                                &self.doc_templates.requires,
+                                "condition",
+                                &unparse(expr),
+                            );
+                            if !transition_docs.is_empty() {
+                                transition_docs.push(OuterDocString::new("", Span::call_site()))
+                            }
+                            transition_docs.push(OuterDocString::new(line, Span::call_site()));
+                            quote! {
+                                match &*self.inner {
+                                    #[allow(unused_variables)]
+                                    #state_enum_name::#node_variant_name #source_pattern => ::core::debug_assert!(#expr),
+                                    found => #msg,
                                 }
-                            }],
-                            // data -> no data
-                            (Some(input), None) => vec![parse_quote! {
-                                #(#transition_docs)*
-                                pub fn #transition_fn_name(self) -> #input {
-                                    let prev =
-                                    ::core::mem::replace(self.inner, #state_enum_name::#outgoing_variant_name);
-                                    match prev {
-                                        #state_enum_name::#node_variant_name(data) => data,
-                                        _ => ::core::unreachable!(#msg)
-                                    }
+                            }
+                        });
+                        // checked against the new state's data, bound like the fields of its variant
+                        let ensures = ensures.as_ref().map(|expr| {
+                            let line = DocTemplates::fill(
+                                &self.doc_templates.ensures,
+                                "condition",
+                                &unparse(expr),
+                            );
+                            if !transition_docs.is_empty() {
+                                transition_docs.push(OuterDocString::new("", Span::call_site()))
+                            }
+                            transition_docs.push(OuterDocString::new(line, Span::call_site()));
+                            let pattern = match &self.nodes[outgoing].ty {
+                                None => quote!(),
+                                Some(Data::Tuple(_)) => quote!((data)),
+                                Some(Data::Record(fields)) => {
+                                    let names = fields.iter().map(|(name, _)| name);
+                                    quote!({ #(#names),* })
+                                }
+                            };
+                            let replaced = self.mismatch(
+                                on_mismatch.as_ref().unwrap_or(&self.on_mismatch),
+                                &outgoing_variant_name,
+                            );
+                            quote! {
+                                match &*self.inner {
+                                    #[allow(unused_variables)]
+                                    #state_enum_name::#outgoing_variant_name #pattern => ::core::debug_assert!(#expr),
+                                    found => #replaced,
                                 }
-                            }],
-                            // data -> data
-                            (Some(input), Some(out)) => vec![parse_quote! {
-                            #(#transition_docs)*
-                            pub fn #transition_fn_name(self, next: #out) -> #input {
-                                let prev =
-                                ::core::mem::replace(self.inner, #state_enum_name::#outgoing_variant_name(next));
-                                match prev {
-                                    #state_enum_name::#node_variant_name(data) => data,
-                                    _ => ::core::unreachable!(#msg)
-                                }
-                            }}],
-                        };
-                        transition_impls.push(parse_quote!(
-                            impl #transition_ty_name<'_> {
-                                #(#methods)*
                             }
-                        ));
-                    }
-                }
+                        });
+                        // callbacks receive the data of the old state, and the data for the new one
+                        let from = node_ty.as_ref().map(|ty| {
+                            let data = match ty {
+                                Data::Tuple(_) => quote!(data),
+                                Data::Record(fields) => {
+                                    let names = fields.iter().map(|(name, _)| name);
+                                    quote!((#(#names,)*))
+                                }
+                            };
+                            quote! {
+                                match &*self.inner {
+                                    #state_enum_name::#node_variant_name #source_pattern => #data,
+                                    found => #msg
+                                }
+                            }
+                        });
+                        let to = self.nodes[outgoing].ty.as_ref().map(|ty| match ty {
+                            Data::Tuple(_) => quote!(&next),
+                            Data::Record(fields) => {
+                                let names = fields.iter().map(|(name, _)| name);
+                                quote!((#(&#names,)*))
+                            }
+                        });
+                        let args = params.iter().map(|(name, _)| quote!(#name));
+                        let callback_args =
+                            from.into_iter().chain(to).chain(args).collect::<Vec<_>>();
+                        // replace the state, binding the old state to `prev`
+                        let store_kind = store_kind(&outgoing_variant_name);
+                        let store_kind = match self.pool {
+                            false => store_kind,
+                            true => Some(quote! {
+                                #store_kind
+                                if let ::core::option::Option::Some((index, slot)) = &mut self.__fsmentry_pool {
+                                    index.moved(*slot, #kind_enum_name::#node_variant_name, #kind_enum_name::#outgoing_variant_name);
+                                }
+                            }),
+                        };
+                        let on_exit = on_exit.as_ref().map(|it| quote!(hooks.#it(self.inner);));
+                        let on_enter = on_enter.as_ref().map(|it| quote!(hooks.#it(self.inner);));
+                        let swap = |next: syn::Expr| match callback {
+                            None => quote! {
+                                #requires
+                                #on_exit
+                                let prev = ::core::mem::replace(self.inner, #next);
+                                #store_kind
+                                #on_enter
+                                #ensures
+                            },
+                            Some(callback) => quote! {
+                                #requires
+                                hooks.#callback(#(#callback_args),*);
+                                let next = #next;
+                                #on_exit
+                                let prev = ::core::mem::replace(self.inner, next);
+                                #store_kind
+                                #on_enter
+                                #ensures
+                            },
+                        };
+                        let (next_params, next) = match &self.nodes[outgoing].ty {
+                            None => (None, quote!(#state_enum_name::#outgoing_variant_name)),
+                            Some(Data::Tuple(out)) => (
+                                Some(quote!(, next: #out)),
+                                quote!(#state_enum_name::#outgoing_variant_name(next)),
+                            ),
+                            Some(Data::Record(fields)) => {
+                                let names = fields.iter().map(|(name, _)| name);
+                                let params = fields.iter().map(|(name, ty)| quote!(#name: #ty));
+                                (
+                                    Some(quote!(#(, #params)*)),
+                                    quote!(#state_enum_name::#outgoing_variant_name { #(#names),* }),
+                                )
+                            }
+                        };
+                        let swap = swap(parse_quote!(#next));
+                        // with `chain`, the handle is given up for the new state's entry
+                        let next_entry = self.chain.then(|| {
+                            let unreachable =
+                                quote!(_ => ::core::unreachable!("the state was just replaced"));
+                            match (&self.nodes[outgoing].ty, self.outgoing(outgoing)) {
+                                (_, Some(_)) => {
+                                    let next_ty = self.transition_ty(outgoing);
+                                    let context = context_ty.map(|_| quote!(, context: self.context));
+                                    let pool = self
+                                        .pool
+                                        .then(|| quote!(, __fsmentry_pool: self.__fsmentry_pool));
+                                    quote!(#entry_enum_name::#outgoing_variant_name(#next_ty { inner: self.inner #context #pool }))
+                                }
+                                (None, None) => quote!(#entry_enum_name::#outgoing_variant_name),
+                                (Some(Data::Tuple(_)), None) => quote! {
+                                    match self.inner {
+                                        #state_enum_name::#outgoing_variant_name(data) => #entry_enum_name::#outgoing_variant_name(data),
+                                        #unreachable
+                                    }
+                                },
+                                (Some(Data::Record(fields)), None) => {
+                                    let names = fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                                    quote! {
+                                        match self.inner {
+                                            #state_enum_name::#outgoing_variant_name { #(#names),* } => #entry_enum_name::#outgoing_variant_name { #(#names),* },
+                                            #unreachable
+                                        }
+                                    }
+                                }
+                            }
+                        });
+                        let next_entry_ty =
+                            next_entry.as_ref().map(|_| quote!(#entry_enum_name<'a>));
+                        // from `method_case`, or named that way in the definition
+                        let allow = (*transition_fn_name != transition_fn_name.snake_case())
+                            .then(|| quote!(#[allow(non_snake_case)]));
+                        let method: syn::ImplItemFn = match node_data_ty {
+                            // no data -> ..
+                            None => parse_quote! {
+                                #(#transition_docs)*
+                                #(#transition_attrs)*
+                                #allow
+                                pub fn #transition_fn_name(self #next_params #hooks_param) #(-> #next_entry_ty)* {
+                                    #swap
+                                    ::core::debug_assert!(::core::matches!(prev, #state_enum_name::#node_variant_name));
+                                    #next_entry
+                                }
+                            },
+                            // data -> .., returning it
+                            Some(input) => {
+                                let (output, pattern, data) = match input {
+                                    Data::Tuple(ty) => (quote!(#ty), quote!((data)), quote!(data)),
+                                    Data::Record(fields) => {
+                                        let names =
+                                            fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                                        let tys = fields.iter().map(|(_, ty)| ty);
+                                        (
+                                            quote!((#(#tys,)*)),
+                                            quote!({ #(#names),* }),
+                                            quote!((#(#names,)*)),
+                                        )
+                                    }
+                                };
+                                let data = quote! {
+                                    match prev {
+                                        #state_enum_name::#node_variant_name #pattern => #data,
+                                        found => #msg
+                                    }
+                                };
+                                let (output, data) = match (&next_entry_ty, &next_entry) {
+                                    (Some(ty), Some(next_entry)) => {
+                                        (quote!((#output, #ty)), quote!((#data, #next_entry)))
+                                    }
+                                    _ => (output, data),
+                                };
+                                parse_quote! {
+                                    #(#transition_docs)*
+                                    #(#transition_attrs)*
+                                    #allow
+                                    pub fn #transition_fn_name(self #next_params #hooks_param) -> #output {
+                                        #swap
+                                        #data
+                                    }
+                                }
+                            }
+                        };
+                        let cfg = cfg.as_ref().map(|it| quote!(#[cfg(#it)]));
+                        if self.owned {
+                            // delegate to the handle, which is only borrowed for the call
+                            let owned_ty = self.owned_ty(node);
+                            let mut args = match &self.nodes[outgoing].ty {
+                                None => vec![],
+                                Some(Data::Tuple(_)) => vec![ident("next")],
+                                Some(Data::Record(fields)) => {
+                                    fields.iter().map(|(name, _)| name.clone()).collect()
+                                }
+                            };
+                            if needs_hooks {
+                                args.extend(params.iter().map(|(name, _)| name.clone()));
+                                args.push(ident("hooks"));
+                            }
+                            let call = quote! {
+                                #transition_ty_name { inner: &mut self.0 #pool_arg }
+                                    .#transition_fn_name(#(#args),*)
+                            };
+                            let data_ty = node_data_ty.as_ref().map(|it| match it {
+                                Data::Tuple(ty) => quote!(#ty),
+                                Data::Record(fields) => {
+                                    let tys = fields.iter().map(|(_, ty)| ty);
+                                    quote!((#(#tys,)*))
+                                }
+                            });
+                            let (output, body) = match (data_ty, self.chain) {
+                                (None, false) => (quote!(#state_enum_name), quote!(#call; self.0)),
+                                (None, true) => {
+                                    (quote!(#state_enum_name), quote!(let _ = #call; self.0))
+                                }
+                                (Some(ty), false) => (
+                                    quote!((#ty, #state_enum_name)),
+                                    quote!(let data = #call; (data, self.0)),
+                                ),
+                                (Some(ty), true) => (
+                                    quote!((#ty, #state_enum_name)),
+                                    quote!(let (data, _) = #call; (data, self.0)),
+                                ),
+                            };
+                            // the handle's method is deprecated too
+                            let allow_deprecated = transition_attrs
+                                .iter()
+                                .any(|it| it.path().is_ident("deprecated"))
+                                .then(|| quote!(#[allow(deprecated)]));
+                            owned_impls.push(parse_quote! {
+                                #cfg
+                                impl #owned_ty {
+                                    #(#transition_docs)*
+                                    #(#transition_attrs)*
+                                    #allow
+                                    #allow_deprecated
+                                    pub fn #transition_fn_name(mut self #next_params #hooks_param) -> #output {
+                                        #body
+                                    }
+                                }
+                            });
+                        }
+                        transition_impls.push(match self.chain {
+                            false => parse_quote!(
+                                #cfg
+                                impl #transition_ty_name<'_> {
+                                    #method
+                                }
+                            ),
+                            true => parse_quote!(
+                                #cfg
+                                impl<'a> #transition_ty_name<'a> {
+                                    #method
+                                }
+                            ),
+                        });
+                    }
+                }
             }
         }
 
         let attrs = &self.attributes;
+        let derive = Derives::attr(&self.derives.machine);
+        let context_field = context_ty.map(|ty| quote!(context: #ty,));
         let state_machine_struct: syn::ItemStruct = parse_quote! {
             #(#attrs)*
+            #derive
             pub struct #state_machine_name {
-                state: #state_enum_name
+                state: #state_enum_name,
+                #context_field
+            }
+        };
+        let (store_initial, state_mut_docs) = match &self.atomic_kind {
+            Some(path) => {
+                let comment = format!(
+                    "Changes through this reference are not reflected in [`{}`] until the next transition.",
+                    unparse(path)
+                );
+                (
+                    Some(quote! {
+                        #path.store(initial.kind(), ::core::sync::atomic::Ordering::Release);
+                    }),
+                    Some(quote! {
+                        ///
+                        #[doc = #comment]
+                    }),
+                )
+            }
+            None => (None, None),
+        };
+        let context_init = context_ty.map(|ty| quote!(, context: #ty));
+        let context_accessors = context_ty.map(|ty| {
+            quote! {
+                /// Get a reference to the context of the state machine
+                pub fn context(&self) -> &#ty {
+                    &self.context
+                }
+                /// Get a mutable reference to the context of the state machine
+                pub fn context_mut(&mut self) -> &mut #ty {
+                    &mut self.context
+                }
+            }
+        });
+        let entry_fn = &self.entry_fn;
+        let entry_body = quote! {
+            match &mut self.state {
+                #(#entry_construction)*
+            }
+        };
+        let (entry_body, pool_entry) = match self.pool {
+            false => (entry_body, None),
+            true => {
+                // states without transitions don't need the index
+                let allow = transition_tys
+                    .is_empty()
+                    .then(|| quote!(#[allow(unused_variables)]));
+                (
+                    quote!(self.__fsmentry_entry(::core::option::Option::None)),
+                    Some(quote! {
+                        #[doc(hidden)]
+                        #allow
+                        fn __fsmentry_entry<'a>(&'a mut self, pool: ::core::option::Option<(&'a mut #pool_index_name, u32)>) -> #entry_enum_name {
+                            #entry_body
+                        }
+                    }),
+                )
             }
         };
         let state_machine_methods: syn::ItemImpl = parse_quote! {
             impl #state_machine_name {
                 /// Create a new state machine
-                pub fn new(initial: #state_enum_name) -> Self {
-                    Self { state: initial }
+                pub fn new(initial: #state_enum_name #context_init) -> Self {
+                    #store_initial
+                    Self { state: initial #context_arg }
                 }
+                #context_accessors
                 /// Get a reference to the current state of the state machine
                 pub fn state(&self) -> &#state_enum_name {
                     &self.state
                 }
                 /// Get a mutable reference to the current state of the state machine
+                #state_mut_docs
                 pub fn state_mut(&mut self) -> &mut #state_enum_name {
                     &mut self.state
                 }
                 /// Transition the state machine
                 #[must_use = "The state must be inspected and transitioned through the returned enum"]
-                pub fn entry(&mut self) -> #entry_enum_name {
-                    match &mut self.state {
-                        #(#entry_construction)*
-                    }
+                pub fn #entry_fn(&mut self) -> #entry_enum_name {
+                    #entry_body
                 }
+                #pool_entry
             }
         };
+        let entry_cell_fn = ident(format!("{}_cell", entry_fn.unraw()));
+        let entry_guard: Vec<syn::Item> = match self.entry_cell {
+            false => vec![],
+            true => {
+                let comment = format!(
+                    "A mutable borrow of a [`{}`] in a [`RefCell`](::core::cell::RefCell), see [`{}::{}`].",
+                    state_machine_name, state_machine_name, entry_cell_fn
+                );
+                let mut items: Vec<syn::Item> = vec![
+                    parse_quote! {
+                        impl #state_machine_name {
+                            /// Mutably borrow the state machine in `cell`, to transition it.
+                            ///
+                            /// # Panics
+                            /// If `cell` is already borrowed.
+                            pub fn #entry_cell_fn(cell: &::core::cell::RefCell<Self>) -> #entry_guard_name<'_> {
+                                #entry_guard_name(cell.borrow_mut())
+                            }
+                        }
+                    },
+                    parse_quote! {
+                        #[doc = #comment]
+                        ///
+                        /// The state machine stays borrowed until this is dropped.
+                        pub struct #entry_guard_name<'a>(::core::cell::RefMut<'a, #state_machine_name>);
+                    },
+                    parse_quote! {
+                        impl #entry_guard_name<'_> {
+                            /// Transition the state machine
+                            #[must_use = "The state must be inspected and transitioned through the returned enum"]
+                            pub fn #entry_fn(&mut self) -> #entry_enum_name {
+                                self.0.#entry_fn()
+                            }
+                        }
+                    },
+                    parse_quote! {
+                        impl ::core::ops::Deref for #entry_guard_name<'_> {
+                            type Target = #state_machine_name;
+                            fn deref(&self) -> &#state_machine_name {
+                                &self.0
+                            }
+                        }
+                    },
+                    parse_quote! {
+                        impl ::core::ops::DerefMut for #entry_guard_name<'_> {
+                            fn deref_mut(&mut self) -> &mut #state_machine_name {
+                                &mut self.0
+                            }
+                        }
+                    },
+                ];
+                if self.fmt {
+                    items.push(parse_quote! {
+                        impl ::core::fmt::Debug for #entry_guard_name<'_> {
+                            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                                f.debug_struct(::core::stringify!(#entry_guard_name)).finish_non_exhaustive()
+                            }
+                        }
+                    })
+                }
+                items
+            }
+        };
+        let pool: Vec<syn::Item> = match self.pool {
+            false => vec![],
+            true => {
+                let id_comment = format!(
+                    "Identifies a machine in a [`{}`], which is never reused once the machine is removed.",
+                    pool_name
+                );
+                let pool_comment = format!(
+                    "Many instances of [`{}`], addressed by [`{}`].",
+                    state_machine_name, pool_id_name
+                );
+                let index_comment = format!(
+                    "Machines are only transitioned through [`{}::{}`], so the pool can keep an index of their states.",
+                    pool_name, entry_fn
+                );
+                let entry_comment = format!(
+                    "Transition the state machine with this id, see [`{}::{}`].",
+                    state_machine_name, entry_fn
+                );
+                let debug = self.fmt.then(|| quote!(Debug,));
+                let vec = quote!(__fsmentry_alloc::vec::Vec);
+                let machine = &state_machine_name;
+                let id = &pool_id_name;
+                let kinds = self.nodes.len();
+                let mut items: Vec<syn::Item> = vec![
+                    parse_quote! {
+                        #[doc(hidden)]
+                        extern crate alloc as __fsmentry_alloc;
+                    },
+                    parse_quote! {
+                        #[doc = #id_comment]
+                        #[derive(#debug Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+                        pub struct #id {
+                            index: u32,
+                            generation: u32,
+                        }
+                    },
+                    parse_quote! {
+                        #[doc = #pool_comment]
+                        ///
+                        #[doc = #index_comment]
+                        pub struct #pool_name {
+                            /// The generation of each slot, which changes when its machine is removed.
+                            slots: #vec<(u32, ::core::option::Option<#machine>)>,
+                            free: #vec<u32>,
+                            /// Slots whose generation ran out, which are never reused.
+                            retired: usize,
+                            index: #pool_index_name,
+                        }
+                    },
+                    parse_quote! {
+                        /// The slots of a pool in each state, updated by transition methods.
+                        #[doc(hidden)]
+                        #[allow(non_camel_case_types)]
+                        struct #pool_index_name {
+                            /// Indexed by `StateKind as usize`.
+                            by_kind: [#vec<u32>; #kinds],
+                            /// The position of each slot in its list in `by_kind`.
+                            positions: #vec<u32>,
+                        }
+                    },
+                    parse_quote! {
+                        impl #pool_index_name {
+                            pub fn insert(&mut self, slot: u32, kind: #kind_enum_name) {
+                                let slots = &mut self.by_kind[kind as usize];
+                                let position = slots.len() as u32;
+                                match self.positions.get_mut(slot as usize) {
+                                    ::core::option::Option::Some(it) => *it = position,
+                                    // slots are created in order
+                                    ::core::option::Option::None => self.positions.push(position),
+                                }
+                                slots.push(slot);
+                            }
+                            pub fn remove(&mut self, slot: u32, kind: #kind_enum_name) {
+                                let slots = &mut self.by_kind[kind as usize];
+                                let position = self.positions[slot as usize];
+                                slots.swap_remove(position as usize);
+                                if let ::core::option::Option::Some(moved) = slots.get(position as usize) {
+                                    self.positions[*moved as usize] = position;
+                                }
+                            }
+                            pub fn moved(&mut self, slot: u32, from: #kind_enum_name, to: #kind_enum_name) {
+                                if from != to {
+                                    self.remove(slot, from);
+                                    self.insert(slot, to);
+                                }
+                            }
+                        }
+                    },
+                    parse_quote! {
+                        impl #pool_name {
+                            /// Create an empty pool
+                            pub const fn new() -> Self {
+                                const EMPTY: #vec<u32> = #vec::new();
+                                Self {
+                                    slots: #vec::new(),
+                                    free: #vec::new(),
+                                    retired: 0,
+                                    index: #pool_index_name { by_kind: [EMPTY; #kinds], positions: #vec::new() },
+                                }
+                            }
+                            /// The number of machines in the pool
+                            pub fn len(&self) -> usize {
+                                self.slots.len() - self.free.len() - self.retired
+                            }
+                            /// Whether there are no machines in the pool
+                            pub fn is_empty(&self) -> bool {
+                                self.len() == 0
+                            }
+                            /// Add a machine to the pool, returning its id
+                            pub fn insert(&mut self, machine: #machine) -> #id {
+                                let kind = machine.state().kind();
+                                let id = match self.free.pop() {
+                                    ::core::option::Option::Some(index) => {
+                                        let (generation, slot) = &mut self.slots[index as usize];
+                                        *slot = ::core::option::Option::Some(machine);
+                                        #id { index, generation: *generation }
+                                    }
+                                    ::core::option::Option::None => {
+                                        let index = ::core::convert::TryFrom::try_from(self.slots.len())
+                                            .expect("there are at most `u32::MAX` machines in a pool");
+                                        self.slots.push((0, ::core::option::Option::Some(machine)));
+                                        #id { index, generation: 0 }
+                                    }
+                                };
+                                self.index.insert(id.index, kind);
+                                id
+                            }
+                            /// Remove a machine from the pool, after which its id refers to nothing
+                            pub fn remove(&mut self, id: #id) -> ::core::option::Option<#machine> {
+                                let (generation, slot) = self.slots.get_mut(id.index as usize)?;
+                                if *generation != id.generation {
+                                    return ::core::option::Option::None;
+                                }
+                                let machine = slot.take()?;
+                                // like `slotmap`, retire the slot rather than let its generation wrap
+                                match generation.checked_add(1) {
+                                    ::core::option::Option::Some(next) => {
+                                        *generation = next;
+                                        self.free.push(id.index);
+                                    }
+                                    ::core::option::Option::None => self.retired += 1,
+                                }
+                                self.index.remove(id.index, machine.state().kind());
+                                ::core::option::Option::Some(machine)
+                            }
+                            /// Get a reference to the machine with this id
+                            pub fn get(&self, id: #id) -> ::core::option::Option<&#machine> {
+                                match self.slots.get(id.index as usize)? {
+                                    (generation, ::core::option::Option::Some(machine)) if *generation == id.generation => ::core::option::Option::Some(machine),
+                                    _ => ::core::option::Option::None,
+                                }
+                            }
+                            #[doc = #entry_comment]
+                            pub fn #entry_fn(&mut self, id: #id) -> ::core::option::Option<#entry_enum_name> {
+                                match self.slots.get_mut(id.index as usize)? {
+                                    (generation, ::core::option::Option::Some(machine)) if *generation == id.generation => ::core::option::Option::Some(
+                                        machine.__fsmentry_entry(::core::option::Option::Some((&mut self.index, id.index))),
+                                    ),
+                                    _ => ::core::option::Option::None,
+                                }
+                            }
+                            /// Iterate over the machines in the pool, with their ids
+                            pub fn iter(&self) -> impl ::core::iter::Iterator<Item = (#id, &#machine)> + '_ {
+                                self.slots.iter().enumerate().filter_map(|(index, (generation, slot))| {
+                                    ::core::option::Option::Some((#id { index: index as u32, generation: *generation }, slot.as_ref()?))
+                                })
+                            }
+                            /// Iterate over the machines in the given state, with their ids,
+                            /// without visiting the others
+                            pub fn in_state(&self, kind: #kind_enum_name) -> impl ::core::iter::Iterator<Item = (#id, &#machine)> + '_ {
+                                self.index.by_kind[kind as usize].iter().map(move |index| {
+                                    let (generation, slot) = &self.slots[*index as usize];
+                                    let machine = slot.as_ref().expect("only machines in the pool are indexed");
+                                    (#id { index: *index, generation: *generation }, machine)
+                                })
+                            }
+                            /// The number of machines in the given state
+                            pub fn count_in_state(&self, kind: #kind_enum_name) -> usize {
+                                self.index.by_kind[kind as usize].len()
+                            }
+                        }
+                    },
+                    parse_quote! {
+                        impl ::core::default::Default for #pool_name {
+                            fn default() -> Self {
+                                Self::new()
+                            }
+                        }
+                    },
+                ];
+                if self.fmt {
+                    items.push(parse_quote! {
+                        impl ::core::fmt::Debug for #pool_name {
+                            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                                f.debug_struct(::core::stringify!(#pool_name))
+                                    .field("len", &self.len())
+                                    .finish_non_exhaustive()
+                            }
+                        }
+                    })
+                }
+                items
+            }
+        };
+        // each state pushed exactly one variant, in order
+        for (variant, NodeData { serde, .. }) in state_variants.iter_mut().zip(self.nodes.values())
+        {
+            variant.attrs.extend(serde.iter().cloned())
+        }
+        let serde_derive = (self.serde != Serde::Off)
+            .then(|| quote!(#[derive(::serde::Serialize, ::serde::Deserialize)]));
+        let serde_tagging = match (self.serde, &self.serde_tagging) {
+            (Serde::Untagged, _) => Some(quote!(#[serde(untagged)])),
+            (Serde::Tagged, (Some(tag), None)) => Some(quote!(#[serde(tag = #tag)])),
+            (Serde::Tagged, (Some(tag), Some(content))) => {
+                Some(quote!(#[serde(tag = #tag, content = #content)]))
+            }
+            _ => None,
+        };
         let attrs = &self.attributes;
+        let derive = Derives::attr(&self.derives.state);
         let state_enum: syn::ItemEnum = parse_quote! {
             #(#attrs)*
+            #derive
+            #serde_derive
+            #serde_tagging
             pub enum #state_enum_name {
                 #state_variants
             }
         };
+        let comment = format!(
+            "as an alternative to matching on [`{}::{}`].",
+            state_machine_name, self.entry_fn
+        );
+        let emit_state = self.emit != Emit::EntryOnly;
+        let emit_entry = self.emit != Emit::StateOnly;
+        let visit_method = emit_entry.then(|| {
+            quote! {
+                /// Call the method on `visitor` for the current state,
+                #[doc = #comment]
+                pub fn visit(&mut self, visitor: &mut impl #visitor_trait_name #context_param) {
+                    match &mut *self {
+                        #(#visit_arms)*
+                    }
+                }
+            }
+        });
+        // only when some state is marked, since the answer would always be `false`
+        let terminal = self
+            .nodes
+            .iter()
+            .filter(|(_, it)| it.terminal)
+            .map(|(node, _)| node.variant())
+            .collect::<Vec<_>>();
+        let is_terminal = (!terminal.is_empty()).then(|| {
+            quote! {
+                /// Whether this state was marked `#[fsmentry(terminal)]`, so may not be left.
+                pub fn is_terminal(&self) -> bool {
+                    ::core::matches!(self, #(Self::#terminal { .. })|*)
+                }
+            }
+        });
+        let mermaid = self.mermaid();
+        let dot = self.dot().to_token_stream().to_string();
+        let state_methods: syn::ItemImpl = parse_quote! {
+            impl #state_enum_name {
+                /// A [`mermaid`](https://mermaid.js.org/) state diagram of the state machine.
+                pub const MERMAID: &'static str = #mermaid;
+                /// A diagram of the state machine in [`DOT`](https://en.wikipedia.org/wiki/DOT_%28graph_description_language%29).
+                pub const DOT: &'static str = #dot;
+                /// Which state this is, without any data.
+                pub fn kind(&self) -> #kind_enum_name {
+                    match self {
+                        #(#kind_arms)*
+                    }
+                }
+                #visit_method
+                #is_terminal
+            }
+        };
+        let state_accessors: Option<syn::ItemImpl> = self.state_accessors.then(|| {
+            let mut methods = Vec::<syn::ImplItemFn>::new();
+            for (node, NodeData { ty, .. }) in &self.nodes {
+                let name = node.variant();
+                let snake = name.to_string().to_snake_case();
+                let is = ident(format!("is_{}", snake));
+                let is_doc = format!("Whether this is [`{}::{}`].", state_enum_name, name);
+                methods.push(parse_quote! {
+                    #[doc = #is_doc]
+                    pub fn #is(&self) -> bool {
+                        ::core::matches!(self, Self::#name { .. })
+                    }
+                });
+                // records are returned as tuples, like from transitions
+                let (pattern, data, data_ty, ref_ty, mut_ty) = match ty {
+                    None => continue,
+                    Some(Data::Tuple(ty)) => (
+                        quote!((data)),
+                        quote!(data),
+                        quote!(#ty),
+                        quote!(&#ty),
+                        quote!(&mut #ty),
+                    ),
+                    Some(Data::Record(fields)) => {
+                        let names = fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                        let tys = fields.iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+                        (
+                            quote!({ #(#names),* }),
+                            quote!((#(#names,)*)),
+                            quote!((#(#tys,)*)),
+                            quote!((#(&#tys,)*)),
+                            quote!((#(&mut #tys,)*)),
+                        )
+                    }
+                };
+                let (as_, as_mut, try_into) = (
+                    ident(format!("as_{}", snake)),
+                    ident(format!("as_{}_mut", snake)),
+                    ident(format!("try_into_{}", snake)),
+                );
+                let as_doc = format!(
+                    "A reference to the data in [`{}::{}`], if this is that state.",
+                    state_enum_name, name
+                );
+                let as_mut_doc = format!(
+                    "A mutable reference to the data in [`{}::{}`], if this is that state.",
+                    state_enum_name, name
+                );
+                let try_into_doc = format!(
+                    "Take the data out of [`{}::{}`], or give back the state if it is another.",
+                    state_enum_name, name
+                );
+                methods.push(parse_quote! {
+                    #[doc = #as_doc]
+                    pub fn #as_(&self) -> ::core::option::Option<#ref_ty> {
+                        match self {
+                            Self::#name #pattern => ::core::option::Option::Some(#data),
+                            _ => ::core::option::Option::None,
+                        }
+                    }
+                });
+                methods.push(parse_quote! {
+                    #[doc = #as_mut_doc]
+                    pub fn #as_mut(&mut self) -> ::core::option::Option<#mut_ty> {
+                        match self {
+                            Self::#name #pattern => ::core::option::Option::Some(#data),
+                            _ => ::core::option::Option::None,
+                        }
+                    }
+                });
+                methods.push(parse_quote! {
+                    #[doc = #try_into_doc]
+                    pub fn #try_into(self) -> ::core::result::Result<#data_ty, Self> {
+                        match self {
+                            Self::#name #pattern => ::core::result::Result::Ok(#data),
+                            other => ::core::result::Result::Err(other),
+                        }
+                    }
+                });
+            }
+            // the other arms can't match a machine with one state
+            let allow = (self.nodes.len() == 1).then(|| quote!(#[allow(unreachable_patterns)]));
+            parse_quote! {
+                #allow
+                impl #state_enum_name {
+                    #(#methods)*
+                }
+            }
+        });
+        let initial = self.initial.as_ref().map(|node| {
+            let node_variant_name = node.variant();
+            let (params, construct) = match &self.nodes[node].ty {
+                None => (vec![], quote!(Self::#node_variant_name)),
+                Some(Data::Tuple(ty)) => (
+                    vec![quote!(data: #ty)],
+                    quote!(Self::#node_variant_name(data)),
+                ),
+                Some(Data::Record(fields)) => {
+                    let names = fields.iter().map(|(name, _)| name);
+                    (
+                        fields.iter().map(|(name, ty)| quote!(#name: #ty)).collect(),
+                        quote!(Self::#node_variant_name { #(#names),* }),
+                    )
+                }
+            };
+            let comment = format!(
+                "The initial state, [`{}::{}`].",
+                state_enum_name, node_variant_name
+            );
+            // there is nothing to default the data to
+            let default: Option<syn::ItemImpl> = params.is_empty().then(|| {
+                parse_quote! {
+                    impl ::core::default::Default for #state_enum_name {
+                        fn default() -> Self {
+                            Self::new()
+                        }
+                    }
+                }
+            });
+            let new: syn::ItemImpl = parse_quote! {
+                impl #state_enum_name {
+                    #[doc = #comment]
+                    pub fn new(#(#params),*) -> Self {
+                        #construct
+                    }
+                }
+            };
+            iter::once(new).chain(default)
+        });
+        let initial = initial.into_iter().flatten();
+        let comment = format!(
+            "The variants of [`{}`], without their data.",
+            state_enum_name
+        );
+        let repr = self.atomic_kind.as_ref().map(|_| quote!(#[repr(u8)]));
+        let debug = self.fmt.then(|| quote!(Debug,));
+        let kind_enum: syn::ItemEnum = parse_quote! {
+            #[doc = #comment]
+            #[derive(#debug Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            #serde_derive
+            #repr
+            pub enum #kind_enum_name {
+                #(#kind_variants),*
+            }
+        };
+        let kind_methods: syn::ItemImpl = parse_quote! {
+            impl #kind_enum_name {
+                /// The name of this state, as written in the definition.
+                pub const fn as_str(self) -> &'static str {
+                    match self {
+                        #(#kind_str_arms)*
+                    }
+                }
+            }
+        };
+        let kind_display: Option<syn::ItemImpl> = self.fmt.then(|| {
+            parse_quote! {
+                impl ::core::fmt::Display for #kind_enum_name {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        f.write_str(self.as_str())
+                    }
+                }
+            }
+        });
+        let from_str: Option<Vec<syn::Item>> = self.from_str.then(|| {
+            let kinds = self.nodes.keys().map(NodeId::variant).collect::<Vec<_>>();
+            let names = kinds.iter().map(Ident::to_string).collect::<Vec<_>>();
+            // states with data can't be created from just their name
+            let dataless = self
+                .nodes
+                .iter()
+                .filter(|(_, it)| it.ty.is_none())
+                .map(|(it, _)| it.variant())
+                .collect::<Vec<_>>();
+            let dataless_names = dataless.iter().map(Ident::to_string).collect::<Vec<_>>();
+            let comment = format!(
+                "The error from parsing a [`{}`] or [`{}`] from a name which isn't one of theirs.",
+                kind_enum_name, state_enum_name
+            );
+            let debug = self.fmt.then(|| quote!(Debug,));
+            let mut items: Vec<syn::Item> = vec![
+                parse_quote! {
+                    #[doc = #comment]
+                    #[derive(#debug Clone, Copy, PartialEq, Eq)]
+                    pub struct #parse_state_error_name {
+                        expected: &'static [&'static str],
+                    }
+                },
+                parse_quote! {
+                    impl #parse_state_error_name {
+                        /// The names which would have parsed.
+                        pub fn expected(&self) -> &'static [&'static str] {
+                            self.expected
+                        }
+                    }
+                },
+                parse_quote! {
+                    impl ::core::str::FromStr for #kind_enum_name {
+                        type Err = #parse_state_error_name;
+                        fn from_str(s: &str) -> ::core::result::Result<Self, Self::Err> {
+                            match s {
+                                #(#names => ::core::result::Result::Ok(Self::#kinds),)*
+                                _ => ::core::result::Result::Err(#parse_state_error_name {
+                                    expected: &[#(#names),*],
+                                }),
+                            }
+                        }
+                    }
+                },
+            ];
+            if self.fmt {
+                items.push(parse_quote! {
+                    impl ::core::fmt::Display for #parse_state_error_name {
+                        fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                            if self.expected.is_empty() {
+                                return f.write_str("only states without data may be parsed, and there are none");
+                            }
+                            f.write_str("expected one of ")?;
+                            for (ix, name) in self.expected.iter().enumerate() {
+                                if ix != 0 {
+                                    f.write_str(", ")?;
+                                }
+                                ::core::write!(f, "`{}`", name)?;
+                            }
+                            ::core::result::Result::Ok(())
+                        }
+                    }
+                })
+            }
+            // a hand-written `State` may have its own
+            if emit_state {
+                items.push(parse_quote! {
+                    /// Only states without data may be parsed.
+                    impl ::core::str::FromStr for #state_enum_name {
+                        type Err = #parse_state_error_name;
+                        fn from_str(s: &str) -> ::core::result::Result<Self, Self::Err> {
+                            match s {
+                                #(#dataless_names => ::core::result::Result::Ok(Self::#dataless),)*
+                                _ => ::core::result::Result::Err(#parse_state_error_name {
+                                    expected: &[#(#dataless_names),*],
+                                }),
+                            }
+                        }
+                    }
+                });
+                if self.fmt {
+                    items.push(parse_quote! {
+                        impl ::core::fmt::Display for #state_enum_name {
+                            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                                f.write_str(self.kind().as_str())
+                            }
+                        }
+                    })
+                }
+            }
+            items
+        });
+        let from_str = from_str.into_iter().flatten();
+        // only needs `&State`, so lives beside it rather than with the entry api
+        let entry_ref: Option<[syn::Item; 3]> = self.entry_ref.then(|| {
+            let lifetime = self
+                .nodes
+                .values()
+                .any(|it| it.ty.is_some())
+                .then(|| quote!(<'a>));
+            let elided = lifetime.as_ref().map(|_| quote!(<'_>));
+            let mut variants = Vec::<syn::Variant>::new();
+            let mut arms = Vec::<syn::Arm>::new();
+            let mut kind_arms = Vec::<syn::Arm>::new();
+            let mut successor_arms = Vec::<syn::Arm>::new();
+            for (node, NodeData { ty, .. }) in &self.nodes {
+                let name = node.variant();
+                let doc = format!("See [`{}::{}`].", state_enum_name, name);
+                match ty {
+                    None => {
+                        variants.push(parse_quote!(#[doc = #doc] #name));
+                        arms.push(parse_quote!(#state_enum_name::#name => #entry_ref_name::#name,));
+                    }
+                    Some(Data::Tuple(ty)) => {
+                        variants.push(parse_quote!(#[doc = #doc] #name(&'a #ty)));
+                        arms.push(parse_quote!(#state_enum_name::#name(data) => #entry_ref_name::#name(data),));
+                    }
+                    Some(Data::Record(fields)) => {
+                        let names = fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                        let tys = fields.iter().map(|(_, ty)| ty);
+                        variants.push(parse_quote!(#[doc = #doc] #name { #(#names: &'a #tys),* }));
+                        arms.push(parse_quote!(#state_enum_name::#name { #(#names),* } => #entry_ref_name::#name { #(#names),* },));
+                    }
+                }
+                kind_arms.push(parse_quote!(#entry_ref_name::#name { .. } => #kind_enum_name::#name,));
+                let successors = self.successor_kinds(node);
+                successor_arms.push(parse_quote!(#entry_ref_name::#name { .. } => &[#(#successors),*],));
+            }
+            let comment = format!(
+                "Read-only access to the current state, from [`{}::entry_ref`].",
+                state_enum_name
+            );
+            [
+                parse_quote! {
+                    #[doc = #comment]
+                    #[derive(Clone, Copy)]
+                    pub enum #entry_ref_name #lifetime {
+                        #(#variants),*
+                    }
+                },
+                parse_quote! {
+                    impl #state_enum_name {
+                        /// Inspect the current state and its data, without transitioning it.
+                        pub fn entry_ref(&self) -> #entry_ref_name #elided {
+                            match self {
+                                #(#arms)*
+                            }
+                        }
+                    }
+                },
+                parse_quote! {
+                    impl #entry_ref_name #elided {
+                        /// Which state this is, without any data.
+                        pub fn kind(&self) -> #kind_enum_name {
+                            match self {
+                                #(#kind_arms)*
+                            }
+                        }
+                        /// The states this state may transition to.
+                        pub fn successors(&self) -> &'static [#kind_enum_name] {
+                            match self {
+                                #(#successor_arms)*
+                            }
+                        }
+                    }
+                },
+            ]
+        });
+        let entry_ref = entry_ref.into_iter().flatten();
+        let atomic_kind: Option<[syn::Item; 2]> = self.atomic_kind.as_ref().map(|_| {
+            let comment = format!(
+                "A [`{}`] which may be shared with interrupt handlers and other threads.",
+                kind_enum_name
+            );
+            let load_arms = kind_variants.iter().map(|syn::Variant { ident, .. }| -> syn::Arm {
+                parse_quote!(it if it == #kind_enum_name::#ident as u8 => #kind_enum_name::#ident,)
+            });
+            [
+                parse_quote! {
+                    #[doc = #comment]
+                    ///
+                    /// The state machine updates this on every transition.
+                    pub struct #atomic_kind_name(::core::sync::atomic::AtomicU8);
+                },
+                parse_quote! {
+                    impl #atomic_kind_name {
+                        pub const fn new(kind: #kind_enum_name) -> Self {
+                            Self(::core::sync::atomic::AtomicU8::new(kind as u8))
+                        }
+                        pub fn load(&self, order: ::core::sync::atomic::Ordering) -> #kind_enum_name {
+                            match self.0.load(order) {
+                                #(#load_arms)*
+                                _ => ::core::unreachable!("only valid kinds are stored"),
+                            }
+                        }
+                        pub fn store(&self, kind: #kind_enum_name, order: ::core::sync::atomic::Ordering) {
+                            self.0.store(kind as u8, order)
+                        }
+                    }
+                },
+            ]
+        });
+        let atomic_kind = atomic_kind.into_iter().flatten();
+        let defmt_state_body = match self.defmt {
+            Defmt::Off => None,
+            Defmt::Data => Some(quote! {
+                match self {
+                    #(#defmt_state_arms)*
+                }
+            }),
+            Defmt::Kind => Some(quote!(::defmt::Format::format(&self.kind(), f))),
+        };
+        let defmt_impls: Option<[syn::ItemImpl; 2]> = defmt_state_body.map(|body| {
+            [
+                parse_quote! {
+                    impl ::defmt::Format for #state_enum_name {
+                        fn format(&self, f: ::defmt::Formatter) {
+                            #body
+                        }
+                    }
+                },
+                parse_quote! {
+                    impl ::defmt::Format for #kind_enum_name {
+                        fn format(&self, f: ::defmt::Formatter) {
+                            match self {
+                                #(#defmt_kind_arms)*
+                            }
+                        }
+                    }
+                },
+            ]
+        });
+        let defmt_impls = defmt_impls.into_iter().flatten();
         let entry_enum_lifetime_param = match entry_has_lifetime {
             false => None,
             true => Some(quote!(<'a>)),
         };
-        let comment = format!("See [`{}::entry`].", state_machine_name);
+        let comment = format!("See [`{}::{}`].", state_machine_name, self.entry_fn);
+        let derive = Derives::attr(&self.derives.entry);
         let entry_enum: syn::ItemEnum = parse_quote! {
             /// Access to the current state with valid transitions for the state machine.
             ///
             #[doc = #comment]
+            #derive
             pub enum #entry_enum_name #entry_enum_lifetime_param {
                 #entry_variants
             }
         };
-        transition_impls.extend(transition_tys.iter().map(|strukt| {
+        let owned_entry: Option<[syn::Item; 2]> = self.owned.then(|| {
+            let comment = format!(
+                "The current state, with transitions which take it by value, see [`{}::into_entry`].",
+                state_enum_name
+            );
+            [
+                parse_quote! {
+                    #[doc = #comment]
+                    pub enum #owned_entry_name {
+                        #owned_variants
+                    }
+                },
+                parse_quote! {
+                    impl #state_enum_name {
+                        /// Take the state by value, to transition it without a state machine.
+                        pub fn into_entry(self) -> #owned_entry_name {
+                            match self {
+                                #(#owned_arms)*
+                            }
+                        }
+                    }
+                },
+            ]
+        });
+        let owned_entry = owned_entry.into_iter().flatten();
+        // there's nothing to implement it for without any transitions
+        let transition_source: Option<Vec<syn::Item>> =
+            (self.transition_source && !transition_tys.is_empty()).then(|| {
+                let transition_source_name = self.transition_source_name();
+                let mut items = vec![parse_quote! {
+                    /// Implemented by the transition type for each state,
+                    /// for code which works with any of them, like logging or metrics.
+                    pub trait #transition_source_name<'a> {
+                        /// The state this transitions from.
+                        fn kind(&self) -> #kind_enum_name;
+                        /// The states this may transition to.
+                        fn available(&self) -> &'static [#kind_enum_name];
+                        /// Give back the entry this came from, without transitioning.
+                        fn into_entry(self) -> #entry_enum_name<'a>
+                        where
+                            Self: Sized;
+                    }
+                }];
+                for node in self.nodes.keys() {
+                    if self.outgoing(node).is_none() {
+                        continue;
+                    }
+                    let transition_ty_name = self.transition_ty(node);
+                    let variant = node.variant();
+                    let available = self.successor_kinds(node);
+                    items.push(parse_quote! {
+                        impl<'a> #transition_source_name<'a> for #transition_ty_name<'a> {
+                            fn kind(&self) -> #kind_enum_name {
+                                #kind_enum_name::#variant
+                            }
+                            fn available(&self) -> &'static [#kind_enum_name] {
+                                &[#(#available),*]
+                            }
+                            fn into_entry(self) -> #entry_enum_name<'a> {
+                                #entry_enum_name::#variant(self)
+                            }
+                        }
+                    })
+                }
+                items
+            });
+        let transition_source_name = transition_source
+            .as_ref()
+            .map(|_| self.transition_source_name());
+        transition_impls.extend(transition_tys.iter().filter(|_| self.fmt).map(|strukt| {
             let ident = &strukt.ident;
             parse_quote! {
                 impl ::core::fmt::Debug for #ident<'_> {
@@ -315,31 +2686,296 @@ impl FSMGenerator {
                 }
             }
         }));
+        let hooks_trait: Option<syn::ItemTrait> =
+            match callbacks.is_empty() && state_hooks.is_empty() {
+                true => None,
+                false => {
+                    let methods = callbacks
+                        .into_iter()
+                        .map(|(callback, (params, transitions))| {
+                            let params = params.iter().map(|(name, ty)| quote!(#name: #ty));
+                            quote! {
+                                /// Called before the following transitions:
+                                #(#transitions)*
+                                fn #callback(&mut self #(, #params)*);
+                            }
+                        });
+                    let state_methods = state_hooks.into_iter().map(|(hook, lines)| {
+                        quote! {
+                            /// Called with the current state:
+                            #(#[doc = #lines])*
+                            fn #hook(&mut self, state: &#state_enum_name);
+                        }
+                    });
+                    Some(parse_quote! {
+                        /// Callbacks attached to transitions and states in the state machine definition.
+                        ///
+                        /// Transition callbacks receive the data of the current state as `from`,
+                        /// and the data for the state which will replace it as `to`,
+                        /// followed by any arguments to the transition.
+                        /// States without data are skipped, and named fields are passed as a tuple.
+                        ///
+                        /// State callbacks are only called by transition methods,
+                        /// not when a state machine is created.
+                        pub trait #hooks_trait_name {
+                            #(#methods)*
+                            #(#state_methods)*
+                        }
+                    })
+                }
+            };
 
-        let vis = &self.vis;
-        let module_name = self.ident.snake_case();
-        let attrs = self
-            .attributes
-            .iter()
-            .filter(|it| it.path().is_ident("doc"));
+        let event_items: Option<[syn::Item; 4]> = self.events.then(|| {
+            let derive = Derives::attr(&self.derives.event);
+            let (variants, names): (Vec<TokenStream>, Vec<syn::Arm>) = events
+                .into_iter()
+                .map(|(method, (args, transitions))| {
+                    let variant_name = method.UpperCamelCase();
+                    let fields = match args.is_empty() {
+                        true => None,
+                        false => {
+                            let args = args.iter().map(|(name, ty)| quote!(#name: #ty));
+                            Some(quote!({ #(#args),* }))
+                        }
+                    };
+                    let name = method.unraw().to_string();
+                    (
+                        quote! {
+                            /// Calls the following transitions:
+                            #(#transitions)*
+                            #variant_name #fields
+                        },
+                        parse_quote!(#event_enum_name::#variant_name { .. } => #name,),
+                    )
+                })
+                .unzip();
+            let hooks_param = handle_hooks.then(|| quote!(, hooks: &mut impl #hooks_trait_name));
+            let comment = format!(
+                "A transition method and its arguments, which may be dispatched with [`{}::handle`].",
+                state_enum_name
+            );
+            let invalid_comment = format!(
+                "Returned by [`{}::handle`] when the current state has no transition for an event, \
+                or the transition's `-requires(..)->` precondition does not hold.",
+                state_enum_name
+            );
+            [
+                parse_quote! {
+                    #[doc = #comment]
+                    #derive
+                    pub enum #event_enum_name {
+                        #(#variants),*
+                    }
+                },
+                parse_quote! {
+                    impl #event_enum_name {
+                        /// The name of the transition method.
+                        pub const fn as_str(&self) -> &'static str {
+                            match *self {
+                                #(#names)*
+                            }
+                        }
+                    }
+                },
+                parse_quote! {
+                    #[doc = #invalid_comment]
+                    pub struct #invalid_transition_name {
+                        /// The state, which is unchanged.
+                        pub state: #kind_enum_name,
+                        /// The event, which may be handled again later.
+                        pub event: #event_enum_name,
+                        /// Whether the state has a transition for the event,
+                        /// but its `-requires(..)->` precondition does not hold.
+                        pub precondition: bool,
+                    }
+                },
+                parse_quote! {
+                    impl #state_enum_name {
+                        /// Call the transition method for `event` on the current state,
+                        /// dropping any data from the state being left.
+                        pub fn handle(&mut self, event: #event_enum_name #context_param #hooks_param) -> ::core::result::Result<(), #invalid_transition_name> {
+                            match (self.kind(), event) {
+                                #(#handle_arms)*
+                                #[allow(unreachable_patterns)]
+                                (state, event) => ::core::result::Result::Err(#invalid_transition_name { state, event, precondition: false }),
+                            }
+                        }
+                    }
+                },
+            ]
+        });
+        let invalid_transition_fmt: Option<[syn::ItemImpl; 2]> = (self.events && self.fmt).then(|| {
+            [
+                parse_quote! {
+                    impl ::core::fmt::Debug for #invalid_transition_name {
+                        fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                            f.debug_struct(::core::stringify!(#invalid_transition_name))
+                                .field("state", &self.state)
+                                .field("event", &self.event.as_str())
+                                .field("precondition", &self.precondition)
+                                .finish()
+                        }
+                    }
+                },
+                parse_quote! {
+                    impl ::core::fmt::Display for #invalid_transition_name {
+                        fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                            match self.precondition {
+                                true => ::core::write!(f, "the precondition of `{}` from `{}` does not hold", self.event.as_str(), self.state.as_str()),
+                                false => ::core::write!(f, "`{}` has no transition `{}`", self.state.as_str(), self.event.as_str()),
+                            }
+                        }
+                    }
+                },
+            ]
+        });
 
-        parse_quote! {
-            #(#attrs)*
-            #vis mod #module_name {
-                #state_machine_struct
-                #state_machine_methods
-                #state_enum
-                #entry_enum
-                #(#transition_tys)*
-                #(#transition_impls)*
+        let event_items = event_items.into_iter().flatten();
+        let invalid_transition_fmt = invalid_transition_fmt.into_iter().flatten();
+
+        let comment = format!(
+            "Handle each state of the state machine in a separate method, see [`{}::visit`].",
+            state_enum_name
+        );
+        let visitor_trait: syn::ItemTrait = parse_quote! {
+            #[doc = #comment]
+            ///
+            /// Every method does nothing by default,
+            /// so implementations continue to compile when states are added.
+            pub trait #visitor_trait_name {
+                #(#visitor_methods)*
             }
+        };
+
+        let module_name = &self.module;
+        let allow = &self.allow;
+        let allow =
+            (!allow.is_empty()).then(|| -> syn::Attribute { parse_quote!(#[allow(#(#allow),*)]) });
+        // restricted transition types appear in `Entry` and `Visitor`,
+        // and can't be re-exported by `entry_mod`
+        let handles_pub = matches!(self.handle_vis, syn::Visibility::Public(_));
+        let private_interfaces = (!handles_pub)
+            .then(|| -> syn::Attribute { parse_quote!(#[allow(private_interfaces)]) });
+
+        // the generated names in each layer, for `state_mod` and `entry_mod`
+        let state_names = [
+            emit_entry.then_some(&state_machine_name),
+            emit_state.then_some(&state_enum_name),
+            Some(&kind_enum_name),
+            self.entry_ref.then_some(&entry_ref_name),
+            self.from_str.then_some(&parse_state_error_name),
+            self.atomic_kind.as_ref().map(|_| &atomic_kind_name),
+            (emit_entry && self.events).then_some(&event_enum_name),
+            (emit_entry && self.events).then_some(&invalid_transition_name),
+            (emit_entry && self.pool).then_some(&pool_name),
+            (emit_entry && self.pool).then_some(&pool_id_name),
+        ];
+        let entry_names = [&entry_enum_name, &visitor_trait_name]
+            .into_iter()
+            .chain(self.entry_cell.then_some(&entry_guard_name))
+            .chain(hooks_trait.as_ref().map(|it| &it.ident))
+            .chain(self.owned.then_some(&owned_entry_name))
+            .chain(&transition_source_name)
+            .chain(
+                transition_tys
+                    .iter()
+                    .map(|it| &it.ident)
+                    .chain(owned_tys.iter().map(|it| &it.ident))
+                    .filter(|_| handles_pub),
+            )
+            .filter(|_| emit_entry);
+        let reexports: Vec<syn::Item> = [
+            (
+                &self.state_mod,
+                state_names
+                    .into_iter()
+                    .flatten()
+                    .chain(self.aliases.iter().map(|it| &it.ident))
+                    .collect::<Vec<_>>(),
+            ),
+            (&self.entry_mod, entry_names.collect()),
+        ]
+        .into_iter()
+        .filter_map(|(module, names)| {
+            let ReexportMod { vis, ident } = module.as_ref()?;
+            Some(parse_quote! {
+                #allow
+                #vis mod #ident {
+                    pub use super::#module_name::{#(#names),*};
+                }
+            })
+        })
+        .collect();
+
+        // items are moved into the module as they are, rather than quoted and parsed again,
+        // so large machines aren't held in memory twice over
+        let mut items = Vec::<syn::Item>::new();
+        items.extend(
+            self.aliases.iter().map(
+                |TypeAlias { docs, ident, ty }| parse_quote!(#(#docs)* pub type #ident = #ty;),
+            ),
+        );
+        if emit_entry {
+            items.push(state_machine_struct.into());
+            items.push(state_machine_methods.into());
+            items.extend(pool);
+        }
+        items.push(match emit_state {
+            true => state_enum.into(),
+            false => parse_quote!(use super::#state_enum_name;),
+        });
+        items.push(state_methods.into());
+        items.extend(state_accessors.map(syn::Item::from));
+        items.extend(initial.map(syn::Item::from));
+        items.push(kind_enum.into());
+        items.push(kind_methods.into());
+        items.extend(kind_display.map(syn::Item::from));
+        items.extend(from_str);
+        items.extend(entry_ref);
+        items.extend(atomic_kind);
+        items.extend(defmt_impls.map(syn::Item::from));
+        if emit_entry {
+            items.push(entry_enum.into());
+            items.extend(entry_guard);
+            items.extend(transition_tys.into_iter().map(syn::Item::from));
+            items.extend(transition_impls.into_iter().map(syn::Item::from));
+            items.extend(transition_source.into_iter().flatten());
+            items.extend(owned_entry);
+            items.extend(owned_tys.into_iter().map(syn::Item::from));
+            items.extend(owned_impls.into_iter().map(syn::Item::from));
+            items.extend(hooks_trait.map(syn::Item::from));
+            items.push(visitor_trait.into());
+            items.extend(event_items);
+            items.extend(invalid_transition_fmt.map(syn::Item::from));
+        }
+
+        let module = syn::ItemMod {
+            attrs: self
+                .attributes
+                .iter()
+                .filter(|it| it.path().is_ident("doc"))
+                .cloned()
+                .chain(allow)
+                .chain(private_interfaces)
+                .collect(),
+            vis: self.vis.clone(),
+            unsafety: None,
+            mod_token: Default::default(),
+            ident: module_name.clone(),
+            content: Some((Default::default(), items)),
+            semi: None,
+        };
+        syn::File {
+            shebang: None,
+            attrs: vec![],
+            items: iter::once(module.into()).chain(reexports).collect(),
         }
     }
     /// Get a basic representation of this graph in dot, suitable for documenting the state machine.
     pub fn dot(&self) -> syn_graphs::dot::Graph {
         use syn_graphs::dot::{
             kw, pun, EdgeDirectedness, EdgeTarget, Graph, GraphDirectedness, NodeId as DotNodeId,
-            Stmt, StmtEdge, StmtList, StmtNode, ID,
+            Stmt, StmtAssign, StmtEdge, StmtList, StmtNode, StmtSubgraph, ID,
         };
         fn conv_node_id(NodeId { inner }: NodeId) -> DotNodeId {
             DotNodeId {
@@ -350,17 +2986,28 @@ impl FSMGenerator {
 
         let span = Span::call_site();
         let mut stmts = vec![];
+        let assign = |left: &str, right: &str| {
+            Stmt::Assign(StmtAssign {
+                left: ID::AnyIdent(ident(left)),
+                eq_token: Token![=](span),
+                right: ID::AnyIdent(ident(right)),
+            })
+        };
 
-        for node_id in self.nodes.keys() {
+        if let Some(rankdir) = self.rankdir {
+            stmts.push((assign("rankdir", rankdir.as_str()), Some(Token![;](span))))
+        }
+        for (node_id, NodeData { label, .. }) in &self.nodes {
             stmts.push((
                 Stmt::Node(StmtNode {
                     node_id: conv_node_id(node_id.clone()),
-                    attrs: None,
+                    attrs: label.as_ref().map(|label| parse_quote!([label = #label])),
                 }),
                 Some(Token![;](span)),
             ))
         }
-        for (from, to) in self.edges.keys() {
+        for ((from, to, method), data) in &self.edges {
+            let dashed = data.cfg.is_some() && self.mark_cfg;
             stmts.push((
                 Stmt::Edge(StmtEdge {
                     from: EdgeTarget::NodeId(conv_node_id(from.clone())),
@@ -368,28 +3015,336 @@ impl FSMGenerator {
                         EdgeDirectedness::Directed(pun::DirectedEdge(span)),
                         EdgeTarget::NodeId(conv_node_id(to.clone())),
                     )],
-                    attrs: None,
+                    attrs: self.edge_label(to, method, data).map(|label| match dashed {
+                        true => parse_quote!([style = dashed, label = #label]),
+                        false => parse_quote!([label = #label]),
+                    }),
                 }),
                 Some(Token![;](span)),
             ))
         }
+        for (rank, nodes) in &self.ranks {
+            let mut inner = vec![(assign("rank", rank.as_str()), Some(Token![;](span)))];
+            for node_id in nodes {
+                inner.push((
+                    Stmt::Node(StmtNode {
+                        node_id: conv_node_id(node_id.clone()),
+                        attrs: None,
+                    }),
+                    Some(Token![;](span)),
+                ))
+            }
+            stmts.push((
+                Stmt::Subgraph(StmtSubgraph {
+                    prelude: None,
+                    brace_token: token::Brace(span),
+                    statements: StmtList { stmts: inner },
+                }),
+                None,
+            ))
+        }
+        // a strict graph would merge parallel edges
+        let mut pairs = self.edges.keys().map(|(from, to, _)| (from, to));
+        let parallel = pairs
+            .clone()
+            .zip(pairs.by_ref().skip(1))
+            .any(|(l, r)| l == r);
 
         Graph {
-            strict: Some(kw::strict(span)),
+            strict: (!parallel).then(|| kw::strict(span)),
             directedness: GraphDirectedness::Digraph(kw::digraph(span)),
             id: Some(ID::AnyIdent(self.ident.clone())),
             brace_token: token::Brace(span),
             stmt_list: StmtList { stmts },
         }
     }
+    /// Get a basic representation of this graph as a [`mermaid`](https://mermaid.js.org/) state diagram.
+    ///
+    /// The diagram has an accessible title and description for screen readers,
+    /// from [`Self::accessible_summary`] and [`Self::accessible_adjacency`].
+    pub fn mermaid(&self) -> String {
+        let mut s = String::from("stateDiagram-v2\n");
+        s.push_str(&format!("    accTitle: {}\n", self.accessible_summary()));
+        s.push_str("    accDescr {\n");
+        for line in self.accessible_adjacency() {
+            s.push_str(&format!("        {}\n", line))
+        }
+        s.push_str("    }\n");
+        if let Some(rankdir) = self.rankdir {
+            s.push_str(&format!("    direction {}\n", rankdir.as_str()))
+        }
+        for (node, NodeData { label, .. }) in &self.nodes {
+            match label {
+                Some(label) => s.push_str(&format!(
+                    "    state \"{}\" as {}\n",
+                    label.replace('"', "#quot;"),
+                    node.inner
+                )),
+                None => s.push_str(&format!("    {}\n", node.inner)),
+            }
+        }
+        for ((from, to, method), data) in &self.edges {
+            match self.edge_label(to, method, data) {
+                Some(label) => {
+                    s.push_str(&format!("    {} --> {}: {}\n", from.inner, to.inner, label))
+                }
+                None => s.push_str(&format!("    {} --> {}\n", from.inner, to.inner)),
+            }
+        }
+        s
+    }
+    /// A sentence describing the shape of the graph, for readers who can't see the diagram.
+    pub fn accessible_summary(&self) -> String {
+        let plural = |n: usize, what: &str| match n {
+            1 => format!("1 {}", what),
+            n => format!("{} {}s", n, what),
+        };
+        let mut s = format!(
+            "State machine {} with {} and {}.",
+            self.ident,
+            plural(self.nodes.len(), "state"),
+            plural(self.edges.len(), "transition")
+        );
+        // as marked, to agree with `State::is_terminal`
+        let terminal = self
+            .nodes
+            .iter()
+            .filter(|(_, it)| it.terminal)
+            .map(|(it, _)| it.inner.to_string())
+            .collect::<Vec<_>>();
+        match &*terminal {
+            [] => {}
+            [it] => s.push_str(&format!(" The terminal state is {}.", it)),
+            them => s.push_str(&format!(" The terminal states are {}.", them.join(", "))),
+        }
+        s
+    }
+    /// A line for each state, listing the states it can transition to.
+    pub fn accessible_adjacency(&self) -> Vec<String> {
+        self.nodes
+            .keys()
+            .map(|node| match self.outgoing(node) {
+                Some(outgoing) => {
+                    let mut to = outgoing
+                        .iter()
+                        .map(|(it, _, _)| it.inner.to_string())
+                        .collect::<Vec<_>>();
+                    to.dedup();
+                    format!("{} goes to {}.", node.inner, to.join(", "))
+                }
+                None if self.nodes[node].terminal => format!("{} is terminal.", node.inner),
+                None => format!("{} has no transitions.", node.inner),
+            })
+            .collect()
+    }
+    /// Give the diagram an accessible name and description, for readers who can't see it.
+    fn label_svg(&self, svg: &str) -> String {
+        let Some(start) = svg.find("<svg") else {
+            return svg.to_owned();
+        };
+        let Some(end) = svg[start..].find('>').map(|it| start + it) else {
+            return svg.to_owned();
+        };
+        let summary = xml_escape(&self.accessible_summary());
+        let adjacency = xml_escape(&self.accessible_adjacency().join(" "));
+        format!(
+            "{} role=\"img\" aria-label=\"{}\"><title>{}</title><desc>{}</desc>{}",
+            svg[..end].trim_end_matches('/'),
+            summary,
+            summary,
+            adjacency,
+            &svg[end + 1..]
+        )
+    }
+    /// The name of the transition method if it was given explicitly,
+    /// and the `#[cfg(..)]` of the transition if it should be shown.
+    fn edge_label(&self, to: &NodeId, method: &Ident, data: &EdgeData) -> Option<String> {
+        let mut label = vec![];
+        if *method != to.transition_fn(&self.method_case) {
+            label.push(method.to_string())
+        }
+        if let (Some(cfg), true) = (&data.cfg, self.mark_cfg) {
+            label.push(format!("cfg({})", cfg))
+        }
+        match label.is_empty() {
+            true => None,
+            false => Some(label.join(", ")),
+        }
+    }
+    /// Problems with the definition, which should be reported to the user.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+    /// The name of the state machine, as it was defined.
+    pub fn name(&self) -> &Ident {
+        &self.ident
+    }
     fn state_enum_name(&self) -> Ident {
         ident("State")
     }
     fn entry_enum_name(&self) -> Ident {
         ident("Entry")
     }
+    fn hooks_trait_name(&self) -> Ident {
+        ident("Hooks")
+    }
+    fn visitor_trait_name(&self) -> Ident {
+        ident("Visitor")
+    }
+    fn kind_enum_name(&self) -> Ident {
+        ident("StateKind")
+    }
+    fn owned_entry_name(&self) -> Ident {
+        ident("OwnedEntry")
+    }
+    fn transition_source_name(&self) -> Ident {
+        ident("TransitionSource")
+    }
+    fn entry_ref_name(&self) -> Ident {
+        ident("EntryRef")
+    }
+    fn parse_state_error_name(&self) -> Ident {
+        ident("ParseStateError")
+    }
+    fn atomic_kind_name(&self) -> Ident {
+        ident("AtomicStateKind")
+    }
+    fn event_enum_name(&self) -> Ident {
+        ident("Event")
+    }
+    fn invalid_transition_name(&self) -> Ident {
+        ident("InvalidTransition")
+    }
+    /// Diverges for a handle for `expected` which finds the machine in the state bound to `found`,
+    /// reporting it to the `mismatch_hook` before panicking.
+    fn mismatch(&self, on_mismatch: &OnMismatch, expected: &Ident) -> TokenStream {
+        let machine = self.ident.unraw().to_string();
+        let kind_enum_name = self.kind_enum_name();
+        let hook = self
+            .mismatch_hook
+            .as_ref()
+            .map(|it| quote!(#it(#machine, #kind_enum_name::#expected, found);));
+        let diverge = on_mismatch.diverge(quote! {{
+            let found = found.kind();
+            #hook
+            ::core::panic!(
+                "`{}` was in `{}` when a handle for `{}` expected it",
+                #machine,
+                found.as_str(),
+                #kind_enum_name::#expected.as_str(),
+            )
+        }});
+        match on_mismatch {
+            OnMismatch::Panic | OnMismatch::DebugUnchecked => diverge,
+            OnMismatch::Unchecked => quote!({
+                let _ = found;
+                #diverge
+            }),
+            OnMismatch::Expr(block) => {
+                let stmts = &block.stmts;
+                quote!({
+                    let _ = found;
+                    #(#stmts)*
+                })
+            }
+        }
+    }
+    fn entry_guard_name(&self) -> Ident {
+        ident("EntryGuard")
+    }
+    fn pool_names(&self) -> (Ident, Ident) {
+        (ident("MachinePool"), ident("MachineId"))
+    }
+    /// The arguments to a transition method into `to`, before any hooks.
+    fn transition_args(
+        &self,
+        to: &NodeId,
+        params: &[(Ident, syn::Type)],
+    ) -> Vec<(Ident, syn::Type)> {
+        let mut args = match &self.nodes[to].ty {
+            None => vec![],
+            Some(Data::Tuple(ty)) => vec![(ident("next"), ty.clone())],
+            Some(Data::Record(fields)) => fields.clone(),
+        };
+        args.extend(params.iter().cloned());
+        args
+    }
     fn transition_ty(&self, node_id: &NodeId) -> Ident {
-        ident(format!("{}", node_id.inner.UpperCamelCase()))
+        let (prefix, suffix) = &self.handle_affixes;
+        ident(format!(
+            "{}{}{}",
+            prefix,
+            node_id.inner.UpperCamelCase(),
+            suffix
+        ))
+    }
+    /// Each state `node_id` may transition to, as a `StateKind`,
+    /// which exists if any of the transitions to it do.
+    fn successor_kinds(&self, node_id: &NodeId) -> Vec<TokenStream> {
+        let kind_enum_name = self.kind_enum_name();
+        let mut successors = BTreeMap::<&NodeId, Vec<Option<&TokenStream>>>::new();
+        for (to, _, EdgeData { cfg, .. }) in self.outgoing(node_id).unwrap_or_default() {
+            successors.entry(to).or_default().push(cfg.as_ref());
+        }
+        successors
+            .into_iter()
+            .map(|(to, cfgs)| {
+                let to = to.variant();
+                match cfgs.into_iter().collect::<Option<Vec<_>>>() {
+                    None => quote!(#kind_enum_name::#to),
+                    Some(cfgs) => quote!(#[cfg(any(#(#cfgs),*))] #kind_enum_name::#to),
+                }
+            })
+            .collect()
+    }
+    /// The by-value counterpart of [`Self::transition_ty`].
+    fn owned_ty(&self, node_id: &NodeId) -> Ident {
+        ident(format!("Owned{}", self.transition_ty(node_id)))
+    }
+    /// The other types and traits in the generated module, which handles mustn't be named like.
+    fn generated_names(&self) -> Vec<Ident> {
+        let mut names = vec![
+            self.ident.UpperCamelCase(),
+            self.state_enum_name(),
+            self.entry_enum_name(),
+            self.kind_enum_name(),
+            self.visitor_trait_name(),
+        ];
+        if self.atomic_kind.is_some() {
+            names.push(self.atomic_kind_name())
+        }
+        let edge_hooks = self.edges.values().any(|it| it.callback.is_some());
+        let node_hooks = self
+            .nodes
+            .values()
+            .any(|it| it.on_enter.is_some() || it.on_exit.is_some());
+        if edge_hooks || node_hooks {
+            names.push(self.hooks_trait_name())
+        }
+        if self.entry_cell {
+            names.push(self.entry_guard_name())
+        }
+        if self.entry_ref {
+            names.push(self.entry_ref_name())
+        }
+        if self.from_str {
+            names.push(self.parse_state_error_name())
+        }
+        if self.owned {
+            names.push(self.owned_entry_name())
+        }
+        if self.transition_source {
+            names.push(self.transition_source_name())
+        }
+        if self.pool {
+            let (pool, id) = self.pool_names();
+            names.extend([pool, id])
+        }
+        if self.events {
+            names.push(self.event_enum_name());
+            names.push(self.invalid_transition_name());
+        }
+        names
     }
     fn getter_names(&self) -> (Ident, Ident) {
         fn names(root: &str) -> impl Iterator<Item = (Ident, Ident)> + '_ {
@@ -415,32 +3370,43 @@ impl FSMGenerator {
             }) {
                 continue;
             }
+            if self
+                .edges
+                .keys()
+                .any(|(_, _, method)| *method == get || *method == get_mut)
+            {
+                continue;
+            }
             return (get, get_mut);
         }
         unreachable!()
     }
     /// [`None`] if the node is a source
     fn incoming(&self, to: &NodeId) -> Option<Vec<&NodeId>> {
-        let vec = self
+        let mut vec = self
             .edges
-            .iter()
-            .filter_map(move |((src, dst), _)| match dst == to {
+            .keys()
+            .filter_map(move |(src, dst, _)| match dst == to {
                 true => Some(src),
                 false => None,
             })
             .collect::<Vec<_>>();
+        vec.dedup();
         match vec.is_empty() {
             true => None,
             false => Some(vec),
         }
     }
-    /// [`None`] if the node is a sink
-    fn outgoing<'a>(&'a self, from: &'a NodeId) -> Option<Vec<(&NodeId, &[OuterDocString])>> {
+    /// Each destination with the transition method, [`None`] if the node is a sink
+    fn outgoing<'a>(
+        &'a self,
+        from: &'a NodeId,
+    ) -> Option<Vec<(&'a NodeId, &'a Ident, &'a EdgeData)>> {
         let vec = self
             .edges
             .iter()
-            .filter_map(move |((src, dst), docs)| match src == from {
-                true => Some((dst, docs.as_slice())),
+            .filter_map(move |((src, dst, method), data)| match src == from {
+                true => Some((dst, method, data)),
                 false => None,
             })
             .collect::<Vec<_>>();
@@ -449,74 +3415,338 @@ impl FSMGenerator {
             false => Some(vec),
         }
     }
-    fn reachability_docs(&self, node: &NodeId) -> Option<Vec<OuterDocString>> {
-        let mut docs = vec![];
-        let span = Span::call_site();
+    /// All the nodes reachable from `from` (excluding itself, unless there is a cycle).
+    fn reachable<'a>(&'a self, from: &'a NodeId) -> Vec<&'a NodeId> {
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            for (next, ..) in self.outgoing(node).unwrap_or_default() {
+                if seen.insert(next) {
+                    queue.push_back(next)
+                }
+            }
+        }
+        seen.into_iter().collect()
+    }
+    /// The states around `node`, as a single attribute, since it is repeated on the
+    /// state and entry enums, and large machines would otherwise have thousands of lines.
+    fn reachability_docs(&self, node: &NodeId) -> Option<OuterDocString> {
+        let mut lines = vec![];
+        let state_enum_name = self.state_enum_name();
+        let state = node.variant().to_string();
+        let templates = &self.doc_templates;
         if let Some(incoming) = self.incoming(node) {
-            docs.push(OuterDocString::new(
-                "This node is reachable from the following states:",
-                span,
+            lines.push(DocTemplates::fill(
+                &templates.reachable_from,
+                "state",
+                &state,
             ));
             for each in incoming {
-                docs.push(OuterDocString::new(
-                    format!("- [`{}::{}`]", self.state_enum_name(), each.variant()),
-                    span,
-                ))
+                lines.push(format!("- [`{}::{}`]", state_enum_name, each.variant()))
             }
         }
         if let Some(outgoing) = self.outgoing(node) {
-            if !docs.is_empty() {
-                docs.push(OuterDocString::new("", span))
+            if !lines.is_empty() {
+                lines.push(String::new())
             }
-            docs.push(OuterDocString::new(
-                "This node can reach the following states:",
-                span,
-            ));
-            for (each, _) in outgoing {
-                docs.push(OuterDocString::new(
-                    format!("- [`{}::{}`]", self.state_enum_name(), each.variant()),
-                    span,
-                ))
+            lines.push(DocTemplates::fill(&templates.can_reach, "state", &state));
+            let mut outgoing = outgoing.into_iter().map(|(it, ..)| it).collect::<Vec<_>>();
+            outgoing.dedup();
+            for each in outgoing {
+                lines.push(format!("- [`{}::{}`]", state_enum_name, each.variant()))
+            }
+        }
+        match lines.is_empty() {
+            true => None,
+            false => Some(OuterDocString::new(lines.join("\n"), Span::call_site())),
+        }
+    }
+    /// A small ASCII rendering of the edges into and out of `node`, for error messages.
+    ///
+    /// ```text
+    /// Fountain ---> BeautifulBridge ---> Tombstone
+    /// Stream   --/                  \--> UnmarkedGrave
+    /// ```
+    fn neighborhood(&self, node: &NodeId, highlight: Option<&NodeId>) -> String {
+        let name = |it: &NodeId| match Some(it) == highlight {
+            true => format!("{} <-- here", it.inner),
+            false => it.inner.to_string(),
+        };
+        let incoming = self
+            .incoming(node)
+            .unwrap_or_default()
+            .into_iter()
+            .map(name)
+            .collect::<Vec<_>>();
+        let outgoing = self
+            .outgoing(node)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(it, ..)| name(it))
+            .collect::<Vec<_>>();
+        let width = incoming.iter().map(String::len).max().unwrap_or_default();
+        let node = node.inner.to_string();
+        let mut lines = vec![];
+        for row in 0..incoming.len().max(outgoing.len()).max(1) {
+            let mut line = String::new();
+            match (incoming.get(row), row) {
+                (Some(it), 0) => line.push_str(&format!("{:<width$} ---> ", it)),
+                (Some(it), _) => line.push_str(&format!("{:<width$} --/  ", it)),
+                (None, _) if incoming.is_empty() => {}
+                (None, _) => line.push_str(&" ".repeat(width + 6)),
+            }
+            match row {
+                0 => line.push_str(&node),
+                _ => line.push_str(&" ".repeat(node.len())),
+            }
+            match (outgoing.get(row), row) {
+                (Some(it), 0) => line.push_str(&format!(" ---> {}", it)),
+                (Some(it), _) => line.push_str(&format!(" \\--> {}", it)),
+                (None, _) => {}
+            }
+            lines.push(line.trim_end().to_owned());
+        }
+        lines.join("\n")
+    }
+}
+
+macro_rules! bail_at {
+    ($kind:ident @ $span:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {
+        return Err(crate::CoreError::new(
+            crate::ErrorKind::$kind,
+            $span,
+            format!($fmt, $($arg,)*),
+        ))
+    };
+    ($span:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {
+        return Err(syn::Error::new($span, format!($fmt, $($arg,)*)))
+    };
+}
+
+impl FSMGenerator {
+    /// Parse a state machine from the following language:
+    /// ```
+    /// # use syn::parse::Parser as _;
+    /// # fsmentry_core::FSMGenerator::parse_dsl.parse2(quote::quote! {
+    /// /// This is documentation for the state machine.
+    /// #[derive(Clone)] // these attributes will be passed to
+    ///                  // MyStateMachine and the State enum
+    /// #[fsmentry(svg = omit)] // options for fsmentry itself
+    /// #[fsmentry(derive(entry: [Debug]))] // derives for particular items
+    /// pub MyStateMachine {
+    ///     /// This is a node declaration.
+    ///     /// This documentation will be attached to the node.
+    ///     ShavingYaks;
+    ///
+    ///     /// This node contains data.
+    ///     SweepingHair: usize;
+    ///
+    ///     /// This node contains named fields,
+    ///     /// which get accessors like `it.bristles()` and `it.bristles_mut()`.
+    ///     Combing { bristles: usize, tangles: Tangles }
+    ///
+    ///     /// Types may be named, for states here and callers through the module.
+    ///     type Tangles = u8;
+    ///
+    ///     // The machine may hold data of its own, which transitions reach
+    ///     // through `it.context()` and `it.context_mut()`.
+    ///     context: u64;
+    ///
+    ///     /// States may call methods on the generated `Hooks` trait
+    ///     /// whenever a transition enters or leaves them.
+    ///     #[fsmentry(on_enter = log_rest, on_exit = log_wake)]
+    ///     Resting;
+    ///
+    ///     /// These are edge declarations
+    ///     /// This documentation will be shared with each edge.
+    ///     ShavingYaks -> SweepingHair -"this is edge-specific documentation"-> Resting;
+    ///                         // implicit nodes will be created as appropriate ^
+    ///
+    ///     /// Several states may share a transition.
+    ///     Sleeping & Combing -> ShavingYaks;
+    ///
+    ///     /// `<->` adds an edge each way, which `<-forward/backward->` names.
+    ///     Combing <-nap/stir-> Sleeping;
+    ///
+    ///     /// Edges may call a method on the generated `Hooks` trait before transitioning,
+    ///     /// with references to the data of the states either side.
+    ///     Resting -on(self.log_yaks)-> ShavingYaks;
+    ///
+    ///     /// Edges may name their transition method,
+    ///     /// so there can be several between the same states.
+    ///     /// Names are kept as written, while other methods are named after their
+    ///     /// destination in `#[fsmentry(method_case = snake | camel | preserve)]`,
+    ///     /// or `#[fsmentry(method_format = "to_{snake}")]`.
+    ///     Resting -wake_early-> ShavingYaks;
+    ///
+    ///     /// Named edges may take arguments,
+    ///     /// which are passed to the `Hooks` method of the same name.
+    ///     Sleeping -wake(reason: &'static str)-> Resting;
+    ///
+    ///     /// Edges may promise something about the data of their destination,
+    ///     /// which is documented, and checked in debug builds.
+    ///     Resting -ensures(*data > 0)-> SweepingHair;
+    ///
+    ///     /// Or about the data of their source before transitioning, which is bound
+    ///     /// as `data`, or by field name like here. `State::handle` refuses the event
+    ///     /// with an `InvalidTransition` when it doesn't hold.
+    ///     Combing -requires(*bristles > 0)-> Resting;
+    ///
+    ///     /// Edges may only exist under a `#[cfg(..)]`.
+    ///     /// Diagrams draw them dashed, unless `#[fsmentry(mark_cfg = false)]`.
+    ///     /// Other attributes, like `#[inline]`, are passed to the transition method.
+    ///     /// `#[fsmentry(on_mismatch = ..)]` may be given for just this method.
+    ///     #[cfg(any())]
+    ///     #[inline]
+    ///     Resting -> Sleeping;
+    ///
+    ///     /// `_` (or `*`) adds an edge from every other state,
+    ///     /// except where there already is one, or the state is terminal.
+    ///     _ -> GaveUp;
+    ///
+    ///     /// Terminal states may not be left, which is checked,
+    ///     /// and get a `State::is_terminal` method.
+    ///     /// Diagrams show a state's `label` in place of its name.
+    ///     #[fsmentry(terminal, label = "gave up")]
+    ///     GaveUp;
+    ///
+    ///     // Diagrams may be given hints for their layout,
+    ///     // as may their direction, with `#[fsmentry(rankdir = LR)]`.
+    ///     same_rank(Resting, Sleeping);
+    ///     rank(ShavingYaks = source);
+    /// }
+    /// # }).unwrap();
+    /// ```
+    pub fn parse_dsl(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self::try_from_dsl(input.parse()?, &[])?)
+    }
+
+    /// Parse one or more state machines in the language of [`Self::parse_dsl`].
+    ///
+    /// A machine may start from the states and transitions of one defined before it:
+    /// ```
+    /// # use syn::parse::Parser as _;
+    /// # fsmentry_core::FSMGenerator::parse_dsl_many.parse2(quote::quote! {
+    /// pub Document {
+    ///     Draft -> Published -> Archived;
+    /// }
+    /// pub ReviewedDocument {
+    ///     extends Document;
+    ///     /// Inherited states may be redeclared to change their data or documentation.
+    ///     Published: String;
+    ///     /// So may inherited transitions.
+    ///     Published -on(self.log_archival)-> Archived;
+    ///     // and transitions may be removed
+    ///     remove Draft -> Published;
+    ///     Draft -> InReview -> Published;
+    /// }
+    /// # }).unwrap();
+    /// ```
+    ///
+    /// Inner attributes at the start are shared by every machine,
+    /// though an option may still only be given once for each:
+    /// ```
+    /// # use syn::parse::Parser as _;
+    /// # fsmentry_core::FSMGenerator::parse_dsl_many.parse2(quote::quote! {
+    /// #![derive(Debug)]
+    /// #![fsmentry(events = true)]
+    /// //! The lights at the crossing.
+    /// pub Traffic { Red -> Green -> Red; }
+    /// #[derive(Clone)]
+    /// pub Pedestrian { Wait -> Walk -> Wait; }
+    /// # }).unwrap();
+    /// ```
+    pub fn parse_dsl_many(input: ParseStream) -> syn::Result<Vec<Self>> {
+        let shared = shared_attrs(input)?;
+        let mut machines = vec![];
+        loop {
+            let mut dsl = input.parse::<dsl::Dsl>()?;
+            dsl.attrs.splice(0..0, shared.iter().cloned());
+            let machine = Self::try_from_dsl(dsl, &machines)?;
+            machines.push(machine);
+            if input.is_empty() {
+                break Ok(machines);
             }
         }
-        match docs.is_empty() {
-            true => None,
-            false => Some(docs),
-        }
     }
-}
-
-macro_rules! bail_at {
-    ($span:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {
-        return Err(syn::Error::new($span, format!($fmt, $($arg,)*)))
-    };
-}
 
-impl FSMGenerator {
-    /// Parse a state machine from the following language:
-    /// ```
-    /// # use syn::parse::Parser as _;
-    /// # fsmentry_core::FSMGenerator::parse_dsl.parse2(quote::quote! {
-    /// /// This is documentation for the state machine.
-    /// #[derive(Clone)] // these attributes will be passed to
-    ///                  // MyStateMachine and the State enum
-    /// pub MyStateMachine {
-    ///     /// This is a node declaration.
-    ///     /// This documentation will be attached to the node.
-    ///     ShavingYaks;
+    /// Like [`Self::parse_dsl_many`], but keeps going after errors,
+    /// so that editors and other tools can report every problem at once.
     ///
-    ///     /// This node contains data.
-    ///     SweepingHair: usize;
+    /// Statements which fail to parse are skipped up to the next `;`,
+    /// and the rest of their machine is still checked.
+    /// Machines which are invalid even so are left out of the result.
+    ///
+    /// This never fails, but returns a [`syn::Result`] for use with [`syn::parse::Parser`].
+    /// See [`Self::check_dsl`] for errors which may be told apart by their [`ErrorKind`].
+    pub fn parse_dsl_lenient(input: ParseStream) -> syn::Result<(Vec<Self>, Vec<syn::Error>)> {
+        let mut errors = vec![];
+        let machines = Self::parse_lenient(input, &mut errors)?;
+        Ok((
+            machines,
+            errors.into_iter().flat_map(syn::Error::from).collect(),
+        ))
+    }
+
+    /// Check state machines in the language of [`Self::parse_dsl_many`],
+    /// returning every problem with them, as in [`Self::parse_dsl_lenient`].
     ///
-    ///     /// These are edge declarations
-    ///     /// This documentation will be shared with each edge.
-    ///     ShavingYaks -> SweepingHair -"this is edge-specific documentation"-> Resting;
-    ///                         // implicit nodes will be created as appropriate ^
-    /// }
-    /// # }).unwrap();
     /// ```
-    pub fn parse_dsl(input: ParseStream) -> syn::Result<Self> {
-        Self::try_from_dsl(input.parse()?)
+    /// use fsmentry_core::{ErrorKind, FSMGenerator};
+    /// let errors = FSMGenerator::check_dsl("Machine { A -> B; A -> B; }").unwrap_err();
+    /// assert_eq!(errors[0].kind(), ErrorKind::DuplicateEdge);
+    /// assert_eq!(errors[0].code(), "E0004");
+    /// ```
+    pub fn check_dsl(input: &str) -> Result<Vec<Self>, Vec<CoreError>> {
+        let mut errors = vec![];
+        let machines = syn::parse::Parser::parse_str(
+            |input: ParseStream| Self::parse_lenient(input, &mut errors),
+            input,
+        );
+        match machines {
+            Ok(machines) if errors.is_empty() => Ok(machines),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.push(e.into());
+                Err(errors)
+            }
+        }
+    }
+
+    pub(crate) fn parse_lenient(
+        input: ParseStream,
+        errors: &mut Vec<CoreError>,
+    ) -> syn::Result<Vec<Self>> {
+        let mut machines = vec![];
+        let shared = shared_attrs(input).unwrap_or_else(|e| {
+            errors.push(e.into());
+            vec![]
+        });
+        while !input.is_empty() {
+            let mut syntax = vec![];
+            let dsl = dsl::Dsl::parse_lenient(input, &mut syntax);
+            errors.extend(syntax.into_iter().map(CoreError::from));
+            let dsl = match dsl {
+                Ok(mut it) => {
+                    it.attrs.splice(0..0, shared.iter().cloned());
+                    it
+                }
+                Err(e) => {
+                    errors.push(e.into());
+                    // skip to the end of this machine
+                    dsl::skip_past(
+                        input,
+                        |it| matches!(it, proc_macro2::TokenTree::Group(g) if g.delimiter() == proc_macro2::Delimiter::Brace),
+                    )?;
+                    continue;
+                }
+            };
+            match Self::try_from_dsl(dsl, &machines) {
+                Ok(it) => machines.push(it),
+                Err(e) => errors.push(e),
+            }
+        }
+        Ok(machines)
     }
 
     /// Parse a state machine from the [`DOT` graph description language](https://en.wikipedia.org/wiki/DOT_%28graph_description_language%29):
@@ -532,8 +3762,30 @@ impl FSMGenerator {
     /// }
     /// # }).unwrap();
     /// ```
-    // Transpiles DOT to the DSL, and then calls [`Self::try_from_dsl`]
+    ///
+    /// Nodes named by strings or numbers which aren't rust identifiers, like `"waiting-for-ack"`
+    /// or `404`, are renamed: each character other than a letter, digit or `_` becomes `_`,
+    /// and names which then don't start with a letter are prefixed with `State`,
+    /// so these are `waiting_for_ack` and `State404`.
+    /// The original name is documented, and labels the state in diagrams.
+    ///
+    /// The following have no meaning for a state machine, so are ignored with a [warning](Self::warnings):
+    /// - attributes, like `[label = "..."]` on nodes and edges, or `rankdir = LR` on the graph.
+    /// - ports, like `node:north`.
+    /// - repeated edges between the same states, unless the graph is `strict`.
+    ///
+    /// See [`Self::parse_dot_strict`] to reject them instead.
     pub fn parse_dot(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self::parse_dot_with(input, false)?)
+    }
+
+    /// Like [`Self::parse_dot`], but DOT that would be ignored is an error.
+    pub fn parse_dot_strict(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self::parse_dot_with(input, true)?)
+    }
+
+    // Transpiles DOT to the DSL, and then calls [`Self::try_from_dsl`]
+    fn parse_dot_with(input: ParseStream, strict: bool) -> Result<Self, CoreError> {
         use dsl::{
             pun, Edge as DslEdge, Stmt as DslStmt, StmtEdges as DslStmtEdges,
             StmtNode as DslStmtNode,
@@ -543,20 +3795,44 @@ impl FSMGenerator {
             Stmt as DotStmt, StmtEdge as DotStmtEdge, StmtNode as DotStmtNode, ID,
         };
         let Graph {
-            strict: _,
+            strict: strict_graph,
             directedness,
             id,
             brace_token,
             stmt_list,
         } = input.parse::<Graph>()?;
         let GraphDirectedness::Digraph(_) = directedness else {
-            bail_at!(directedness.span(), "must be `digraph`")
+            bail_at!(
+                Unsupported @ directedness.span(),
+                "must be `digraph`, since every transition has a direction"
+            )
         };
         let Some(ID::AnyIdent(id)) = id else {
-            bail_at!(directedness.span(), "graph must be named")
+            bail_at!(
+                Unsupported @ directedness.span(),
+                "graph must be named, since the name is used for the state machine"
+            )
+        };
+        let mut warnings = vec![];
+        let mut ignore = |span: Span, what: &str| match strict {
+            true => Err(CoreError::new(
+                ErrorKind::Unsupported,
+                span,
+                format!("{} are not supported", what),
+            )),
+            false => {
+                warnings.push(Warning {
+                    span,
+                    message: format!("{} are not supported, so this is ignored", what),
+                });
+                Ok(())
+            }
         };
+        let mut seen = BTreeSet::new();
         let mut stmts = vec![];
         let span = Span::call_site();
+        // the original name of each node which had to be renamed, see `id_to_ident`
+        let mut names = BTreeMap::<Ident, Option<String>>::new();
         for (stmt, _) in stmt_list.stmts {
             match stmt {
                 DotStmt::Node(DotStmtNode {
@@ -564,188 +3840,1300 @@ impl FSMGenerator {
                     attrs,
                 }) => {
                     if let Some(attrs) = attrs {
-                        bail_at!(attrs.span(), "attrs are not supported")
+                        ignore(attrs.span(), "attributes")?
                     }
                     if let Some(port) = port {
-                        bail_at!(port.span(), "ports are not supported")
+                        ignore(port.span(), "ports")?
+                    }
+                    let ident = id_to_ident(id, &mut names)?;
+                    // renamed nodes are declared below, with their original name
+                    if let Some(None) = names.get(&ident) {
+                        stmts.push(DslStmt::Node(DslStmtNode {
+                            attrs: vec![],
+                            ident,
+                            colon: None,
+                            ty: None,
+                            fields: None,
+                            semi: Some(Token![;](span)),
+                        }))
                     }
-                    let ID::AnyIdent(id) = id else {
-                        bail_at!(id.span(), "unsupported id")
-                    };
-                    stmts.push(DslStmt::Node(DslStmtNode {
-                        attrs: vec![],
-                        ident: syn::parse2(id.into_token_stream())?,
-                        colon: None,
-                        ty: None,
-                        semi: Token![;](span),
-                    }))
                 }
                 DotStmt::Edge(DotStmtEdge { from, edges, attrs }) => {
                     if let Some(attrs) = attrs {
-                        bail_at!(attrs.span(), "attrs are not supported")
+                        ignore(attrs.span(), "attributes")?
                     };
-                    let mut rest = edges
-                        .into_iter()
-                        .map(|(dir, to)| {
-                            let EdgeDirectedness::Directed(_) = dir else {
-                                bail_at!(dir.span(), "edge must be directed")
-                            };
-                            Ok((
-                                DslEdge::Short(pun::ShortArrow(span)),
-                                edge_target_to_ident(to)?,
-                            ))
-                        })
-                        .collect::<syn::Result<Vec<_>>>()?;
-
-                    let (edge, to) = rest.remove(0);
-
-                    stmts.push(DslStmt::Edges(DslStmtEdges {
-                        attrs: vec![],
-                        from: edge_target_to_ident(from)?,
-                        edge,
-                        to,
-                        rest,
-                        semi: Token![;](span),
-                    }))
+                    let mut from = edge_target_to_ident(from, &mut names, &mut ignore)?;
+                    // split the chain around repeated edges
+                    let mut chain = None::<DslStmtEdges>;
+                    for (dir, to) in edges {
+                        let EdgeDirectedness::Directed(_) = dir else {
+                            bail_at!(Unsupported @ dir.span(), "edge must be directed")
+                        };
+                        let to_span = to.span();
+                        let to = edge_target_to_ident(to, &mut names, &mut ignore)?;
+                        if !seen.insert((from.clone(), to.clone())) {
+                            if strict_graph.is_none() {
+                                ignore(to_span, "repeated edges")?
+                            }
+                            stmts.extend(chain.take().map(DslStmt::Edges));
+                        } else {
+                            let edge = DslEdge::Short(pun::ShortArrow(span));
+                            match &mut chain {
+                                Some(chain) => chain.rest.push((edge, to.clone())),
+                                None => {
+                                    chain = Some(DslStmtEdges {
+                                        attrs: vec![],
+                                        from: from.clone(),
+                                        fan_in: vec![],
+                                        edge,
+                                        to: to.clone(),
+                                        rest: vec![],
+                                        semi: Token![;](span),
+                                    })
+                                }
+                            }
+                        }
+                        from = to;
+                    }
+                    stmts.extend(chain.map(DslStmt::Edges));
                 }
-                it @ (DotStmt::Attr(_) | DotStmt::Assign(_) | DotStmt::Subgraph(_)) => {
-                    bail_at!(it.span(), "unsupported statement")
+                it @ (DotStmt::Attr(_) | DotStmt::Assign(_)) => ignore(it.span(), "attributes")?,
+                DotStmt::Subgraph(it) => {
+                    bail_at!(Unsupported @ it.span(), "subgraphs are not supported")
                 }
             }
         }
-        return Self::try_from_dsl(crate::dsl::Dsl {
-            attrs: vec![],
-            vis: parse_quote!(pub),
-            name: syn::parse2(id.into_token_stream())?,
-            brace_token,
-            stmts,
-        });
+        for (ident, name) in names {
+            if let Some(name) = name {
+                let doc = format!("Named `{}` in the `DOT` definition.", name);
+                stmts.push(DslStmt::Node(DslStmtNode {
+                    attrs: vec![
+                        parse_quote!(#[doc = #doc]),
+                        parse_quote!(#[fsmentry(label = #name)]),
+                    ],
+                    ident,
+                    colon: None,
+                    ty: None,
+                    fields: None,
+                    semi: Some(Token![;](span)),
+                }))
+            }
+        }
+        let mut this = Self::try_from_dsl(
+            crate::dsl::Dsl {
+                attrs: vec![],
+                vis: parse_quote!(pub),
+                name: syn::parse2(id.into_token_stream())?,
+                brace_token,
+                stmts,
+            },
+            &[],
+        )?;
+        this.warnings.splice(0..0, warnings);
+        return Ok(this);
 
-        fn edge_target_to_ident(edge_target: EdgeTarget) -> syn::Result<Ident> {
+        fn edge_target_to_ident(
+            edge_target: EdgeTarget,
+            names: &mut BTreeMap<Ident, Option<String>>,
+            ignore: &mut impl FnMut(Span, &str) -> Result<(), CoreError>,
+        ) -> Result<Ident, CoreError> {
             match edge_target {
                 EdgeTarget::Subgraph(_) => {
-                    bail_at!(edge_target.span(), "subgraphs are not supported")
+                    bail_at!(Unsupported @ edge_target.span(), "subgraphs are not supported")
                 }
                 EdgeTarget::NodeId(DotNodeId { id, port }) => {
                     if let Some(port) = port {
-                        bail_at!(port.span(), "ports are not supported")
+                        ignore(port.span(), "ports")?
+                    }
+                    id_to_ident(id, names)
+                }
+            }
+        }
+
+        /// Rename nodes which aren't rust identifiers, recording their original name in `names`.
+        fn id_to_ident(
+            id: ID,
+            names: &mut BTreeMap<Ident, Option<String>>,
+        ) -> Result<Ident, CoreError> {
+            let span = id.span();
+            let (ident, name) = match id {
+                ID::AnyIdent(id) => (syn::parse2(id.into_token_stream())?, None),
+                ID::AnyLit(lit) => {
+                    let name = match &lit {
+                        syn::Lit::Str(it) => it.value(),
+                        other => other.to_token_stream().to_string(),
+                    };
+                    let mut renamed = name
+                        .chars()
+                        .map(|it| match unicode_ident::is_xid_continue(it) {
+                            true => it,
+                            false => '_',
+                        })
+                        .collect::<String>();
+                    if !renamed.starts_with(unicode_ident::is_xid_start) {
+                        renamed.insert_str(0, "State")
                     }
-                    let ID::AnyIdent(id) = id else {
-                        bail_at!(id.span(), "only idents are allowed here")
+                    let ident = ident_at(&renamed, span)?;
+                    match syn::parse_str::<Ident>(&name).is_ok_and(|it| it == renamed) {
+                        true => (ident, None),
+                        false => (ident, Some(name)),
+                    }
+                }
+                _ => bail_at!(
+                    Unsupported @ span,
+                    "only identifiers, strings and numbers are supported, since they are used as names in rust"
+                ),
+            };
+            match names.get(&ident) {
+                Some(other) if *other != name => {
+                    let describe = |it: &Option<String>| match it {
+                        Some(it) => format!("`{:?}`", it),
+                        None => format!("`{}`", ident),
                     };
-                    syn::parse2(id.into_token_stream())
+                    bail_at!(
+                        NameClash @ span,
+                        "{} and {} would both be named `{}`",
+                        describe(other),
+                        describe(&name),
+                        ident
+                    )
+                }
+                _ => {
+                    names.insert(ident.clone(), name);
                 }
             }
+            Ok(ident)
         }
     }
 
-    fn try_from_dsl(dsl: crate::dsl::Dsl) -> syn::Result<Self> {
-        use dsl::{DocumentedArrow, Dsl, Edge, Stmt, StmtEdges, StmtNode};
-        use std::{
-            cmp::Ordering::{Equal, Greater, Less},
-            collections::btree_map::Entry::{Occupied, Vacant},
+    /// `bases` are the machines that may be named in an `extends` statement.
+    fn try_from_dsl(dsl: crate::dsl::Dsl, bases: &[Self]) -> Result<Self, CoreError> {
+        use dsl::{
+            CallbackArrow, DocumentedArrow, Dsl, Edge, EnsuresArrow, Fields, NamedArrow, Param,
+            Params, RankHint, RequiresArrow, Stmt, StmtContext, StmtEdges, StmtExtends, StmtNode,
+            StmtRank, StmtRemove, StmtSameRank, StmtType, StmtWildcard,
         };
+        use std::collections::btree_map::Entry::{Occupied, Vacant};
 
         let Dsl {
-            attrs,
+            mut attrs,
             vis,
             name,
             brace_token: _,
             mut stmts,
         } = dsl;
 
+        let mut derives = Derives::default();
+        let mut include_svg = None;
+        let mut dot_path = None;
+        let mut dot_timeout = None::<syn::LitInt>;
+        let mut svg_max_size = None::<syn::LitInt>;
+        let mut diagram_fallback = None;
+        let mut mark_cfg = None::<syn::LitBool>;
+        let mut atomic_kind = None::<syn::Path>;
+        let mut defmt = None;
+        let mut serde = None;
+        let mut serde_tag = None::<syn::LitStr>;
+        let mut serde_content = None::<syn::LitStr>;
+        let mut fmt = None::<syn::LitBool>;
+        let mut registry = None::<syn::LitBool>;
+        let mut events = None::<syn::LitBool>;
+        let mut entry_fn = None::<syn::LitStr>;
+        let mut entry_cell = None::<syn::LitBool>;
+        let mut owned = None::<syn::LitBool>;
+        let mut transition_source = None::<syn::LitBool>;
+        let mut chain = None::<syn::LitBool>;
+        let mut entry_ref = None::<syn::LitBool>;
+        let mut from_str = None::<syn::LitBool>;
+        let mut state_accessors = None::<syn::LitBool>;
+        let mut pool = None::<syn::LitBool>;
+        let mut handle_prefix = None::<syn::LitStr>;
+        let mut handle_suffix = None::<syn::LitStr>;
+        let mut conversions = vec![];
+        let mut expose_handle_field = None::<syn::Visibility>;
+        let mut handle_vis = None::<syn::Visibility>;
+        let mut on_mismatch = None::<OnMismatch>;
+        let mut mismatch_hook = None::<syn::Path>;
+        let mut rankdir = None;
+        let mut method_case = None;
+        let mut method_format = None::<syn::LitStr>;
+        let mut emit = None;
+        let mut initial = None::<Ident>;
+        let mut module = None::<Ident>;
+        let mut state_mod = None::<ReexportMod>;
+        let mut allow = vec![];
+        let mut entry_mod = None::<ReexportMod>;
+        let mut max_states = None::<syn::LitInt>;
+        let mut max_edges = None::<syn::LitInt>;
+        let mut handle_doc = None::<syn::LitStr>;
+        let mut reachable_from_doc = None::<syn::LitStr>;
+        let mut can_reach_doc = None::<syn::LitStr>;
+        let mut ensures_doc = None::<syn::LitStr>;
+        let mut requires_doc = None::<syn::LitStr>;
+        crate::args::Parser::new()
+            .group(
+                "derive",
+                crate::args::Parser::new()
+                    .list("machine", &mut derives.machine)
+                    .list("state", &mut derives.state)
+                    .list("entry", &mut derives.entry)
+                    .list("event", &mut derives.event),
+            )
+            .once("svg", &mut include_svg)
+            .once("dot_path", &mut dot_path)
+            .once("dot_timeout", &mut dot_timeout)
+            .once("svg_max_size", &mut svg_max_size)
+            .once("diagram_fallback", &mut diagram_fallback)
+            .once("mark_cfg", &mut mark_cfg)
+            .once("atomic_kind", &mut atomic_kind)
+            .once("defmt", &mut defmt)
+            .once("serde", &mut serde)
+            .once("serde_tag", &mut serde_tag)
+            .once("serde_content", &mut serde_content)
+            .once("fmt", &mut fmt)
+            .once("registry", &mut registry)
+            .once("events", &mut events)
+            .once("entry_fn", &mut entry_fn)
+            .once("entry_cell", &mut entry_cell)
+            .once("owned", &mut owned)
+            .once("transition_source", &mut transition_source)
+            .once("chain", &mut chain)
+            .once("entry_ref", &mut entry_ref)
+            .once("from_str", &mut from_str)
+            .once("state_accessors", &mut state_accessors)
+            .once("pool", &mut pool)
+            .once("handle_prefix", &mut handle_prefix)
+            .once("handle_suffix", &mut handle_suffix)
+            .list("conversions", &mut conversions)
+            .once("expose_handle_field", &mut expose_handle_field)
+            .once("handle_vis", &mut handle_vis)
+            .once("on_mismatch", &mut on_mismatch)
+            .once("mismatch_hook", &mut mismatch_hook)
+            .once("rankdir", &mut rankdir)
+            .once("method_case", &mut method_case)
+            .once("method_format", &mut method_format)
+            .once("emit", &mut emit)
+            .once("initial", &mut initial)
+            .once("module", &mut module)
+            .once("state_mod", &mut state_mod)
+            .once("entry_mod", &mut entry_mod)
+            .list("allow", &mut allow)
+            .once("max_states", &mut max_states)
+            .once("max_edges", &mut max_edges)
+            .group(
+                "doc_templates",
+                crate::args::Parser::new()
+                    .once("handle", &mut handle_doc)
+                    .once("reachable_from", &mut reachable_from_doc)
+                    .once("can_reach", &mut can_reach_doc)
+                    .once("ensures", &mut ensures_doc)
+                    .once("requires", &mut requires_doc),
+            )
+            .extract(&mut attrs)?;
+        // checked against the other methods once the context is known
+        let entry_fn = match entry_fn {
+            Some(lit) => lit.parse::<Ident>()?,
+            None => ident("entry"),
+        };
+        let dot_timeout = match dot_timeout {
+            Some(it) => Duration::from_secs(it.base10_parse()?),
+            None => svg::DEFAULT_DOT_TIMEOUT,
+        };
+        let svg_max_size = match svg_max_size {
+            Some(it) => it.base10_parse()?,
+            None => svg::DEFAULT_SVG_MAX_SIZE,
+        };
+        let max_states = match max_states {
+            Some(it) => it.base10_parse()?,
+            None => DEFAULT_MAX_STATES,
+        };
+        let max_edges = match max_edges {
+            Some(it) => it.base10_parse()?,
+            None => DEFAULT_MAX_EDGES,
+        };
+        let on_mismatch = on_mismatch.unwrap_or_default();
+        if let (Some(vis), OnMismatch::Unchecked | OnMismatch::DebugUnchecked) =
+            (&expose_handle_field, &on_mismatch)
+        {
+            bail_at!(
+                InvalidOption @ vis.span(),
+                "`expose_handle_field` lets safe code mismatch a handle, so `on_mismatch` must not be unchecked"
+            )
+        }
+        let serde = serde.unwrap_or_default();
+        if let (None, Some(lit)) = (&serde_tag, &serde_content) {
+            bail_at!(
+                InvalidOption @ lit.span(),
+                "`serde_content` needs `serde_tag`, since states are only tagged adjacently with both"
+            )
+        }
+        if let (Some(lit), Serde::Off | Serde::Untagged) = (&serde_tag, serde) {
+            bail_at!(
+                InvalidOption @ lit.span(),
+                "`serde_tag` needs `serde = true`"
+            )
+        }
+        let method_case = match (method_case, method_format) {
+            (Some(_), Some(lit)) => bail_at!(
+                InvalidOption @ lit.span(),
+                "`method_format` and `method_case` may not both be given"
+            ),
+            (case, None) => case.unwrap_or_default(),
+            (None, Some(lit)) => {
+                let format = lit.value();
+                let example = ["{snake}", "{camel}", "{preserve}"]
+                    .iter()
+                    .fold(format.clone(), |acc, it| acc.replace(it, "state"));
+                if example == format || syn::parse_str::<Ident>(&example).is_err() {
+                    bail_at!(
+                        Syntax @ lit.span(),
+                        "expected an identifier containing `{{snake}}`, `{{camel}}` or `{{preserve}}`"
+                    )
+                }
+                MethodCase::Format(format)
+            }
+        };
+        let mut doc_templates = DocTemplates::default();
+        for (lit, dst, placeholders) in [
+            (handle_doc, &mut doc_templates.handle, &["state"][..]),
+            (
+                reachable_from_doc,
+                &mut doc_templates.reachable_from,
+                &["state"][..],
+            ),
+            (can_reach_doc, &mut doc_templates.can_reach, &["state"][..]),
+            (ensures_doc, &mut doc_templates.ensures, &["condition"][..]),
+            (
+                requires_doc,
+                &mut doc_templates.requires,
+                &["condition"][..],
+            ),
+        ] {
+            if let Some(lit) = lit {
+                *dst = DocTemplates::check(lit, placeholders)?
+            }
+        }
+        let module = module.unwrap_or_else(|| name.snake_case());
+        let mut mods = vec![];
+        for ReexportMod { ident, .. } in state_mod.iter().chain(&entry_mod) {
+            if *ident == module || mods.contains(&ident) {
+                bail_at!(
+                    NameClash @ ident.span(),
+                    "the module `{}` would clash with another generated module",
+                    ident
+                )
+            }
+            mods.push(ident)
+        }
+        let [handle_prefix, handle_suffix] = [handle_prefix, handle_suffix].map(|lit| match lit {
+            Some(lit) => match lit
+                .value()
+                .chars()
+                .all(|it| it.is_ascii_alphanumeric() || it == '_')
+            {
+                true => Ok(lit.value()),
+                false => bail_at!(
+                    Syntax @ lit.span(),
+                    "expected letters, digits and underscores, to be part of a type name"
+                ),
+            },
+            None => Ok(String::new()),
+        });
+        let handle_affixes = (handle_prefix?, handle_suffix?);
+
         let mut nodes = BTreeMap::new();
         let mut edges = BTreeMap::new();
+        let (mut node_order, mut edge_order) = (vec![], vec![]);
+        let mut ranks = vec![];
+        // nodes and edges from an `extends` base, which may be redeclared
+        let mut inherited = BTreeSet::new();
+        let mut inherited_edges = BTreeSet::new();
+        let mut extended = false;
+        // an inherited context may be redeclared, but only once
+        let mut context = None;
+        let mut context_declared = false;
+        // inherited aliases may be redeclared
+        let mut aliases = Vec::<TypeAlias>::new();
+        let mut inherited_aliases = BTreeSet::new();
+        // reported once the whole graph is known, so we can show the neighborhood
+        let mut duplicate_edges = vec![];
+
+        // before sorting, while statements are in source order
+        let mut warnings = vec![];
+        for pair in stmts.windows(2) {
+            let [Stmt::Edges(prev), Stmt::Edges(next)] = pair else {
+                continue;
+            };
+            let end = prev.rest.last().map(|(_, it)| it).unwrap_or(&prev.to);
+            if prev.attrs.is_empty()
+                && next.attrs.is_empty()
+                && next.fan_in.is_empty()
+                && *end == next.from
+            {
+                warnings.push(Warning {
+                    span: next.from.span(),
+                    message: format!(
+                        "the previous statement ends at `{}`, so this can be appended to it as a chain",
+                        end
+                    ),
+                })
+            }
+        }
 
-        // Nodes first, so Node should be less than Edge
-        stmts.sort_unstable_by(|left, right| match (left, right) {
-            (Stmt::Edges(_), Stmt::Edges(_)) => Equal,
-            (Stmt::Edges(_), Stmt::Node(_)) => Greater,
-            (Stmt::Node(_), Stmt::Edges(_)) => Less,
-            (Stmt::Node(_), Stmt::Node(_)) => Equal,
+        // The base first, then nodes, then edges, then edges from every node, then hints about them
+        stmts.sort_by_key(|it| match it {
+            Stmt::Extends(_) => 0,
+            Stmt::Remove(_) | Stmt::Context(_) | Stmt::Type(_) => 1,
+            Stmt::Node(_) => 2,
+            Stmt::Edges(_) => 3,
+            Stmt::Wildcard(_) => 4,
+            Stmt::SameRank(_) | Stmt::Rank(_) => 5,
         });
 
-        for stmt in stmts {
+        let mut stmts = VecDeque::from(stmts);
+        while let Some(stmt) = stmts.pop_front() {
             match stmt {
+                Stmt::Extends(StmtExtends { base, .. }) => {
+                    if extended {
+                        bail_at!(DuplicateDeclaration @ base.span(), "a machine may only extend one other machine")
+                    }
+                    let Some(base) = bases.iter().find(|it| it.ident == base) else {
+                        bail_at!(
+                            Undefined @ base.span(),
+                            "no machine named `{}` is defined before this one",
+                            base
+                        )
+                    };
+                    nodes.extend(base.nodes.clone());
+                    edges.extend(base.edges.clone());
+                    node_order.extend(base.order.0.iter().cloned());
+                    edge_order.extend(base.order.1.iter().cloned());
+                    inherited.extend(base.nodes.keys().cloned());
+                    inherited_edges.extend(base.edges.keys().cloned());
+                    ranks.extend(base.ranks.clone());
+                    context.clone_from(&base.context);
+                    aliases.extend(base.aliases.clone());
+                    inherited_aliases.extend(base.aliases.iter().map(|it| it.ident.clone()));
+                    extended = true;
+                }
+                Stmt::Context(StmtContext {
+                    context: token, ty, ..
+                }) => {
+                    if context_declared {
+                        bail_at!(DuplicateDeclaration @ token.span, "a machine may only have one context")
+                    }
+                    context = Some(ty);
+                    context_declared = true;
+                }
+                Stmt::Type(StmtType {
+                    attrs, ident, ty, ..
+                }) => {
+                    let mut docs = vec![];
+                    for attr in attrs {
+                        if attr.path().is_ident("doc") {
+                            docs.push(OuterDocString::try_from(attr)?)
+                        } else {
+                            bail_at!(
+                                Unsupported @ attr.span(),
+                                "only `#[doc]` attributes are supported on type aliases"
+                            )
+                        }
+                    }
+                    let alias = TypeAlias { docs, ident, ty };
+                    match aliases.iter_mut().find(|it| it.ident == alias.ident) {
+                        Some(it) if inherited_aliases.remove(&alias.ident) => *it = alias,
+                        Some(_) => {
+                            bail_at!(DuplicateDeclaration @ alias.ident.span(), "duplicate type alias")
+                        }
+                        None => aliases.push(alias),
+                    }
+                }
+                Stmt::Remove(StmtRemove {
+                    remove,
+                    from,
+                    edge,
+                    to,
+                    semi,
+                }) => {
+                    // `remove A <-> B` also removes `B -> A`, which is handled next
+                    let (edge, backward) = edge.split();
+                    if let Some(backward) = backward {
+                        stmts.push_front(Stmt::Remove(StmtRemove {
+                            remove,
+                            from: to.clone(),
+                            edge: backward,
+                            to: from.clone(),
+                            semi,
+                        }))
+                    }
+                    let (from_id, to_id) = (NodeId::from(from.clone()), NodeId::from(to.clone()));
+                    let (arrow, key) = match edge.method() {
+                        Some(method) => (format!("-{}->", method), Some((from_id, to_id, method))),
+                        // the base may name its methods differently, so find it by its states
+                        None => (
+                            String::from("->"),
+                            inherited_edges
+                                .iter()
+                                .find(|it| {
+                                    (&it.0, &it.1) == (&from_id, &to_id) && !edges[*it].named
+                                })
+                                .cloned(),
+                        ),
+                    };
+                    let Some(key) = key.filter(|it| inherited_edges.remove(it)) else {
+                        bail_at!(
+                            Undefined @ from.span().join(to.span()).unwrap_or(from.span()),
+                            "there is no inherited edge `{} {} {}` to remove",
+                            from,
+                            arrow,
+                            to
+                        )
+                    };
+                    edges.remove(&key);
+                }
                 Stmt::Node(StmtNode {
                     attrs,
                     ident,
                     colon: _,
                     ty,
+                    fields,
                     semi: _,
                 }) => {
                     let span = ident.span();
-                    match nodes.entry(ident.into()) {
-                        Occupied(_) => bail_at!(span, "duplicate node definition"),
-                        Vacant(v) => v.insert(NodeData { ty, docs: attrs }),
+                    let ty = match (ty, fields) {
+                        (Some(ty), _) => Some(Data::Tuple(ty)),
+                        (None, Some(Fields { brace, fields })) => {
+                            if fields.is_empty() {
+                                bail_at!(Syntax @ brace.span.join(), "expected at least one field")
+                            }
+                            let mut record = vec![];
+                            for Param { name, ty, .. } in fields {
+                                if name == "hooks" {
+                                    bail_at!(
+                                        Reserved @ name.span(),
+                                        "`hooks` is reserved, and may not be used as a field name"
+                                    )
+                                }
+                                record.push((name, ty))
+                            }
+                            Some(Data::Record(record))
+                        }
+                        (None, None) => None,
+                    };
+                    let mut attrs = attrs;
+                    let mut on_enter = None;
+                    let mut on_exit = None;
+                    let mut terminal = false;
+                    let mut label = None::<syn::LitStr>;
+                    crate::args::Parser::new()
+                        .once("on_enter", &mut on_enter)
+                        .once("on_exit", &mut on_exit)
+                        .flag("terminal", &mut terminal)
+                        .once("label", &mut label)
+                        .extract(&mut attrs)?;
+                    let mut docs = vec![];
+                    let mut serde_attrs = vec![];
+                    for attr in attrs {
+                        if attr.path().is_ident("doc") {
+                            docs.push(OuterDocString::try_from(attr)?)
+                        } else if attr.path().is_ident("serde") && serde != Serde::Off {
+                            serde_attrs.push(attr)
+                        } else {
+                            bail_at!(
+                                Unsupported @ attr.span(),
+                                "only `#[doc]` and `#[fsmentry]` attributes are supported on states"
+                            )
+                        }
+                    }
+                    let data = NodeData {
+                        ty,
+                        docs,
+                        on_enter,
+                        on_exit,
+                        terminal,
+                        label: label.map(|it| it.value()),
+                        serde: serde_attrs,
                     };
+                    match nodes.entry(ident.into()) {
+                        Occupied(mut o) if inherited.remove(o.key()) => {
+                            let values = |docs: &[OuterDocString]| {
+                                docs.iter().map(|it| it.doc.value()).collect::<Vec<_>>()
+                            };
+                            let inherited_docs = values(&o.get().docs);
+                            if !inherited_docs.is_empty() && inherited_docs != values(&data.docs) {
+                                warnings.push(Warning {
+                                    span,
+                                    message: format!(
+                                        "redeclaring `{}` replaces its inherited documentation",
+                                        o.key().inner
+                                    ),
+                                })
+                            }
+                            o.insert(data);
+                        }
+                        Occupied(_) => bail_at!(DuplicateState @ span, "duplicate node definition"),
+                        Vacant(v) => {
+                            node_order.push(v.key().clone());
+                            v.insert(data);
+                        }
+                    }
                 }
                 Stmt::Edges(StmtEdges {
                     attrs,
                     mut from,
+                    fan_in,
                     edge,
                     to,
                     rest,
-                    semi: _,
+                    semi,
                 }) => {
+                    // each further state gets its own first transition, which are handled next
+                    for (_, other) in fan_in.into_iter().rev() {
+                        stmts.push_front(Stmt::Edges(StmtEdges {
+                            attrs: attrs.clone(),
+                            from: other,
+                            fan_in: vec![],
+                            edge: edge.clone(),
+                            to: to.clone(),
+                            rest: vec![],
+                            semi,
+                        }))
+                    }
                     for ident in iter::once(&from)
                         .chain([&to])
                         .chain(rest.iter().map(|(_edge, ident)| ident))
                     {
-                        nodes.entry(ident.clone().into()).or_insert(NodeData {
-                            ty: None,
-                            docs: vec![],
-                        });
+                        if let Vacant(v) = nodes.entry(ident.clone().into()) {
+                            node_order.push(v.key().clone());
+                            v.insert(NodeData {
+                                ty: None,
+                                docs: vec![],
+                                on_enter: None,
+                                on_exit: None,
+                                terminal: false,
+                                label: None,
+                                serde: vec![],
+                            });
+                        }
+                    }
+                    let mut docs = vec![];
+                    let mut cfgs = vec![];
+                    let mut passthrough = vec![];
+                    let mut options = vec![];
+                    for attr in attrs.clone() {
+                        if attr.path().is_ident("cfg") {
+                            cfgs.push(attr.meta.require_list()?.tokens.clone())
+                        } else if attr.path().is_ident("doc") {
+                            docs.push(OuterDocString::try_from(attr)?)
+                        } else if attr.path().is_ident("fsmentry") {
+                            options.push(attr)
+                        } else {
+                            passthrough.push(attr)
+                        }
+                    }
+                    let cfg = match &*cfgs {
+                        [] => None,
+                        [cfg] => Some(cfg.clone()),
+                        cfgs => Some(quote!(all(#(#cfgs),*))),
+                    };
+                    let mut on_mismatch = None::<OnMismatch>;
+                    crate::args::Parser::new()
+                        .once("on_mismatch", &mut on_mismatch)
+                        .extract(&mut options)?;
+                    if let (Some(vis), Some(OnMismatch::Unchecked | OnMismatch::DebugUnchecked)) =
+                        (&expose_handle_field, &on_mismatch)
+                    {
+                        bail_at!(
+                            InvalidOption @ vis.span(),
+                            "`expose_handle_field` lets safe code mismatch a handle, so `on_mismatch` must not be unchecked"
+                        )
                     }
                     for (edge, to) in iter::once((edge, to)).chain(rest) {
-                        match edges.entry((from.clone().into(), to.clone().into())) {
-                            Occupied(_) => bail_at!(edge.span(), "duplicate edge definition"),
-                            Vacant(v) => {
-                                let mut attrs = attrs.clone();
-                                if let Edge::Documented(DocumentedArrow { doc, .. }) = edge {
-                                    if !attrs.is_empty() {
-                                        // newline
-                                        attrs.push(OuterDocString::new("", doc.span()))
+                        // `A <-> B` is also `B -> A`, which is handled next
+                        let (edge, backward) = edge.split();
+                        if let Some(backward) = backward {
+                            stmts.push_front(Stmt::Edges(StmtEdges {
+                                attrs: attrs.clone(),
+                                from: to.clone(),
+                                fan_in: vec![],
+                                edge: backward,
+                                to: from.clone(),
+                                rest: vec![],
+                                semi,
+                            }))
+                        }
+                        let method = edge.method().unwrap_or_else(|| {
+                            NodeId::from(to.clone()).transition_fn(&method_case)
+                        });
+                        let key = (NodeId::from(from.clone()), NodeId::from(to.clone()), method);
+                        if let Some((_, other, method)) = edges
+                            .keys()
+                            .find(|(f, t, m)| (f, m) == (&key.0, &key.2) && *t != key.1)
+                        {
+                            bail_at!(
+                                DuplicateMethod @ edge.span(),
+                                "`{}` already has a transition method `{}`, to `{}`",
+                                from,
+                                method,
+                                other.inner
+                            )
+                        }
+                        let named = edge.method().is_some();
+                        match edges.entry(key) {
+                            Occupied(o) if !inherited_edges.remove(o.key()) => {
+                                duplicate_edges.push((edge.span(), o.key().clone()))
+                            }
+                            entry => {
+                                if let Vacant(v) = &entry {
+                                    edge_order.push(v.key().clone())
+                                }
+                                let mut docs = docs.clone();
+                                let mut callback = None;
+                                let mut params = vec![];
+                                let mut ensures = None;
+                                let mut requires = None;
+                                match edge {
+                                    Edge::Documented(DocumentedArrow { doc, .. }) => {
+                                        if !docs.is_empty() {
+                                            // newline
+                                            docs.push(OuterDocString::new("", doc.span()))
+                                        }
+                                        docs.push(OuterDocString::new(doc.value(), doc.span()))
+                                    }
+                                    Edge::Ensures(EnsuresArrow { expr, .. }) => {
+                                        ensures = Some(expr)
+                                    }
+                                    Edge::Requires(RequiresArrow { expr, .. }) => {
+                                        requires = Some(expr)
+                                    }
+                                    Edge::Callback(CallbackArrow { method, .. }) => {
+                                        callback = Some(method)
                                     }
-                                    attrs.push(OuterDocString::new(doc.value(), doc.span()))
+                                    Edge::Named(NamedArrow {
+                                        method,
+                                        params: Some(Params { params: it, .. }),
+                                        ..
+                                    }) => {
+                                        for Param { name, ty, .. } in it {
+                                            if ["from", "to", "next", "hooks"]
+                                                .iter()
+                                                .any(|it| name == it)
+                                            {
+                                                bail_at!(
+                                                    Reserved @ name.span(),
+                                                    "`{}` is reserved, and may not be used as an argument name",
+                                                    name
+                                                )
+                                            }
+                                            params.push((name, ty))
+                                        }
+                                        callback = Some(method)
+                                    }
+                                    Edge::Short(_)
+                                    | Edge::Long(..)
+                                    | Edge::Named(_)
+                                    | Edge::Both(_)
+                                    | Edge::NamedBoth(_) => {}
                                 }
-                                v.insert(attrs);
+                                // overrides an inherited edge
+                                *entry.or_default() = EdgeData {
+                                    docs,
+                                    callback,
+                                    params,
+                                    cfg: cfg.clone(),
+                                    ensures,
+                                    requires,
+                                    attrs: passthrough.clone(),
+                                    on_mismatch: on_mismatch.clone(),
+                                    named,
+                                };
                             }
                         }
                         from = to;
                     }
                 }
+                Stmt::Wildcard(StmtWildcard {
+                    attrs,
+                    wildcard,
+                    edge,
+                    to,
+                    semi,
+                }) => {
+                    if let (_, Some(_)) = edge.clone().split() {
+                        bail_at!(
+                            Unsupported @ edge.span(),
+                            "`<->` may not be used with `{}`, since there is no one state to return to",
+                            wildcard.to_token_stream()
+                        )
+                    }
+                    // expanded to an edge from every other node which may be left,
+                    // and doesn't already go there by any method, which are handled next
+                    let to_id = NodeId::from(to.clone());
+                    for (from, NodeData { terminal, .. }) in nodes.iter().rev() {
+                        if *from == to_id
+                            || *terminal
+                            || edges.keys().any(|(f, t, _)| (f, t) == (from, &to_id))
+                        {
+                            continue;
+                        }
+                        stmts.push_front(Stmt::Edges(StmtEdges {
+                            attrs: attrs.clone(),
+                            from: Ident::new(&from.inner.to_string(), wildcard.span()),
+                            fan_in: vec![],
+                            edge: edge.clone(),
+                            to: to.clone(),
+                            rest: vec![],
+                            semi,
+                        }))
+                    }
+                }
+                Stmt::SameRank(StmtSameRank { nodes: idents, .. }) => {
+                    let mut same = vec![];
+                    for ident in idents {
+                        same.push(existing(&nodes, ident)?)
+                    }
+                    ranks.push((Rank::Same, same))
+                }
+                Stmt::Rank(StmtRank { hints, .. }) => {
+                    for RankHint { node, rank, .. } in hints {
+                        let all = [Rank::Min, Rank::Max, Rank::Source, Rank::Sink];
+                        let Some(rank) = all.into_iter().find(|it| rank == it.as_str()) else {
+                            bail_at!(
+                                Syntax @ rank.span(),
+                                "expected one of `min`, `max`, `source` or `sink`"
+                            )
+                        };
+                        ranks.push((rank, vec![existing(&nodes, node)?]))
+                    }
+                }
             }
         }
 
         if nodes.is_empty() {
-            bail_at!(name.span(), "must have at least one state")
+            bail_at!(NoStates @ name.span(), "must have at least one state")
+        }
+        let initial = initial.map(|it| existing(&nodes, it)).transpose()?;
+        if let Some((from, to, _)) = edges.keys().find(|(from, ..)| nodes[from].terminal) {
+            bail_at!(
+                TerminalTransition @ from.inner.span(),
+                "`{}` is terminal, so may not have a transition to `{}`",
+                from.inner,
+                to.inner
+            )
+        }
+        // fields get accessors on the transition type, alongside its methods
+        for (node, data) in &nodes {
+            let Some(Data::Record(fields)) = &data.ty else {
+                continue;
+            };
+            for (field, _) in fields {
+                let accessors = [field.clone(), ident(format!("{}_mut", field.unraw()))];
+                if let Some((_, to, method)) = edges
+                    .keys()
+                    .find(|(from, _, method)| from == node && accessors.contains(method))
+                {
+                    bail_at!(
+                        NameClash @ field.span(),
+                        "the accessors for `{}` clash with the transition method `{}`, to `{}`",
+                        field,
+                        method,
+                        to.inner
+                    )
+                }
+                // fields are arguments to the transition methods into this state
+                if let Some((name, _)) = edges
+                    .iter()
+                    .filter(|((_, to, _), _)| to == node)
+                    .flat_map(|(_, data)| &data.params)
+                    .find(|(name, _)| name == field)
+                {
+                    bail_at!(
+                        NameClash @ name.span(),
+                        "`{}` is already a field of `{}`, so may not be used as an argument name",
+                        name,
+                        node.inner
+                    )
+                }
+            }
+        }
+        // the context is reached through accessors, and passed alongside event fields
+        if context.is_some() {
+            let accessors = [ident("context"), ident("context_mut")];
+            if let Some((_, to, method)) = edges.keys().find(|(_, _, it)| accessors.contains(it)) {
+                bail_at!(
+                    NameClash @ method.span(),
+                    "`{}` is an accessor for the machine's context, so may not name the transition to `{}`",
+                    method,
+                    to.inner
+                )
+            }
+            if let Some(name) = nodes
+                .values()
+                .flat_map(|it| match &it.ty {
+                    Some(Data::Record(fields)) => &fields[..],
+                    _ => &[],
+                })
+                .map(|(name, _)| name)
+                .chain(
+                    edges
+                        .values()
+                        .flat_map(|it| it.params.iter().map(|(name, _)| name)),
+                )
+                .find(|it| accessors.contains(it))
+            {
+                bail_at!(
+                    Reserved @ name.span(),
+                    "`{}` is reserved for the machine's context, and may not be used as a field or argument name",
+                    name
+                )
+            }
+        }
+        // `entry_fn` sits alongside the machine's other methods, and the pool's
+        let mut methods = ["new", "state", "state_mut"]
+            .map(|it| (it, "state machine"))
+            .to_vec();
+        if context.is_some() {
+            methods.extend(["context", "context_mut"].map(|it| (it, "state machine")))
+        }
+        if pool.as_ref().is_some_and(|it| it.value) {
+            methods.extend(
+                [
+                    "new",
+                    "len",
+                    "is_empty",
+                    "insert",
+                    "remove",
+                    "get",
+                    "iter",
+                    "in_state",
+                    "count_in_state",
+                ]
+                .map(|it| (it, "pool")),
+            )
+        }
+        if let Some((_, owner)) = methods.iter().find(|(it, _)| entry_fn == it) {
+            bail_at!(
+                NameClash @ entry_fn.span(),
+                "`{}` is already a method on the {}",
+                entry_fn,
+                owner
+            )
+        }
+        // every transition calling a hook must agree on its signature,
+        // which includes the data of the states either side
+        let mut hooks = BTreeMap::new();
+        for (
+            (from, to, _),
+            EdgeData {
+                callback, params, ..
+            },
+        ) in &edges
+        {
+            let Some(callback) = callback else { continue };
+            let data = |node: &NodeId| {
+                nodes[node]
+                    .ty
+                    .as_ref()
+                    .map(|it| it.reference().to_token_stream().to_string())
+            };
+            let sig = [data(from), data(to)]
+                .into_iter()
+                .chain(
+                    params
+                        .iter()
+                        .map(|(_, ty)| Some(ty.to_token_stream().to_string())),
+                )
+                .collect::<Vec<_>>();
+            match hooks.entry(callback) {
+                Vacant(v) => {
+                    v.insert(sig);
+                }
+                Occupied(o) if *o.get() == sig => {}
+                Occupied(o) => bail_at!(
+                    MismatchedArguments @ o.key().span(),
+                    "transitions calling `{}` must all take the same arguments, and leave and enter states with the same data",
+                    callback
+                ),
+            }
+        }
+        // state hooks take only the state, so can't share a name with a transition hook
+        for hook in nodes
+            .values()
+            .flat_map(|it| it.on_enter.iter().chain(&it.on_exit))
+        {
+            if let Some(other) = hooks.keys().find(|it| **it == hook) {
+                bail_at!(
+                    NameClash @ hook.span(),
+                    "`{}` is already called by a transition, so may not be called on entering or leaving a state",
+                    other
+                )
+            }
+        }
+        if let Some(path) = &atomic_kind {
+            if nodes.len() > usize::from(u8::MAX) + 1 {
+                bail_at!(
+                    InvalidOption @ path.span(),
+                    "`atomic_kind` supports at most 256 states, but there are {}",
+                    nodes.len()
+                )
+            }
         }
 
-        Ok(Self {
+        // removed edges are only forgotten by `edges`
+        edge_order.retain(|it| edges.contains_key(it));
+        let mut this = Self {
             attributes: attrs,
             vis,
             ident: name,
             nodes,
             edges,
-        })
+            order: (node_order, edge_order),
+            derives,
+            include_svg,
+            dot_path,
+            dot_timeout,
+            svg_max_size,
+            diagram_fallback: diagram_fallback.unwrap_or_default(),
+            atomic_kind,
+            defmt: defmt.unwrap_or_default(),
+            serde,
+            serde_tagging: (serde_tag, serde_content),
+            fmt: fmt.map(|it| it.value).unwrap_or(true),
+            mark_cfg: mark_cfg.map(|it| it.value).unwrap_or(true),
+            registry: registry.map(|it| it.value).unwrap_or(false),
+            events: events.map(|it| it.value).unwrap_or(false),
+            entry_fn,
+            entry_cell: entry_cell.map(|it| it.value).unwrap_or(false),
+            owned: owned.map(|it| it.value).unwrap_or(false),
+            transition_source: transition_source.map(|it| it.value).unwrap_or(false),
+            chain: chain.map(|it| it.value).unwrap_or(false),
+            entry_ref: entry_ref.map(|it| it.value).unwrap_or(false),
+            from_str: from_str.map(|it| it.value).unwrap_or(false),
+            state_accessors: state_accessors.map(|it| it.value).unwrap_or(false),
+            pool: pool.map(|it| it.value).unwrap_or(false),
+            handle_affixes,
+            conversions: conversions
+                .into_iter()
+                .flat_map(|it| match it {
+                    Conversion::BorrowMut => vec![Conversion::Borrow, it],
+                    Conversion::DerefMut => vec![Conversion::Deref, it],
+                    it => vec![it],
+                })
+                .collect(),
+            expose_handle_field,
+            handle_vis: handle_vis.unwrap_or_else(|| parse_quote!(pub)),
+            on_mismatch,
+            mismatch_hook,
+            rankdir,
+            method_case,
+            emit: emit.unwrap_or_default(),
+            initial,
+            module,
+            state_mod,
+            entry_mod,
+            context,
+            aliases,
+            allow,
+            doc_templates,
+            ranks,
+            warnings,
+        };
+
+        let mut errors = duplicate_edges.into_iter().map(|(span, (from, to, method))| {
+            let mut message = format!(
+                "duplicate edge definition\n\n{}",
+                this.neighborhood(&from, Some(&to))
+            );
+            // parallel edges are fine, as long as each has its own method
+            if method == to.transition_fn(&this.method_case) {
+                message.push_str(&format!(
+                    "\n\nto have several transitions between these states, name them, like `{} -name-> {}`",
+                    from.inner, to.inner
+                ))
+            }
+            syn::Error::new(span, message)
+        });
+        if let Some(mut first) = errors.next() {
+            first.extend(errors);
+            return Err(CoreError::with_kind(ErrorKind::DuplicateEdge, first));
+        }
+
+        // by-value transitions have no machine to borrow the context from
+        if let (true, Some(ty)) = (this.owned, &this.context) {
+            bail_at!(
+                InvalidOption @ ty.span(),
+                "`owned` transitions can't reach the machine's context"
+            )
+        }
+
+        // `State::is_terminal` is generated when any state is terminal
+        if this.state_accessors && this.nodes.values().any(|it| it.terminal) {
+            if let Some(node) = this.nodes.keys().find(|it| it.variant() == "Terminal") {
+                bail_at!(
+                    NameClash @ node.inner.span(),
+                    "the accessors for `{}` would clash with `State::is_terminal`",
+                    node.inner
+                )
+            }
+        }
+
+        // only the obvious collisions, since types in scope of the macro are unknown
+        let generated = this.generated_names();
+        if let Some(clash) = generated[1..].iter().find(|it| **it == generated[0]) {
+            bail_at!(
+                NameClash @ this.ident.span(),
+                "the machine would clash with the generated `{}`",
+                clash
+            )
+        }
+        for TypeAlias { ident, .. } in &this.aliases {
+            let handles = this
+                .nodes
+                .keys()
+                .filter(|it| this.outgoing(it).is_some())
+                .map(|it| this.transition_ty(it));
+            if let Some(clash) = generated
+                .iter()
+                .cloned()
+                .chain(handles)
+                .find(|it| it == ident)
+            {
+                bail_at!(
+                    NameClash @ ident.span(),
+                    "the alias `{}` would clash with the generated `{}`",
+                    ident,
+                    clash
+                )
+            }
+        }
+        let mut variants = BTreeMap::new();
+        for node in this.nodes.keys() {
+            if let Some(other) = variants.insert(node.variant(), node) {
+                bail_at!(
+                    NameClash @ node.inner.span(),
+                    "`{}` and `{}` would both be called `{}`",
+                    other.inner,
+                    node.inner,
+                    node.variant()
+                )
+            }
+            let handle = this.transition_ty(node);
+            if this.outgoing(node).is_some() && generated.contains(&handle) {
+                bail_at!(
+                    NameClash @ node.inner.span(),
+                    "the transition type for `{}` would clash with the generated `{}`, \
+                    consider `#[fsmentry(handle_suffix = \"Handle\")]`",
+                    node.inner,
+                    handle
+                )
+            }
+        }
+
+        // each event has one variant, so its transitions must agree on their arguments
+        if this.events {
+            let mut events = BTreeMap::new();
+            for ((_, to, method), data) in &this.edges {
+                let args = this
+                    .transition_args(to, &data.params)
+                    .iter()
+                    .map(|(name, ty)| (name.to_string(), ty.to_token_stream().to_string()))
+                    .collect::<Vec<_>>();
+                match events.entry(method) {
+                    Vacant(v) => {
+                        v.insert(args);
+                    }
+                    Occupied(o) if *o.get() == args => {}
+                    Occupied(_) => bail_at!(
+                        MismatchedArguments @ method.span(),
+                        "transitions named `{}` must all take the same arguments to be an event",
+                        method
+                    ),
+                }
+            }
+        }
+
+        if let Some(initial) = &this.initial {
+            let mut reachable = BTreeSet::from([initial]);
+            let mut queue = vec![initial];
+            while let Some(from) = queue.pop() {
+                for (_, to, _) in this.edges.keys().filter(|(it, ..)| it == from) {
+                    if reachable.insert(to) {
+                        queue.push(to)
+                    }
+                }
+            }
+            let warnings = this
+                .nodes
+                .keys()
+                .filter(|it| !reachable.contains(it))
+                .map(|node| Warning {
+                    span: node.inner.span(),
+                    message: format!(
+                        "`{}` can't be reached from the initial state `{}`\n\n{}",
+                        node.inner,
+                        initial.inner,
+                        this.neighborhood(node, None)
+                    ),
+                })
+                .collect::<Vec<_>>();
+            this.warnings.extend(warnings)
+        }
+
+        // the generated code grows with the graph, and is expanded on every build
+        for (what, count, max, option) in [
+            ("states", this.nodes.len(), max_states, "max_states"),
+            ("transitions", this.edges.len(), max_edges, "max_edges"),
+        ] {
+            if count > max {
+                this.warnings.push(Warning {
+                    span: this.ident.span(),
+                    message: format!(
+                        "`{}` has {} {}, which is more than the {} allowed by `{}`, \
+                        so may be slow to compile. \
+                        Consider generating the code ahead of time with the `fsmentry` CLI, \
+                        or from a build script with `FSMGenerator`",
+                        this.ident, count, what, max, option
+                    ),
+                })
+            }
+        }
+
+        return Ok(this);
+
+        /// Layout hints may only refer to states declared elsewhere.
+        fn existing(nodes: &BTreeMap<NodeId, NodeData>, ident: Ident) -> Result<NodeId, CoreError> {
+            let span = ident.span();
+            let node = NodeId::from(ident);
+            match nodes.contains_key(&node) {
+                true => Ok(node),
+                false => bail_at!(Undefined @ span, "no state named `{}`", node.inner),
+            }
+        }
+    }
+}
+
+/// `#![...]` at the start of several machines, as outer attributes for each.
+fn shared_attrs(input: ParseStream) -> syn::Result<Vec<syn::Attribute>> {
+    let mut attrs = input.call(syn::Attribute::parse_inner)?;
+    for attr in &mut attrs {
+        attr.style = syn::AttrStyle::Outer
     }
+    Ok(attrs)
 }
 
 trait IdentExt {
     fn get_ident(&self) -> &Ident;
+    /// A name derived from this one, or this one if the result isn't an identifier,
+    /// like `1` from `_1`.
+    fn derived(&self, name: String) -> Ident {
+        ident_at(&name, self.get_ident().span()).unwrap_or_else(|_| self.get_ident().clone())
+    }
     #[allow(non_snake_case)]
     fn UpperCamelCase(&self) -> Ident {
-        Ident::new(
-            &self.get_ident().to_string().to_upper_camel_case(),
-            self.get_ident().span(),
-        )
+        self.derived(self.get_ident().unraw().to_string().to_upper_camel_case())
     }
     fn snake_case(&self) -> Ident {
-        Ident::new(
-            &self.get_ident().to_string().to_snake_case(),
-            self.get_ident().span(),
-        )
+        self.derived(self.get_ident().unraw().to_string().to_snake_case())
     }
 }
 
@@ -0,0 +1,171 @@
+use crate::{
+    util::{paragraphs, unparse, OuterDocString},
+    EdgeData, FSMGenerator, NodeData,
+};
+
+impl FSMGenerator {
+    /// Render a documentation page for the state machine in [GitHub-flavored Markdown](https://github.github.com/gfm/).
+    ///
+    /// The page contains a [`mermaid`](Self::mermaid) diagram,
+    /// a table of states and a table of transitions.
+    pub fn markdown(&self) -> String {
+        let mut s = format!("# `{}`\n\n", self.ident);
+        let docs = self
+            .attributes
+            .iter()
+            .filter_map(|attr| OuterDocString::try_from(attr.clone()).ok())
+            .map(|it| it.doc.value().trim().to_owned())
+            .collect::<Vec<_>>();
+        if !docs.is_empty() {
+            s.push_str(&docs.join("\n"));
+            s.push_str("\n\n");
+        }
+        s.push_str(&format!("```mermaid\n{}```\n\n", self.mermaid()));
+
+        s.push_str("## States\n\n");
+        s.push_str("| State | Data | Documentation |\n");
+        s.push_str("| ----- | ---- | ------------- |\n");
+        for (node, NodeData { ty, docs, .. }) in &self.nodes {
+            let ty = match ty {
+                Some(ty) => format!("`{}`", escape(&unparse(ty))),
+                None => String::new(),
+            };
+            s.push_str(&format!("| `{}` | {} | {} |\n", node.inner, ty, cell(docs)))
+        }
+
+        s.push_str("\n## Transitions\n\n");
+        s.push_str("| From | To | Method | Documentation |\n");
+        s.push_str("| ---- | -- | ------ | ------------- |\n");
+        for (
+            (from, to, method),
+            EdgeData {
+                docs,
+                callback,
+                params,
+                cfg,
+                ensures,
+                requires,
+                attrs: _,
+                on_mismatch: _,
+                named: _,
+            },
+        ) in &self.edges
+        {
+            let mut docs = cell(docs);
+            if let Some(callback) = callback {
+                if !docs.is_empty() {
+                    docs.push_str("<br>")
+                }
+                docs.push_str(&format!(
+                    "Calls `{}::{}`",
+                    self.hooks_trait_name(),
+                    callback
+                ));
+                if !params.is_empty() {
+                    let params = params
+                        .iter()
+                        .map(|(name, ty)| format!("`{}: {}`", name, unparse(ty)))
+                        .collect::<Vec<_>>();
+                    docs.push_str(&escape(&format!(" with {}", params.join(", "))))
+                }
+            }
+            if let Some(requires) = requires {
+                if !docs.is_empty() {
+                    docs.push_str("<br>")
+                }
+                docs.push_str(&escape(&format!("Requires `{}`", unparse(requires))))
+            }
+            if let Some(ensures) = ensures {
+                if !docs.is_empty() {
+                    docs.push_str("<br>")
+                }
+                docs.push_str(&escape(&format!("Ensures `{}`", unparse(ensures))))
+            }
+            if let Some(cfg) = cfg {
+                if !docs.is_empty() {
+                    docs.push_str("<br>")
+                }
+                docs.push_str(&escape(&format!("Only with `cfg({})`", cfg)))
+            }
+            s.push_str(&format!(
+                "| `{}` | `{}` | `{}::{}` | {} |\n",
+                from.inner,
+                to.inner,
+                self.transition_ty(from),
+                method,
+                docs
+            ))
+        }
+        s
+    }
+}
+
+/// Join documentation lines into a single table cell.
+fn cell(docs: &[OuterDocString]) -> String {
+    paragraphs(docs)
+        .iter()
+        .map(|it| escape(it))
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[test]
+fn markdown() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            /// A traffic light.
+            pub TrafficLight {
+                /// Cars must stop
+                Red;
+                Green: Vec<u8>;
+                /// Cars speed up
+                Red -> Green -"slow down"-> Amber -on(log)-> Red;
+            }
+        })
+        .unwrap();
+    assert_eq!(
+        generator.markdown(),
+        "\
+# `TrafficLight`
+
+A traffic light.
+
+```mermaid
+stateDiagram-v2
+    accTitle: State machine TrafficLight with 3 states and 3 transitions.
+    accDescr {
+        Amber goes to Red.
+        Green goes to Amber.
+        Red goes to Green.
+    }
+    Amber
+    Green
+    Red
+    Amber --> Red
+    Green --> Amber
+    Red --> Green
+```
+
+## States
+
+| State | Data | Documentation |
+| ----- | ---- | ------------- |
+| `Amber` |  |  |
+| `Green` | `Vec<u8>` |  |
+| `Red` |  | Cars must stop |
+
+## Transitions
+
+| From | To | Method | Documentation |
+| ---- | -- | ------ | ------------- |
+| `Amber` | `Red` | `Amber::red` | Cars speed up<br>Calls `Hooks::log` |
+| `Green` | `Amber` | `Green::amber` | Cars speed up<br>slow down |
+| `Red` | `Green` | `Red::green` | Cars speed up |
+"
+    );
+}
@@ -0,0 +1,118 @@
+use syn::parse_quote;
+
+use crate::FSMGenerator;
+
+/// A machine in [`FSMGenerator::registry`].
+///
+/// Unlike [`FSMGenerator`], this may be sent to other threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryEntry {
+    /// The name of the state machine.
+    pub name: String,
+    /// The name of each state.
+    pub states: Vec<String>,
+    /// From [`FSMGenerator::mermaid`].
+    pub mermaid: String,
+}
+
+impl FSMGenerator {
+    /// This machine's entry in [`Self::registry`], if it has `#[fsmentry(registry = true)]`.
+    pub fn registry_entry(&self) -> Option<RegistryEntry> {
+        self.registry.then(|| RegistryEntry {
+            name: self.ident.to_string(),
+            states: self.nodes.keys().map(|it| it.inner.to_string()).collect(),
+            mermaid: self.mermaid(),
+        })
+    }
+
+    /// A `fsm_registry` module listing every machine with `#[fsmentry(registry = true)]`,
+    /// so that an application can enumerate and render its state machines at run time.
+    ///
+    /// Returns [`None`] if no machine opted in.
+    ///
+    /// ```rust,ignore
+    /// pub mod fsm_registry {
+    ///     pub struct Machine {
+    ///         pub name: &'static str,
+    ///         pub states: &'static [&'static str],
+    ///         pub mermaid: &'static str,
+    ///     }
+    ///     pub const MACHINES: &[Machine] = &[..];
+    ///     pub fn get(name: &str) -> Option<&'static Machine> { .. }
+    /// }
+    /// ```
+    pub fn registry(machines: &[Self]) -> Option<syn::ItemMod> {
+        Self::registry_of(machines.iter().filter_map(Self::registry_entry))
+    }
+
+    /// [`Self::registry`], from entries which may have been gathered on other threads.
+    pub fn registry_of(entries: impl IntoIterator<Item = RegistryEntry>) -> Option<syn::ItemMod> {
+        let machines = entries
+            .into_iter()
+            .map(|it| {
+                let RegistryEntry {
+                    name,
+                    states,
+                    mermaid,
+                } = it;
+                quote::quote! {
+                    Machine {
+                        name: #name,
+                        states: &[#(#states),*],
+                        mermaid: #mermaid,
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        if machines.is_empty() {
+            return None;
+        }
+        Some(parse_quote! {
+            /// The state machines defined alongside this module.
+            pub mod fsm_registry {
+                /// A state machine, described for debugging and introspection.
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                pub struct Machine {
+                    /// The name of the state machine, as it was defined.
+                    pub name: &'static str,
+                    /// The name of each state.
+                    pub states: &'static [&'static str],
+                    /// A [`mermaid`](https://mermaid.js.org/) state diagram.
+                    pub mermaid: &'static str,
+                }
+
+                /// Every registered state machine.
+                pub const MACHINES: &[Machine] = &[#(#machines),*];
+
+                /// Look up a state machine by name.
+                pub fn get(name: &str) -> ::core::option::Option<&'static Machine> {
+                    MACHINES.iter().find(|it| it.name == name)
+                }
+            }
+        })
+    }
+}
+
+#[test]
+fn registry() {
+    use quote::ToTokens as _;
+    use syn::parse::Parser as _;
+    let machines = FSMGenerator::parse_dsl_many
+        .parse2(quote::quote! {
+            #[fsmentry(registry = true)]
+            Door {
+                Open -> Closed;
+            }
+            Hidden {
+                A -> B;
+            }
+        })
+        .unwrap();
+    let registry = FSMGenerator::registry(&machines)
+        .unwrap()
+        .into_token_stream()
+        .to_string();
+    assert!(registry.contains(r#"name : "Door" , states : & ["Closed" , "Open"]"#));
+    assert!(!registry.contains("Hidden"));
+    assert!(FSMGenerator::registry(&machines[1..]).is_none());
+}
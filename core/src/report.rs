@@ -0,0 +1,108 @@
+use std::collections::BTreeSet;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens as _;
+
+use crate::FSMGenerator;
+
+/// How much code [`FSMGenerator::codegen`] generates for a state machine,
+/// for tracking the growth of the generated API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Report {
+    /// The variants of every generated enum.
+    pub variants: usize,
+    /// The types with methods to transition out of each state.
+    pub handle_structs: usize,
+    /// All the other structs, enums and traits.
+    pub other_types: usize,
+    /// Methods in `impl` blocks and traits.
+    pub methods: usize,
+    pub impls: usize,
+    /// The size of the generated code, without diagrams.
+    pub tokens: usize,
+}
+
+impl FSMGenerator {
+    /// Count the items in the generated code.
+    pub fn report(&self) -> Report {
+        let handles = self
+            .nodes
+            .keys()
+            .filter(|it| self.outgoing(it).is_some())
+            .map(|it| self.transition_ty(it))
+            .collect::<BTreeSet<_>>();
+        let file = self.codegen();
+        let mut report = Report {
+            tokens: tokens(file.to_token_stream()),
+            ..Report::default()
+        };
+        count(&file.items, &handles, &mut report);
+        report
+    }
+}
+
+fn count(items: &[syn::Item], handles: &BTreeSet<proc_macro2::Ident>, report: &mut Report) {
+    for item in items {
+        match item {
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => count(items, handles, report),
+            syn::Item::Struct(it) => match handles.contains(&it.ident) {
+                true => report.handle_structs += 1,
+                false => report.other_types += 1,
+            },
+            syn::Item::Enum(it) => {
+                report.other_types += 1;
+                report.variants += it.variants.len();
+            }
+            syn::Item::Trait(it) => {
+                report.other_types += 1;
+                report.methods += it
+                    .items
+                    .iter()
+                    .filter(|it| matches!(it, syn::TraitItem::Fn(_)))
+                    .count();
+            }
+            syn::Item::Impl(it) => {
+                report.impls += 1;
+                report.methods += it
+                    .items
+                    .iter()
+                    .filter(|it| matches!(it, syn::ImplItem::Fn(_)))
+                    .count();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn tokens(stream: TokenStream) -> usize {
+    stream
+        .into_iter()
+        .map(|it| match it {
+            TokenTree::Group(group) => 1 + tokens(group.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+#[test]
+fn report() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            Machine {
+                A -> B -> C;
+                A -> C;
+            }
+        })
+        .unwrap();
+    let report = generator.report();
+    // `A` and `B` may be left
+    assert_eq!(report.handle_structs, 2);
+    // in `State`, `StateKind` and `Entry`
+    assert_eq!(report.variants, 9);
+    assert!(report.methods >= 3);
+    assert!(report.tokens > 0);
+}
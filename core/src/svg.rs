@@ -0,0 +1,651 @@
+use std::{
+    env, fmt, io,
+    io::{Read as _, Write as _},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use proc_macro2::Ident;
+use quote::ToTokens as _;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+};
+
+use crate::{DiagramFallback, FSMGenerator, NodeId};
+
+/// Overrides the graphviz binary for all state machines without a `dot_path`.
+const DOT_PATH_ENV: &str = "FSMENTRY_DOT_PATH";
+
+/// How long graphviz may take to render, if not configured.
+pub const DEFAULT_DOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How large a minified diagram may be before it is left out of the docs, if not configured.
+pub const DEFAULT_SVG_MAX_SIZE: usize = 128 * 1024;
+
+/// Whether to render a diagram for the documentation,
+/// from `#[fsmentry(svg = auto | force | omit)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncludeSvg {
+    /// Render if graphviz is available.
+    Auto,
+    /// Fail if graphviz is unavailable.
+    Force,
+    /// Don't render.
+    Omit,
+}
+
+impl Parse for IncludeSvg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse::<syn::Ident>()?;
+        match &*ident.to_string() {
+            "auto" => Ok(Self::Auto),
+            "force" => Ok(Self::Force),
+            "omit" => Ok(Self::Omit),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "expected one of `auto`, `force` or `omit`",
+            )),
+        }
+    }
+}
+
+impl crate::args::Value for IncludeSvg {
+    const SHAPE: &'static str = "auto | force | omit";
+}
+
+/// The ways that rendering with graphviz can fail.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The binary couldn't be started.
+    Spawn { dot: PathBuf, source: io::Error },
+    /// Piping to or from the binary failed.
+    Io(io::Error),
+    /// The binary didn't exit in time, so was killed.
+    Timeout { dot: PathBuf, timeout: Duration },
+    /// The binary exited unsuccessfully.
+    Failed {
+        dot: PathBuf,
+        status: ExitStatus,
+        stderr: String,
+    },
+    /// The binary returned an SVG that wasn't UTF-8.
+    NonUtf8 { dot: PathBuf },
+    /// The SVG was larger than `#[fsmentry(svg_max_size = <bytes>)]`, even after minifying.
+    TooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Spawn { dot, source } => write!(
+                f,
+                "couldn't run `{}` - is graphviz installed? ({})",
+                dot.display(),
+                source
+            ),
+            RenderError::Io(e) => write!(f, "couldn't communicate with graphviz: {}", e),
+            RenderError::Timeout { dot, timeout } => write!(
+                f,
+                "`{}` didn't finish within {:?}, so was killed",
+                dot.display(),
+                timeout
+            ),
+            RenderError::Failed {
+                dot,
+                status,
+                stderr,
+            } => {
+                write!(f, "`{}` failed ({})", dot.display(), status)?;
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ":\n{}", stderr),
+                }
+            }
+            RenderError::NonUtf8 { dot } => {
+                write!(f, "`{}` returned a non-utf8 svg", dot.display())
+            }
+            RenderError::TooLarge { size, max } => write!(
+                f,
+                "the diagram is {} bytes, which is more than the {} allowed by `svg_max_size`",
+                size, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Spawn { source, .. } | RenderError::Io(source) => Some(source),
+            RenderError::Timeout { .. }
+            | RenderError::Failed { .. }
+            | RenderError::NonUtf8 { .. }
+            | RenderError::TooLarge { .. } => None,
+        }
+    }
+}
+
+/// A call to graphviz, from [`FSMGenerator::render_job`].
+///
+/// Unlike [`FSMGenerator`], this may be sent to other threads.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RenderJob {
+    /// The graphviz binary.
+    pub dot: PathBuf,
+    /// The `DOT` source to render.
+    pub input: String,
+    /// The binary is killed if it runs for longer than this.
+    pub timeout: Duration,
+}
+
+impl RenderJob {
+    /// Run graphviz, returning the SVG.
+    pub fn run(&self) -> Result<String, RenderError> {
+        let RenderJob {
+            dot,
+            input,
+            timeout,
+        } = self.clone();
+        let mut child = match Command::new(&dot)
+            .arg("-Tsvg")
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(it) => it,
+            Err(source) => return Err(RenderError::Spawn { dot, source }),
+        };
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        // Pipe on other threads so a stalled pipe can't stop us from enforcing the timeout.
+        // They aren't joined after a timeout, since the child's own children may hold the pipes open.
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let stdout = thread::spawn(move || {
+            let mut buf = vec![];
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+        let stderr = thread::spawn(move || {
+            let mut buf = vec![];
+            stderr.read_to_end(&mut buf).map(|_| buf)
+        });
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(RenderError::Io)? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(RenderError::Timeout { dot, timeout });
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let join = |it: thread::JoinHandle<io::Result<Vec<u8>>>| {
+            it.join()
+                .expect("reading a pipe doesn't panic")
+                .map_err(RenderError::Io)
+        };
+        let (stdout, stderr) = (join(stdout)?, join(stderr)?);
+        match status.success() {
+            true => String::from_utf8(stdout).map_err(|_| RenderError::NonUtf8 { dot }),
+            false => Err(RenderError::Failed {
+                dot,
+                status,
+                stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            }),
+        }
+    }
+}
+
+impl FSMGenerator {
+    /// `#[fsmentry(svg = ...)]`, if given.
+    pub fn include_svg(&self) -> Option<IncludeSvg> {
+        self.include_svg
+    }
+
+    /// The graphviz `dot` binary configured for this state machine, if any.
+    ///
+    /// This is `#[fsmentry(dot_path = "...")]`,
+    /// or else the `FSMENTRY_DOT_PATH` environment variable.
+    pub fn dot_path(&self) -> Option<PathBuf> {
+        match &self.dot_path {
+            Some(it) => Some(PathBuf::from(it.value())),
+            None => env::var_os(DOT_PATH_ENV).map(PathBuf::from),
+        }
+    }
+
+    /// Render [`Self::dot`] to an SVG by shelling out to graphviz.
+    ///
+    /// See [`Self::render_job`] for the arguments.
+    pub fn render_svg(
+        &self,
+        dot_path: Option<&Path>,
+        timeout: Option<Duration>,
+    ) -> Result<String, RenderError> {
+        self.render_job(dot_path, timeout).run()
+    }
+
+    /// Prepare to render [`Self::dot`] to an SVG by shelling out to graphviz.
+    ///
+    /// The binary is `dot_path` if given, else [`Self::dot_path`], else `dot` from the `PATH`.
+    ///
+    /// The binary is killed if it runs for longer than `timeout` if given,
+    /// else `#[fsmentry(dot_timeout = <seconds>)]` (10 seconds by default).
+    pub fn render_job(&self, dot_path: Option<&Path>, timeout: Option<Duration>) -> RenderJob {
+        RenderJob {
+            dot: match dot_path {
+                Some(it) => it.to_path_buf(),
+                None => self.dot_path().unwrap_or_else(|| PathBuf::from("dot")),
+            },
+            input: self.dot().to_token_stream().to_string(),
+            timeout: timeout.unwrap_or(self.dot_timeout),
+        }
+    }
+    /// Embed `svg` in the documentation of the module generated by [`Self::codegen`].
+    ///
+    /// `svg` should be the output of `dot -Tsvg` on [`Self::dot`].
+    /// Nodes in the diagram link to their variant on the state enum,
+    /// and edges link to their transition method.
+    /// Each node also has an anchor, which the variants of the state and entry enums link back to.
+    ///
+    /// The SVG is minified first, dropping its XML prologue, comments, metadata and indentation.
+    /// If it is still larger than `#[fsmentry(svg_max_size = <bytes>)]` (128 KiB by default),
+    /// it is not embedded, and [`RenderError::TooLarge`] is returned.
+    pub fn attach_svg(&self, file: &mut syn::File, svg: &str) -> Result<(), RenderError> {
+        let Some(syn::Item::Mod(syn::ItemMod { attrs, .. })) = file.items.first_mut() else {
+            unreachable!("the code generates a module")
+        };
+        let svg = minify_svg(svg);
+        if svg.len() > self.svg_max_size {
+            return Err(RenderError::TooLarge {
+                size: svg.len(),
+                max: self.svg_max_size,
+            });
+        }
+        let svg = format!("<div>{}</div>", self.label_svg(&self.link_svg(&svg)));
+        if !attrs.is_empty() {
+            attrs.push(parse_quote!(#[doc = ""]))
+        }
+        attrs.push(parse_quote!(#[doc = #svg]));
+        self.link_variants_to_diagram(file);
+        Ok(())
+    }
+
+    /// Point each variant of the state and entry enums at its node in the diagram,
+    /// which is on the module page.
+    fn link_variants_to_diagram(&self, file: &mut syn::File) {
+        let Some(syn::Item::Mod(syn::ItemMod {
+            content: Some((_, items)),
+            ..
+        })) = file.items.first_mut()
+        else {
+            unreachable!("the code generates a module")
+        };
+        let enums = [self.state_enum_name(), self.entry_enum_name()];
+        for item in items {
+            let syn::Item::Enum(syn::ItemEnum {
+                ident, variants, ..
+            }) = item
+            else {
+                continue;
+            };
+            if !enums.contains(ident) {
+                continue;
+            }
+            for syn::Variant { attrs, ident, .. } in variants {
+                let line = format!(
+                    "See it in the [diagram](index.html#{}).",
+                    diagram_anchor(ident)
+                );
+                if attrs.iter().any(|it| it.path().is_ident("doc")) {
+                    attrs.push(parse_quote!(#[doc = ""]))
+                }
+                attrs.push(parse_quote!(#[doc = #line]))
+            }
+        }
+    }
+
+    /// Embed a plain text diagram in the documentation of the module generated by [`Self::codegen`],
+    /// for when [`Self::attach_svg`] isn't called, so readers aren't left without one.
+    ///
+    /// This does nothing under `#[fsmentry(diagram_fallback = none)]`.
+    pub fn attach_text_diagram(&self, file: &mut syn::File) {
+        let DiagramFallback::Text = self.diagram_fallback else {
+            return;
+        };
+        let Some(syn::Item::Mod(syn::ItemMod { attrs, .. })) = file.items.first_mut() else {
+            unreachable!("the code generates a module")
+        };
+        let text = format!("```text\n{}```", self.text_diagram());
+        if !attrs.is_empty() {
+            attrs.push(parse_quote!(#[doc = ""]))
+        }
+        attrs.push(parse_quote!(#[doc = #text]))
+    }
+
+    /// Each line is a chain of transitions, like `Red -> RedAmber -> Green`,
+    /// and states without any transitions are listed alone.
+    fn text_diagram(&self) -> String {
+        let mut unused = self.edges.keys().collect::<Vec<_>>();
+        let mut s = String::new();
+        while let Some(&(first, ..)) = unused.first() {
+            let mut from = first;
+            s.push_str(&from.inner.to_string());
+            while let Some(ix) = unused.iter().position(|(it, ..)| it == from) {
+                let (_, to, method) = unused.remove(ix);
+                match self.edge_label(
+                    to,
+                    method,
+                    &self.edges[&(from.clone(), to.clone(), method.clone())],
+                ) {
+                    Some(label) => s.push_str(&format!(" -{}-> {}", label, to.inner)),
+                    None => s.push_str(&format!(" -> {}", to.inner)),
+                }
+                from = to;
+            }
+            s.push('\n')
+        }
+        for node in self.nodes.keys() {
+            if self.outgoing(node).is_none() && self.incoming(node).is_none() {
+                s.push_str(&format!("{}\n", node.inner))
+            }
+        }
+        s
+    }
+
+    /// Wrap the contents of each node and edge group from graphviz in a link to rustdoc.
+    fn link_svg(&self, svg: &str) -> String {
+        let mut out = String::new();
+        let mut rest = svg;
+        while let Some(ix) = rest.find("<g ") {
+            let (before, group) = rest.split_at(ix);
+            out.push_str(before);
+            let Some((open, title, body, after)) = split_group(group) else {
+                out.push_str("<g ");
+                rest = &group[3..];
+                continue;
+            };
+            match self.title_node(open, title) {
+                // graphviz numbers its groups, so give nodes a stable id
+                Some(node) => match open.split_once(" id=\"") {
+                    Some((start, rest)) => out.push_str(&format!(
+                        "{} id=\"{}\"{}",
+                        start,
+                        diagram_anchor(&node.variant()),
+                        rest.split_once('"').map(|(_, it)| it).unwrap_or(rest)
+                    )),
+                    None => out.push_str(open),
+                },
+                None => out.push_str(open),
+            }
+            out.push_str(title);
+            match self.href(open, title, body) {
+                Some(href) => out.push_str(&format!(
+                    "<a href=\"{href}\" xlink:href=\"{href}\">{body}</a></g>"
+                )),
+                None => {
+                    out.push_str(body);
+                    out.push_str("</g>")
+                }
+            }
+            rest = after;
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The node drawn by a graphviz group, if it is a node.
+    fn title_node(&self, open: &str, title: &str) -> Option<&NodeId> {
+        let title = svg_title(title)?;
+        match open.contains("class=\"node\"") {
+            true => self.nodes.keys().find(|it| it.inner == title),
+            false => None,
+        }
+    }
+
+    /// The rustdoc link (relative to the module page) for a graphviz group.
+    fn href(&self, open: &str, title: &str, body: &str) -> Option<String> {
+        if let Some(node) = self.title_node(open, title) {
+            return Some(format!(
+                "enum.{}.html#variant.{}",
+                self.state_enum_name(),
+                node.variant()
+            ));
+        }
+        if !open.contains("class=\"edge\"") {
+            return None;
+        }
+        let title = svg_title(title)?;
+        let (from, to) = title.split_once("->")?;
+        let mut parallel = self
+            .edges
+            .keys()
+            .filter(|(NodeId { inner: f }, NodeId { inner: t }, _)| f == from && t == to)
+            .peekable();
+        let first = parallel.peek().copied()?;
+        // parallel edges share a title, so tell them apart by their label
+        let (from, _, method) = parallel
+            .find(|(_, to, method)| {
+                *method != to.transition_fn(&self.method_case)
+                    && body.contains(&format!(">{}", method))
+            })
+            .unwrap_or(first);
+        Some(format!(
+            "struct.{}.html#method.{}",
+            self.transition_ty(from),
+            method
+        ))
+    }
+}
+
+/// The text of a graphviz `<title>` element.
+fn svg_title(title: &str) -> Option<String> {
+    Some(
+        title
+            .trim()
+            .strip_prefix("<title>")?
+            .strip_suffix("</title>")?
+            .replace("&#45;", "-")
+            .replace("&gt;", ">"),
+    )
+}
+
+/// The id of a state's node in the diagram, for linking to it from rustdoc.
+fn diagram_anchor(variant: &Ident) -> String {
+    format!("diagram.{}", variant)
+}
+
+/// Drop everything from graphviz's output that a browser doesn't need to draw it.
+fn minify_svg(svg: &str) -> String {
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg;
+    while !rest.is_empty() {
+        let skip = [
+            ("<?", "?>"),
+            ("<!--", "-->"),
+            ("<!", ">"),
+            ("<metadata", "</metadata>"),
+        ];
+        if let Some((_, end)) = skip.iter().find(|(start, _)| rest.starts_with(start)) {
+            rest = match rest.find(end) {
+                Some(ix) => &rest[ix + end.len()..],
+                None => "",
+            };
+            continue;
+        }
+        if rest.starts_with('<') {
+            let end = rest.find('>').map(|it| it + 1).unwrap_or(rest.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        // text, which is dropped between tags, and otherwise has its whitespace collapsed
+        let end = rest.find('<').unwrap_or(rest.len());
+        let text = rest[..end].split_whitespace().collect::<Vec<_>>();
+        if !text.is_empty() {
+            let (first, last) = (
+                rest.starts_with(char::is_whitespace),
+                rest[..end].ends_with(char::is_whitespace),
+            );
+            if first {
+                out.push(' ')
+            }
+            out.push_str(&text.join(" "));
+            if last {
+                out.push(' ')
+            }
+        }
+        rest = &rest[end..];
+    }
+    out
+}
+
+/// Split a graphviz `<g ...>` group (without nested groups) into
+/// the opening tag, title element, the remaining body, and what follows the closing tag.
+fn split_group(group: &str) -> Option<(&str, &str, &str, &str)> {
+    let (open, rest) = group.split_at(group.find('>')? + 1);
+    let title_end = rest.find("</title>")? + "</title>".len();
+    let (title, rest) = rest.split_at(title_end);
+    if !title.trim_start().starts_with("<title>") {
+        return None;
+    }
+    let close = rest.find("</g>")?;
+    if rest[..close].contains("<g ") {
+        return None;
+    }
+    Some((open, title, &rest[..close], &rest[close + "</g>".len()..]))
+}
+
+#[test]
+fn link_svg() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            pub TrafficLight {
+                Red -> Green;
+            }
+        })
+        .unwrap();
+    let svg = r#"<svg>
+<g id="graph0" class="graph">
+<title>TrafficLight</title>
+<g id="node1" class="node">
+<title>Red</title>
+<ellipse/>
+</g>
+<g id="edge1" class="edge">
+<title>Red&#45;&gt;Green</title>
+<path/>
+</g>
+</g>
+</svg>"#;
+    assert_eq!(
+        generator.link_svg(svg),
+        r#"<svg>
+<g id="graph0" class="graph">
+<title>TrafficLight</title>
+<g id="diagram.Red" class="node">
+<title>Red</title><a href="enum.State.html#variant.Red" xlink:href="enum.State.html#variant.Red">
+<ellipse/>
+</a></g>
+<g id="edge1" class="edge">
+<title>Red&#45;&gt;Green</title><a href="struct.Red.html#method.green" xlink:href="struct.Red.html#method.green">
+<path/>
+</a></g>
+</g>
+</svg>"#
+    );
+}
+
+#[test]
+fn link_variants_to_diagram() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            pub TrafficLight {
+                Red -> Green;
+            }
+        })
+        .unwrap();
+    let mut file = generator.codegen();
+    generator
+        .attach_svg(
+            &mut file,
+            r#"<svg><g id="node1" class="node"><title>Red</title></g></svg>"#,
+        )
+        .unwrap();
+    let code = file.into_token_stream().to_string();
+    assert!(code.contains(r#"id=\"diagram.Red\""#));
+    let link = "# [doc = \"\"] # [doc = \"See it in the [diagram](index.html#diagram.Red).\"]";
+    assert!(code.contains(&format!("{} Red }}", link)));
+    assert!(code.contains(&format!("{} Red (Red < 'a >)", link)));
+}
+
+#[test]
+fn minify() {
+    let svg = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN"
+ "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<!-- Generated by graphviz version 2.43.0 (0)
+ -->
+<!-- Title: TrafficLight Pages: 1 -->
+<svg width="62pt" height="116pt">
+<metadata><rdf:RDF/></metadata>
+<g id="node1" class="node">
+<title>Red</title>
+<text text-anchor="middle">Red   light</text>
+</g>
+</svg>
+"#;
+    assert_eq!(
+        minify_svg(svg),
+        r#"<svg width="62pt" height="116pt"><g id="node1" class="node"><title>Red</title><text text-anchor="middle">Red light</text></g></svg>"#
+    );
+}
+
+#[test]
+fn label_svg() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            pub TrafficLight {
+                Red -> Green -> Off;
+                Red -> Off;
+                #[fsmentry(terminal)]
+                Broken;
+            }
+        })
+        .unwrap();
+    let summary = "State machine TrafficLight with 4 states and 3 transitions. \
+                   The terminal state is Broken.";
+    assert_eq!(generator.accessible_summary(), summary);
+    assert_eq!(
+        generator.label_svg(r#"<svg width="62pt"><g/></svg>"#),
+        format!(
+            r#"<svg width="62pt" role="img" aria-label="{summary}"><title>{summary}</title><desc>Broken is terminal. Green goes to Off. Off has no transitions. Red goes to Green, Off.</desc><g/></svg>"#
+        )
+    );
+}
+
+#[test]
+fn text_diagram() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            pub TrafficLight {
+                Red -> RedAmber -> Green -> Amber -> Red;
+                Red -fail-> Broken;
+                Off;
+            }
+        })
+        .unwrap();
+    assert_eq!(
+        generator.text_diagram(),
+        "Amber -> Red -fail-> Broken\nGreen -> Amber\nRed -> RedAmber -> Green\nOff\n"
+    );
+}
@@ -0,0 +1,234 @@
+//! Helpers for testing tools which embed the DSL, by asserting on its errors,
+//! and for checking that committed generated code is up to date.
+//!
+//! Requires the `testing` feature, which enables span locations in `proc-macro2`.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use syn::{
+    parse::{ParseStream, Parser as _},
+    parse_quote,
+};
+
+use crate::FSMGenerator;
+
+/// An error from parsing a state machine, with the source it points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpannedError {
+    /// The first line of the message.
+    ///
+    /// Some messages go on to describe the surrounding graph.
+    pub message: String,
+    /// The source text the error points at.
+    pub snippet: String,
+    /// 1-indexed.
+    pub line: usize,
+    /// 0-indexed, in characters.
+    pub column: usize,
+}
+
+/// All the errors from parsing `source` as [`FSMGenerator::parse_dsl_many`] does,
+/// empty if it is valid.
+pub fn dsl_errors(source: &str) -> Vec<SpannedError> {
+    errors(source, FSMGenerator::parse_dsl_many)
+}
+
+/// All the errors from parsing `source` as [`FSMGenerator::parse_dot`] does,
+/// empty if it is valid.
+pub fn dot_errors(source: &str) -> Vec<SpannedError> {
+    errors(source, FSMGenerator::parse_dot)
+}
+
+/// Panic unless parsing `source` as [`dsl_errors`] does fails with `message`,
+/// pointing at `snippet`.
+#[track_caller]
+pub fn assert_dsl_error(source: &str, message: &str, snippet: &str) {
+    assert_error(dsl_errors(source), message, snippet)
+}
+
+/// Panic unless parsing `source` as [`dot_errors`] does fails with `message`,
+/// pointing at `snippet`.
+#[track_caller]
+pub fn assert_dot_error(source: &str, message: &str, snippet: &str) {
+    assert_error(dot_errors(source), message, snippet)
+}
+
+#[track_caller]
+fn assert_error(errors: Vec<SpannedError>, message: &str, snippet: &str) {
+    if !errors
+        .iter()
+        .any(|it| it.message == message && it.snippet == snippet)
+    {
+        panic!(
+            "expected an error `{}` at `{}`, but got {:#?}",
+            message, snippet, errors
+        )
+    }
+}
+
+fn errors<T>(
+    source: &str,
+    parser: impl FnOnce(ParseStream) -> syn::Result<T>,
+) -> Vec<SpannedError> {
+    let tokens = match source.parse::<TokenStream>() {
+        Ok(it) => it,
+        Err(e) => return vec![spanned(e.span(), e.to_string())],
+    };
+    match parser.parse2(tokens) {
+        Ok(_) => vec![],
+        Err(e) => e
+            .into_iter()
+            .map(|it| spanned(it.span(), it.to_string()))
+            .collect(),
+    }
+}
+
+/// The module generated by [`up_to_date_test`], which [`assert_up_to_date`] ignores.
+const TEST_MODULE: &str = "fsmentry_up_to_date";
+
+/// Panic if `generated`, the code the `fsmentry` CLI generated from `source`,
+/// differs from what `source` generates now.
+///
+/// Diagrams are ignored, since they depend on whether graphviz was available,
+/// as is the test from [`up_to_date_test`].
+#[track_caller]
+pub fn assert_up_to_date(source: &str, generated: &str) {
+    let generators = FSMGenerator::parse_dsl_many
+        .parse_str(source)
+        .unwrap_or_else(|e| panic!("couldn't parse the state machines: {}", e));
+    let mut expected = syn::File {
+        shebang: None,
+        attrs: vec![],
+        items: generators
+            .iter()
+            .flat_map(|it| it.codegen().items)
+            .chain(FSMGenerator::registry(&generators).map(Into::into))
+            .collect(),
+    };
+    let mut actual = syn::parse_file(generated)
+        .unwrap_or_else(|e| panic!("couldn't parse the generated code: {}", e));
+    actual
+        .items
+        .retain(|it| !matches!(it, syn::Item::Mod(it) if it.ident == TEST_MODULE));
+    without_diagrams(&mut expected.items);
+    without_diagrams(&mut actual.items);
+    // compared as the CLI prints them, which isn't how they are tokenized
+    if prettyplease::unparse(&expected) != prettyplease::unparse(&actual) {
+        panic!("the generated code is out of date, run the `fsmentry` CLI again")
+    }
+}
+
+/// A test calling [`assert_up_to_date`] with `source`,
+/// and the file at `path`, relative to the file the test is written in.
+///
+/// The `fsmentry` CLI appends this to its output with `--with-test`.
+pub fn up_to_date_test(source: &str, path: &str) -> syn::ItemMod {
+    let ident = Ident::new(TEST_MODULE, Span::call_site());
+    parse_quote! {
+        #[cfg(test)]
+        mod #ident {
+            #[test]
+            fn generated_code_is_up_to_date() {
+                ::fsmentry_core::testing::assert_up_to_date(#source, ::core::include_str!(#path))
+            }
+        }
+    }
+}
+
+/// Remove the documentation added by [`FSMGenerator::attach_svg`] and [`FSMGenerator::attach_text_diagram`].
+fn without_diagrams(items: &mut [syn::Item]) {
+    fn strip(attrs: &mut Vec<syn::Attribute>) {
+        let doc = |attr: &syn::Attribute| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(it),
+                        ..
+                    }),
+                ..
+            }) if path.is_ident("doc") => Some(it.value()),
+            _ => None,
+        };
+        attrs.retain(|it| {
+            !doc(it).is_some_and(|it| {
+                it.starts_with("<div>")
+                    || it.starts_with("```text")
+                    || it.starts_with("See it in the [diagram]")
+            })
+        });
+        // and the blank line separating them from other documentation
+        while attrs.last().and_then(doc).is_some_and(|it| it.is_empty()) {
+            attrs.pop();
+        }
+    }
+    for item in items {
+        match item {
+            syn::Item::Mod(syn::ItemMod { attrs, content, .. }) => {
+                strip(attrs);
+                if let Some((_, items)) = content {
+                    without_diagrams(items)
+                }
+            }
+            syn::Item::Enum(syn::ItemEnum { variants, .. }) => {
+                for variant in variants {
+                    strip(&mut variant.attrs)
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spanned(span: Span, message: String) -> SpannedError {
+    let start = span.start();
+    SpannedError {
+        message: message.lines().next().unwrap_or_default().to_owned(),
+        snippet: span.source_text().unwrap_or_default(),
+        line: start.line,
+        column: start.column,
+    }
+}
+
+#[test]
+fn testing() {
+    assert_eq!(dsl_errors("Machine { A -> B; }"), []);
+    assert_eq!(
+        dsl_errors("Machine {\n    A;\n    A;\n}"),
+        [SpannedError {
+            message: String::from("duplicate node definition"),
+            snippet: String::from("A"),
+            line: 3,
+            column: 4,
+        }]
+    );
+    assert_dsl_error(
+        "Machine { A -> B; A -> B; }",
+        "duplicate edge definition\n\nA ---> B <-- here\n\nto have several transitions between these states, name them, like `A -name-> B`",
+        "->",
+    );
+    assert_dot_error(
+        "digraph machine { a -> subgraph { b } }",
+        "subgraphs are not supported",
+        "subgraph { b }",
+    );
+}
+
+#[test]
+fn up_to_date() {
+    let source = "Machine { /// Docs.\n A -> B; }";
+    let generator = FSMGenerator::parse_dsl.parse_str(source).unwrap();
+    let mut file = generator.codegen();
+    generator
+        .attach_svg(
+            &mut file,
+            "<svg><g class=\"node\"><title>A</title></g></svg>",
+        )
+        .unwrap();
+    file.items
+        .push(up_to_date_test(source, "generated.rs").into());
+    let generated = prettyplease::unparse(&file);
+    assert_up_to_date(source, &generated);
+
+    let drifted = std::panic::catch_unwind(|| assert_up_to_date("Machine { A -> C; }", &generated));
+    assert!(drifted.is_err());
+}
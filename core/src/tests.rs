@@ -0,0 +1,1266 @@
+//! How definitions are parsed, and what is rejected.
+//!
+//! The behavior of the generated code is tested by compiling it, in `trybuild/`.
+
+use super::*;
+use syn::parse::Parser as _;
+
+fn parse(input: TokenStream) -> FSMGenerator {
+    FSMGenerator::parse_dsl.parse2(input).unwrap()
+}
+
+fn parse_err(input: TokenStream) -> String {
+    FSMGenerator::parse_dsl
+        .parse2(input)
+        .unwrap_err()
+        .to_string()
+}
+
+fn parse_many(input: TokenStream) -> Vec<FSMGenerator> {
+    FSMGenerator::parse_dsl_many.parse2(input).unwrap()
+}
+
+fn parse_many_err(input: TokenStream) -> String {
+    FSMGenerator::parse_dsl_many
+        .parse2(input)
+        .unwrap_err()
+        .to_string()
+}
+
+/// For what can't be observed by compiling the generated code, like its documentation.
+fn codegen(input: TokenStream) -> String {
+    parse(input).codegen().into_token_stream().to_string()
+}
+
+#[test]
+fn duplicate_edge_neighborhood() {
+    let error = parse_err(quote! {
+        Machine {
+            Fountain -> BeautifulBridge -> Tombstone;
+            Stream -> BeautifulBridge -> UnmarkedGrave;
+            BeautifulBridge -> Tombstone;
+        }
+    });
+    assert_eq!(
+        error,
+        "duplicate edge definition
+
+Fountain ---> BeautifulBridge ---> Tombstone <-- here
+Stream   --/                  \\--> UnmarkedGrave
+
+to have several transitions between these states, name them, like `BeautifulBridge -name-> Tombstone`"
+    );
+}
+
+#[test]
+fn chain_warning() {
+    let generator = parse(quote! {
+        Machine {
+            A -> B;
+            B -> C -> D;
+            D -> A;
+            /// Documented separately.
+            A -> C;
+        }
+    });
+    let messages = generator
+        .warnings()
+        .iter()
+        .map(|it| &*it.message)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "the previous statement ends at `B`, so this can be appended to it as a chain",
+            "the previous statement ends at `D`, so this can be appended to it as a chain",
+        ]
+    );
+}
+
+#[test]
+fn extends() {
+    let machines = parse_many(quote! {
+        Base {
+            A -> B;
+        }
+        Derived {
+            extends Base;
+            B: String;
+            B -> C;
+        }
+    });
+    let derived = &machines[1];
+    assert_eq!(derived.nodes.len(), 3);
+    assert!(derived.nodes[&NodeId::from(quote::format_ident!("B"))]
+        .ty
+        .is_some());
+    assert_eq!(derived.edges.len(), 2);
+
+    // docs aren't merged, so a redeclaration without them loses them
+    let machines = parse_many(quote! {
+        Base {
+            /// Waiting.
+            A;
+            /// Done.
+            B;
+            A -> B;
+        }
+        Derived {
+            extends Base;
+            A: u8;
+            /// Done.
+            B: u8;
+        }
+    });
+    let messages = machines[1]
+        .warnings()
+        .iter()
+        .map(|it| &*it.message)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        ["redeclaring `A` replaces its inherited documentation"]
+    );
+
+    let e = parse_many_err(quote! {
+        Derived {
+            extends Base;
+        }
+        Base {
+            A -> B;
+        }
+    });
+    assert_eq!(e, "no machine named `Base` is defined before this one");
+
+    let machines = parse_many(quote! {
+        Base {
+            A -> B -> C;
+        }
+        Derived {
+            extends Base;
+            remove B -> C;
+            A -on(log)-> B;
+        }
+    });
+    let derived = &machines[1];
+    assert_eq!(derived.edges.len(), 1);
+    let (_, edge) = derived.edges.first_key_value().unwrap();
+    assert_eq!(edge.callback.as_ref().unwrap(), "log");
+
+    let e = parse_many_err(quote! {
+        Base {
+            A -> B;
+        }
+        Derived {
+            extends Base;
+            remove B -> A;
+        }
+    });
+    assert_eq!(e, "there is no inherited edge `B -> A` to remove");
+
+    // unnamed edges are found by their states, however the base names its methods
+    let machines = parse_many(quote! {
+        #[fsmentry(method_case = camel)]
+        Base {
+            A -> NextState;
+            A -retry-> A;
+        }
+        #[fsmentry(method_case = preserve)]
+        Derived {
+            extends Base;
+            remove A -> NextState;
+        }
+    });
+    let derived = &machines[1];
+    assert_eq!(derived.edges.len(), 1);
+    assert!(derived.edges.contains_key(&(
+        NodeId::from(quote::format_ident!("A")),
+        NodeId::from(quote::format_ident!("A")),
+        quote::format_ident!("retry"),
+    )));
+
+    let e = parse_many_err(quote! {
+        Base {
+            A -> B;
+        }
+        Derived {
+            extends Base;
+            A -> B;
+            A -> B;
+        }
+    });
+    assert!(e.starts_with("duplicate edge definition"));
+}
+
+#[test]
+fn cfg_edges() {
+    let generator = parse(quote! {
+        Machine {
+            #[cfg(feature = "admin")]
+            A -> B;
+        }
+    });
+    assert!(generator
+        .mermaid()
+        .ends_with("\n    A\n    B\n    A --> B: cfg(feature = \"admin\")\n"));
+    let e = parse_err(quote! {
+        Machine {
+            #[fsmentry(terminal)]
+            A -> B;
+        }
+    });
+    assert!(e.starts_with("unknown argument `terminal`"));
+}
+
+#[test]
+fn named_edges() {
+    let generator = parse(quote! {
+        Machine {
+            A -> B;
+            A -skip-> B;
+        }
+    });
+    assert_eq!(generator.edges.len(), 2);
+    assert!(generator
+        .mermaid()
+        .ends_with("\n    A\n    B\n    A --> B\n    A --> B: skip\n"));
+    assert!(generator.dot().strict.is_none());
+
+    let e = parse_err(quote! {
+        Machine {
+            A -skip-> B;
+            A -skip-> C;
+        }
+    });
+    assert_eq!(e, "`A` already has a transition method `skip`, to `B`");
+}
+
+#[test]
+fn record_states() {
+    let generator = parse(quote! {
+        Machine {
+            A { ttl: u64, reason: String }
+            A -> B;
+        }
+    });
+    assert!(matches!(
+        generator.nodes[&NodeId::from(ident("A"))].ty,
+        Some(Data::Record(ref fields)) if fields.len() == 2
+    ));
+
+    for (input, expected) in [
+        (quote!(Machine { A {} }), "expected at least one field"),
+        (
+            quote!(Machine { A { hooks: u8 } }),
+            "`hooks` is reserved, and may not be used as a field name",
+        ),
+        (
+            quote!(Machine { A { b: u8 } A -> B; }),
+            "the accessors for `b` clash with the transition method `b`, to `B`",
+        ),
+        (
+            quote!(Machine { B { n: u8 } A -go(n: u8)-> B; }),
+            "`n` is already a field of `B`, so may not be used as an argument name",
+        ),
+    ] {
+        assert_eq!(parse_err(input), expected)
+    }
+}
+
+#[test]
+fn state_hooks() {
+    for (input, expected) in [
+        (
+            quote!(Machine { #[cfg(any())] A; }),
+            "only `#[doc]` and `#[fsmentry]` attributes are supported on states",
+        ),
+        (
+            quote!(Machine { #[fsmentry(on_enter = log)] A; B -on(log)-> A; }),
+            "`log` is already called by a transition, so may not be called on entering or leaving a state",
+        ),
+    ] {
+        assert_eq!(parse_err(input), expected)
+    }
+}
+
+#[test]
+fn events() {
+    let e = parse_err(quote! {
+        #[fsmentry(events = true)]
+        Machine {
+            B: u8;
+            A -go-> B -go-> C;
+        }
+    });
+    assert_eq!(
+        e,
+        "transitions named `go` must all take the same arguments to be an event"
+    );
+}
+
+#[test]
+fn entry_docs() {
+    let code = codegen(quote! {
+        Machine {
+            /// The summary.
+            ///
+            /// The details.
+            A { n: u8, s: String }
+            A -> B;
+        }
+    });
+    let entry = &code[code.find("pub enum Entry").unwrap()..];
+    assert!(entry.contains("# [doc = r\" The summary.\"] # [doc = \"\"] # [doc = \"Holds `n: u8` and `s: String`. See [`State::A`] for more.\"]"));
+    assert!(!entry[..entry.find("A (A").unwrap()].contains("The details."));
+}
+
+#[test]
+fn reachability_docs() {
+    let code = codegen(quote! {
+        Machine {
+            A -> B -> C;
+        }
+    });
+    // one attribute for both lists, on each of the state and entry enums
+    let docs = r#"# [doc = "This node is reachable from the following states:\n- [`State::A`]\n\nThis node can reach the following states:\n- [`State::C`]"]"#;
+    assert_eq!(code.matches(docs).count(), 2);
+    assert_eq!(
+        code.matches("reachable from the following states").count(),
+        4
+    );
+}
+
+#[test]
+fn doc_templates() {
+    let code = codegen(quote! {
+        #[fsmentry(doc_templates(
+            handle = "Verlässt `{state}` mit:",
+            reachable_from = "Erreichbar aus:",
+            ensures = "Garantiert `{condition}`.",
+            requires = "Setzt `{condition}` voraus.",
+        ))]
+        Machine {
+            A -> B;
+            B -ensures(true)-> C;
+            C -requires(false)-> A;
+        }
+    });
+    assert!(code.contains("# [doc = \"Verlässt `A` mit:\"]"));
+    assert!(code.contains("Erreichbar aus:\\n- [`State::A`]"));
+    assert!(code.contains("# [doc = \"Garantiert `true`.\"]"));
+    assert!(code.contains("# [doc = \"Setzt `false` voraus.\"]"));
+    assert!(
+        code.contains("# [doc = \"This node can reach the following states:\\n- [`State::B`]\"]")
+    );
+
+    let e = parse_err(quote! {
+        #[fsmentry(doc_templates(can_reach = "From {state} to {target}:"))]
+        Machine {
+            A -> B;
+        }
+    });
+    assert_eq!(e, "unknown placeholder `{target}`, expected `{state}`");
+}
+
+#[test]
+fn wildcard_edges() {
+    let generator = parse(quote! {
+        Machine {
+            A -> B -> C;
+            /// Shared docs
+            _ -> Fatal;
+            C -"already there"-> Fatal;
+            B -fail-> Fatal;
+        }
+    });
+    let fatal = NodeId::from(ident("Fatal"));
+    let incoming = generator
+        .edges
+        .iter()
+        .filter(|((_, to, _), _)| *to == fatal)
+        .map(|((from, ..), data)| (from.inner.to_string(), docs(&data.docs)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        incoming,
+        [
+            (String::from("A"), vec![String::from(" Shared docs")]),
+            (String::from("B"), vec![]),
+            (String::from("C"), vec![String::from("already there")]),
+        ]
+    );
+
+    let e = parse_err(quote! {
+        Machine {
+            A -fatal-> B;
+            * -> Fatal;
+        }
+    });
+    assert_eq!(e, "`A` already has a transition method `fatal`, to `B`");
+
+    fn docs(docs: &[OuterDocString]) -> Vec<String> {
+        docs.iter().map(|it| it.doc.value()).collect()
+    }
+}
+
+#[test]
+fn method_case() {
+    for (case, method) in [
+        ("snake", "red_amber"),
+        ("camel", "redAmber"),
+        ("preserve", "RedAmber"),
+    ] {
+        let case = ident(case);
+        let generator = parse(quote! {
+            #[fsmentry(method_case = #case)]
+            Machine {
+                Red -> RedAmber;
+            }
+        });
+        let (_, _, name) = generator.edges.keys().next().unwrap();
+        assert_eq!(name, method);
+        assert_eq!(
+            generator.mermaid().lines().last().unwrap(),
+            "    Red --> RedAmber"
+        );
+    }
+}
+
+#[test]
+fn method_format() {
+    let generator = parse(quote! {
+        #[fsmentry(method_format = "to_{snake}")]
+        Machine {
+            Red -> RedAmber;
+            Red -stop-> Off;
+        }
+    });
+    let names = generator
+        .edges
+        .keys()
+        .map(|(_, _, name)| name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["stop", "to_red_amber"]);
+
+    for (options, message) in [
+        (
+            quote!(method_format = "to_state"),
+            "expected an identifier containing `{snake}`, `{camel}` or `{preserve}`",
+        ),
+        (
+            quote!(method_format = "to-{snake}"),
+            "expected an identifier containing `{snake}`, `{camel}` or `{preserve}`",
+        ),
+        (
+            quote!(method_case = camel, method_format = "to{preserve}"),
+            "`method_format` and `method_case` may not both be given",
+        ),
+    ] {
+        let e = parse_err(quote! {
+            #[fsmentry(#options)]
+            Machine {
+                Red -> RedAmber;
+            }
+        });
+        assert_eq!(e, message);
+    }
+}
+
+#[test]
+fn on_mismatch() {
+    for (option, expected) in [
+        (quote!(panic), "found => { let found = found . kind () ;"),
+        (
+            quote!(unchecked),
+            "found => { let _ = found ; unsafe { :: core :: hint :: unreachable_unchecked () } }",
+        ),
+        (
+            quote!(debug_unchecked),
+            "found => match :: core :: cfg ! (debug_assertions) {",
+        ),
+        (
+            quote!({ ::std::process::abort() }),
+            "found => { let _ = found ; :: std :: process :: abort () }",
+        ),
+    ] {
+        let code = codegen(quote! {
+            #[fsmentry(on_mismatch = #option)]
+            Machine {
+                A: u8;
+                A -> B;
+            }
+        });
+        assert!(code.contains(expected), "{}", code);
+    }
+    // transitions may override the machine
+    let code = codegen(quote! {
+        Machine {
+            A: u8;
+            B: u8;
+            #[fsmentry(on_mismatch = unchecked)]
+            A -> C;
+            B -> C;
+        }
+    });
+    assert_eq!(code.matches("unreachable_unchecked").count(), 1);
+    // as do the checks of pre- and postconditions
+    let code = codegen(quote! {
+        #[fsmentry(on_mismatch = unchecked, events = true)]
+        Machine {
+            A: u8;
+            B: u8;
+            A -requires(*data > 0)-> B -ensures(*data > 0)-> A;
+        }
+    });
+    assert!(!code.contains("unreachable !"));
+    let e = parse_err(quote! {
+        #[fsmentry(on_mismatch = unchecked, expose_handle_field = pub)]
+        Machine {
+            A -> B;
+        }
+    });
+    assert_eq!(
+        e,
+        "`expose_handle_field` lets safe code mismatch a handle, so `on_mismatch` must not be unchecked"
+    );
+}
+
+#[test]
+fn fan_in() {
+    let generator = parse(quote! {
+        Machine {
+            /// Shared docs
+            Timeout & Error -on(log)-> Retry -> Running;
+        }
+    });
+    let edges = generator
+        .edges
+        .iter()
+        .map(|((from, to, _), data)| {
+            (
+                format!("{} -> {}", from.inner, to.inner),
+                data.callback.as_ref().map(|it| it.to_string()),
+                data.docs.len(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edges,
+        [
+            (String::from("Error -> Retry"), Some(String::from("log")), 1),
+            (String::from("Retry -> Running"), None, 1),
+            (
+                String::from("Timeout -> Retry"),
+                Some(String::from("log")),
+                1
+            ),
+        ]
+    );
+    assert!(generator.warnings().is_empty());
+}
+
+#[test]
+fn name_collisions() {
+    let generator = parse(quote! {
+        #[fsmentry(handle_prefix = "From", handle_suffix = "Handle")]
+        Machine {
+            Entry -> Visitor;
+        }
+    });
+    assert_eq!(
+        generator.transition_ty(&NodeId::from(ident("Entry"))),
+        "FromEntryHandle"
+    );
+
+    for (input, expected) in [
+        (
+            quote!(Machine { Entry -> Visitor; }),
+            "the transition type for `Entry` would clash with the generated `Entry`, \
+            consider `#[fsmentry(handle_suffix = \"Handle\")]`",
+        ),
+        (
+            quote!(#[fsmentry(events = true)] Machine { Event -> A; }),
+            "the transition type for `Event` would clash with the generated `Event`, \
+            consider `#[fsmentry(handle_suffix = \"Handle\")]`",
+        ),
+        (
+            quote!(State { A -> B; }),
+            "the machine would clash with the generated `State`",
+        ),
+        (
+            quote!(Machine { red_light -> RedLight; }),
+            "`RedLight` and `red_light` would both be called `RedLight`",
+        ),
+        (
+            quote!(#[fsmentry(handle_suffix = "-")] Machine { A; }),
+            "expected letters, digits and underscores, to be part of a type name",
+        ),
+    ] {
+        assert_eq!(parse_err(input), expected)
+    }
+}
+
+#[test]
+fn context() {
+    let machines = parse_many(quote! {
+        Base { context: Vec<u8>; A -> B; }
+        Extended { extends Base; B -> A; }
+    });
+    assert_eq!(
+        machines[1].context.to_token_stream().to_string(),
+        "Vec < u8 >"
+    );
+
+    for (input, expected) in [
+        (
+            quote!(Machine { context: u8; context: u16; A -> B; }),
+            "a machine may only have one context",
+        ),
+        (
+            quote!(Machine { context: u8; A -context_mut-> B; }),
+            "`context_mut` is an accessor for the machine's context, so may not name the transition to `B`",
+        ),
+        (
+            quote!(Machine { context: u8; A -go(context: u8)-> B; }),
+            "`context` is reserved for the machine's context, and may not be used as a field or argument name",
+        ),
+    ] {
+        assert_eq!(parse_err(input), expected)
+    }
+}
+
+#[test]
+fn reexport_mods() {
+    for input in [
+        quote!(#[fsmentry(state_mod = pub machine)] Machine { A; }),
+        quote!(#[fsmentry(state_mod = pub api, entry_mod = api)] Machine { A; }),
+    ] {
+        assert!(parse_err(input).ends_with("would clash with another generated module"))
+    }
+}
+
+#[test]
+fn module() {
+    let generator = parse(quote! {
+        #[fsmentry(module = lights, state_mod = pub state)]
+        pub TrafficLight {
+            Red -> Green;
+        }
+    });
+    let items = generator.codegen().items;
+    let syn::Item::Mod(module) = &items[0] else {
+        panic!("expected a module")
+    };
+    assert_eq!(module.ident, "lights");
+    assert_eq!(
+        items[1].to_token_stream().to_string(),
+        "pub mod state { pub use super :: lights :: { TrafficLight , State , StateKind } ; }"
+    );
+
+    let e = parse_err(quote! {
+        #[fsmentry(module = state, state_mod = pub state)]
+        Machine {
+            A -> B;
+        }
+    });
+    assert_eq!(
+        e,
+        "the module `state` would clash with another generated module"
+    );
+}
+
+#[test]
+fn handle_vis() {
+    let generator = parse(quote! {
+        #[fsmentry(handle_vis = pub(crate), entry_mod = pub entry)]
+        pub Machine {
+            A -> B;
+        }
+    });
+    let items = generator.codegen().items;
+    let code = items[0].to_token_stream().to_string();
+    assert!(code.starts_with("# [allow (private_interfaces)] pub mod machine"));
+    assert!(code.contains("pub (crate) struct A < 'a >"));
+    assert_eq!(
+        items[1].to_token_stream().to_string(),
+        "pub mod entry { pub use super :: machine :: { Entry , Visitor } ; }"
+    );
+}
+
+#[test]
+fn initial_state() {
+    // `State::new` takes the data instead
+    let code = codegen(quote!(#[fsmentry(initial = A)] Machine { A: u8; A -> B; }));
+    assert!(!code.contains("Default for State"));
+
+    let generator = parse(quote!(#[fsmentry(initial = A)] Machine { A -> B; C -> B; }));
+    let messages = generator
+        .warnings()
+        .iter()
+        .map(|it| &*it.message)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        ["`C` can't be reached from the initial state `A`\n\nC ---> B"]
+    );
+
+    let e = parse_err(quote!(#[fsmentry(initial = C)] Machine { A -> B; }));
+    assert_eq!(e, "no state named `C`");
+}
+
+#[test]
+fn terminal_states() {
+    let generator = parse(quote! {
+        Machine {
+            #[fsmentry(terminal)]
+            Done;
+            #[fsmentry(terminal = false)]
+            A;
+            A -> B -> Done;
+            _ -> Failed;
+        }
+    });
+    // no edge out of `Done`
+    assert_eq!(generator.edges.len(), 4);
+
+    let e = parse_err(quote!(Machine { #[fsmentry(terminal)] Done; Done -> Again; }));
+    assert_eq!(
+        e,
+        "`Done` is terminal, so may not have a transition to `Again`"
+    );
+}
+
+#[test]
+fn size_limits() {
+    let generator = parse(quote! {
+        #[fsmentry(max_states = 2)]
+        Machine {
+            A -> B -> C;
+        }
+    });
+    let [Warning { message, .. }] = generator.warnings() else {
+        panic!("{:?}", generator.warnings())
+    };
+    assert!(message
+        .starts_with("`Machine` has 3 states, which is more than the 2 allowed by `max_states`"));
+    let generator = parse(quote! {
+        #[fsmentry(max_edges = 1)]
+        Machine {
+            A -> B -> C;
+        }
+    });
+    let [Warning { message, .. }] = generator.warnings() else {
+        panic!("{:?}", generator.warnings())
+    };
+    assert!(message.starts_with(
+        "`Machine` has 2 transitions, which is more than the 1 allowed by `max_edges`"
+    ));
+}
+
+#[test]
+fn type_aliases() {
+    for (input, expected) in [
+        (
+            quote!(Machine { type Payload = u8; type Payload = u16; A -> B; }),
+            "duplicate type alias",
+        ),
+        (
+            quote!(Machine { type Entry = u8; A -> B; }),
+            "the alias `Entry` would clash with the generated `Entry`",
+        ),
+        (
+            quote!(Machine { type A = u8; A -> B; }),
+            "the alias `A` would clash with the generated `A`",
+        ),
+        (
+            quote!(Machine { #[derive(Debug)] type Payload = u8; A -> B; }),
+            "only `#[doc]` attributes are supported on type aliases",
+        ),
+    ] {
+        assert_eq!(parse_err(input), expected)
+    }
+}
+
+#[test]
+fn bidirectional_edges() {
+    let [base, extended] = parse_many(quote! {
+        Base {
+            /// Either way.
+            A <-> B <-open/close-> C;
+        }
+        Extended { extends Base; remove B <-open/close-> C; }
+    })
+    .try_into()
+    .unwrap();
+    let edges = |it: &FSMGenerator| {
+        it.edges
+            .iter()
+            .map(|((from, to, method), data)| {
+                assert_eq!(data.docs.len(), 1);
+                format!("{} -{}-> {}", from.inner, method, to.inner)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        edges(&base),
+        ["A -b-> B", "B -a-> A", "B -open-> C", "C -close-> B"]
+    );
+    assert_eq!(edges(&extended), ["A -b-> B", "B -a-> A"]);
+
+    let e = parse_err(quote!(Machine { A; _ <-> B; }));
+    assert_eq!(
+        e,
+        "`<->` may not be used with `_`, since there is no one state to return to"
+    );
+}
+
+/// Generated items must either be part of the api, or internal.
+#[test]
+fn hygiene() {
+    let [generator] = parse_many(quote! {
+        #[fsmentry(
+            events = true,
+            entry_cell = true,
+            pool = true,
+            atomic_kind = super::KIND,
+            initial = A,
+            state_mod = pub state,
+            entry_mod = pub entry,
+            conversions = [AsRef, DerefMut],
+            expose_handle_field = pub(crate),
+        )]
+        pub Machine {
+            context: u8;
+            type Payload = Vec<u8>;
+            #[fsmentry(on_enter = entered)]
+            A: Payload;
+            B { count: u8 }
+            #[fsmentry(terminal)]
+            D;
+            A -on(self.log)-> B -go(reason: u8)-> C <-> A;
+            _ -> D;
+        }
+    })
+    .try_into()
+    .unwrap();
+    let api = generator
+        .generated_names()
+        .into_iter()
+        .chain(generator.nodes.keys().map(|it| generator.transition_ty(it)))
+        .chain(generator.aliases.iter().map(|it| it.ident.clone()))
+        .chain([generator.ident.snake_case(), ident("state"), ident("entry")])
+        .map(|it| it.to_string())
+        .collect::<BTreeSet<_>>();
+    let internal = |name: &Ident, attrs: &[syn::Attribute]| {
+        assert!(
+            name.to_string().starts_with("__fsmentry_"),
+            "`{}` is neither part of the api, nor prefixed with `__fsmentry_`",
+            name
+        );
+        assert!(
+            attrs.iter().any(|it| it.meta == parse_quote!(doc(hidden))),
+            "`{}` is internal, so should be `#[doc(hidden)]`",
+            name
+        );
+    };
+    let mut items = generator.codegen().items;
+    while let Some(item) = items.pop() {
+        let (name, attrs) = match item {
+            syn::Item::Mod(syn::ItemMod {
+                ident,
+                attrs,
+                content,
+                ..
+            }) => {
+                items.extend(content.into_iter().flat_map(|(_, it)| it));
+                (ident, attrs)
+            }
+            syn::Item::Impl(syn::ItemImpl {
+                trait_: None,
+                items: impl_items,
+                ..
+            }) => {
+                for item in impl_items {
+                    match item {
+                        syn::ImplItem::Const(syn::ImplItemConst {
+                            vis: syn::Visibility::Inherited,
+                            ident,
+                            attrs,
+                            ..
+                        })
+                        | syn::ImplItem::Fn(syn::ImplItemFn {
+                            vis: syn::Visibility::Inherited,
+                            sig: syn::Signature { ident, .. },
+                            attrs,
+                            ..
+                        }) => internal(&ident, &attrs),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            syn::Item::Struct(syn::ItemStruct { ident, attrs, .. })
+            | syn::Item::Enum(syn::ItemEnum { ident, attrs, .. })
+            | syn::Item::Trait(syn::ItemTrait { ident, attrs, .. })
+            | syn::Item::Type(syn::ItemType { ident, attrs, .. })
+            | syn::Item::Const(syn::ItemConst { ident, attrs, .. })
+            | syn::Item::Static(syn::ItemStatic { ident, attrs, .. })
+            | syn::Item::ExternCrate(syn::ItemExternCrate {
+                rename: Some((_, ident)),
+                attrs,
+                ..
+            })
+            | syn::Item::Fn(syn::ItemFn {
+                sig: syn::Signature { ident, .. },
+                attrs,
+                ..
+            }) => (ident, attrs),
+            _ => continue,
+        };
+        if name != "_" && !api.contains(&name.to_string()) {
+            internal(&name, &attrs)
+        }
+    }
+}
+
+#[test]
+fn raw_identifiers() {
+    let generator = parse(quote! {
+        Loop {
+            r#try { r#type: u8 }
+            r#try -> r#final -> Super;
+            _ -> r#try;
+        }
+    });
+    assert!(!generator.mermaid().contains("r#"));
+}
+
+#[test]
+fn shared_attributes() {
+    let [traffic, pedestrian] = parse_many(quote! {
+        #![derive(Debug)]
+        #![fsmentry(events = true)]
+        /// Shared.
+        Traffic { Red -> Green -> Red; }
+        #[derive(Clone)]
+        Pedestrian { Wait -> Walk -> Wait; }
+    })
+    .try_into()
+    .unwrap();
+    assert!(traffic.events && pedestrian.events);
+
+    let e = parse_many_err(quote! {
+        #![fsmentry(fmt = false)]
+        #[fsmentry(fmt = true)]
+        Traffic { Red -> Green -> Red; }
+    });
+    assert_eq!(e, "duplicate argument `fmt`");
+}
+
+#[test]
+fn layout_hints() {
+    let generator = parse(quote! {
+        #[fsmentry(rankdir = LR)]
+        Machine {
+            A -> B -> C;
+            same_rank(B, C);
+            rank(A = source);
+        }
+    });
+    assert_eq!(
+        generator.dot().to_token_stream().to_string(),
+        "strict digraph Machine { rankdir = LR ; A ; B ; C ; A -> B ; B -> C ; \
+         { rank = same ; B ; C ; } { rank = source ; A ; } }"
+    );
+    assert!(generator.mermaid().contains("\n    direction LR\n"));
+
+    let e = parse_err(quote!(Machine { A; same_rank(A, B); }));
+    assert_eq!(e, "no state named `B`");
+    let e = parse_err(quote!(Machine { A; rank(A = same); }));
+    assert_eq!(e, "expected one of `min`, `max`, `source` or `sink`");
+}
+
+#[test]
+fn edge_arguments() {
+    let e = parse_err(quote! {
+        Machine {
+            A -close(reason: String)-> B;
+            B -on(close)-> C;
+        }
+    });
+    assert_eq!(
+        e,
+        "transitions calling `close` must all take the same arguments, and leave and enter states with the same data"
+    );
+
+    let e = parse_err(quote! {
+        Machine {
+            A: u8;
+            A -on(log)-> B;
+            C -on(log)-> B;
+        }
+    });
+    assert_eq!(
+        e,
+        "transitions calling `log` must all take the same arguments, and leave and enter states with the same data"
+    );
+
+    let e = parse_err(quote! {
+        Machine {
+            A -close(next: u8)-> B;
+        }
+    });
+    assert_eq!(
+        e,
+        "`next` is reserved, and may not be used as an argument name"
+    );
+}
+
+#[test]
+fn entry_fn() {
+    let e = parse_err(quote! {
+        #[fsmentry(entry_fn = "state")]
+        Machine {
+            A -> B;
+        }
+    });
+    assert_eq!(e, "`state` is already a method on the state machine");
+    let e = parse_err(quote! {
+        #[fsmentry(entry_fn = "context")]
+        Machine {
+            context: u8;
+            A -> B;
+        }
+    });
+    assert_eq!(e, "`context` is already a method on the state machine");
+    let e = parse_err(quote! {
+        #[fsmentry(entry_fn = "get", pool = true)]
+        Machine {
+            A -> B;
+        }
+    });
+    assert_eq!(e, "`get` is already a method on the pool");
+    // only a clash when the pool is generated
+    parse(quote! {
+        #[fsmentry(entry_fn = "get")]
+        Machine {
+            A -> B;
+        }
+    });
+}
+
+#[test]
+fn owned() {
+    let e = parse_err(quote! {
+        #[fsmentry(owned = true)]
+        Machine {
+            context: u8;
+            A -> B;
+        }
+    });
+    assert_eq!(e, "`owned` transitions can't reach the machine's context");
+}
+
+#[test]
+fn serde() {
+    for (options, message) in [
+        (
+            quote!(serde_tag = "type"),
+            "`serde_tag` needs `serde = true`",
+        ),
+        (
+            quote!(serde = untagged, serde_tag = "type"),
+            "`serde_tag` needs `serde = true`",
+        ),
+        (
+            quote!(serde = true, serde_content = "data"),
+            "`serde_content` needs `serde_tag`, since states are only tagged adjacently with both",
+        ),
+    ] {
+        let e = parse_err(quote! {
+            #[fsmentry(#options)]
+            Machine {
+                A -> B;
+            }
+        });
+        assert_eq!(e, message);
+    }
+    // `#[serde]` on a state is an error without the option
+    assert!(FSMGenerator::parse_dsl
+        .parse2(quote! {
+            Machine {
+                #[serde(skip)]
+                A;
+            }
+        })
+        .is_err());
+}
+
+#[test]
+fn state_accessors() {
+    let e = parse_err(quote! {
+        #[fsmentry(state_accessors = true)]
+        Machine {
+            #[fsmentry(terminal)]
+            Terminal;
+            A -> Terminal;
+        }
+    });
+    assert_eq!(
+        e,
+        "the accessors for `Terminal` would clash with `State::is_terminal`"
+    );
+}
+
+#[test]
+fn parse_dsl_lenient() {
+    let (machines, errors) = FSMGenerator::parse_dsl_lenient
+        .parse2(quote! {
+            Broken {
+                A -> B;
+                A => B;
+                B -> C;
+                C: ;
+                C -> A;
+            }
+            pub Invalid {
+                A;
+                A;
+            }
+            + Garbage { A; }
+            Fine {
+                extends Broken;
+            }
+        })
+        .unwrap();
+    assert_eq!(errors.len(), 4);
+    assert_eq!(machines.len(), 2);
+    assert_eq!(machines[0].edges.len(), 3);
+    assert_eq!(machines[1].edges.len(), 3);
+}
+
+#[test]
+fn defmt() {
+    let code = codegen(quote! {
+        #[fsmentry(defmt = kind)]
+        Machine {
+            A -> B;
+            B: String;
+        }
+    });
+    assert!(code.contains(":: defmt :: Format :: format (& self . kind () , f)"));
+
+    let e = parse_err(quote! {
+        #[fsmentry(defmt = everything)]
+        Machine {
+            A -> B;
+        }
+    });
+    assert_eq!(e, "expected one of `true`, `false` or `kind`");
+}
+
+#[test]
+fn dot_names() {
+    let generator = FSMGenerator::parse_dot
+        .parse2(quote! {
+            digraph Machine {
+                "waiting-for-ack" -> 404 -> idle;
+            }
+        })
+        .unwrap();
+    let names = generator
+        .nodes
+        .iter()
+        .map(|(node, data)| (node.inner.to_string(), data.label.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            (String::from("State404"), Some(String::from("404"))),
+            (String::from("idle"), None),
+            (
+                String::from("waiting_for_ack"),
+                Some(String::from("waiting-for-ack"))
+            ),
+        ]
+    );
+    assert!(generator
+        .mermaid()
+        .contains("\n    state \"waiting-for-ack\" as waiting_for_ack\n"));
+    assert!(generator
+        .dot()
+        .into_token_stream()
+        .to_string()
+        .contains("waiting_for_ack [label = \"waiting-for-ack\"]"));
+
+    let e = FSMGenerator::parse_dot
+        .parse2(quote! {
+            digraph Machine {
+                a_b -> "a-b";
+            }
+        })
+        .unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "`a_b` and `\"a-b\"` would both be named `a_b`"
+    );
+
+    // alphanumeric, but not allowed in identifiers
+    let generator = FSMGenerator::parse_dot
+        .parse2(quote! {
+            digraph Machine {
+                "a²" -> "①";
+            }
+        })
+        .unwrap();
+    let names = generator
+        .nodes
+        .iter()
+        .map(|(node, data)| (node.inner.to_string(), data.label.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            (String::from("State_"), Some(String::from("①"))),
+            (String::from("a_"), Some(String::from("a²"))),
+        ]
+    );
+    assert_eq!(
+        ident_at("a²", Span::call_site()).unwrap_err().to_string(),
+        "`a²` can't be used as a name in rust"
+    );
+}
+
+#[test]
+fn dot_downgrades() {
+    let input = quote! {
+        digraph Machine {
+            rankdir = LR;
+            a [label = "A"];
+            a -> b -> a -> b:n;
+        }
+    };
+    let generator = FSMGenerator::parse_dot.parse2(input.clone()).unwrap();
+    let messages = generator
+        .warnings()
+        .iter()
+        .map(|it| &*it.message)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "attributes are not supported, so this is ignored",
+            "attributes are not supported, so this is ignored",
+            "ports are not supported, so this is ignored",
+            "repeated edges are not supported, so this is ignored",
+        ]
+    );
+    assert_eq!(generator.edges.len(), 2);
+
+    let e = FSMGenerator::parse_dot_strict.parse2(input).unwrap_err();
+    assert_eq!(e.to_string(), "attributes are not supported");
+
+    let generator = FSMGenerator::parse_dot
+        .parse2(quote!(strict digraph Machine { a -> b; a -> b; }))
+        .unwrap();
+    assert!(generator.warnings().is_empty());
+}
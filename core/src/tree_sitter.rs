@@ -0,0 +1,144 @@
+use crate::FSMGenerator;
+
+impl FSMGenerator {
+    /// A [tree-sitter](https://tree-sitter.github.io/) `grammar.js` for the DSL,
+    /// so editors can highlight standalone files of state machines.
+    ///
+    /// See [`Self::parse_dsl`] for the language itself.
+    pub fn tree_sitter_grammar() -> &'static str {
+        include_str!("grammar.js")
+    }
+}
+
+/// Each rule in the grammar should be exercised by an example the parser accepts,
+/// so new syntax in the parser is noticed here.
+#[test]
+fn tree_sitter_grammar() {
+    use syn::parse::Parser as _;
+    let grammar = FSMGenerator::tree_sitter_grammar();
+    for (rule, example) in [
+        ("machine", "#[derive(Debug)] pub(crate) Machine { A; }"),
+        (
+            "inner_attribute",
+            "//! docs\n#![derive(Debug)] Machine { A; }",
+        ),
+        ("extends", "Base { A; } Machine { extends Base; }"),
+        ("context", "Machine { context: Vec<u8>; A -> B; }"),
+        (
+            "type_alias",
+            "Machine { /// docs\n type Payload = Vec<u8>; A: Payload; }",
+        ),
+        (
+            "remove",
+            "Base { A -> B; } Machine { extends Base; remove A -> B; }",
+        ),
+        (
+            "node",
+            "Machine { /// docs\n A: Vec<(u8, [&'static str; 2])>; }",
+        ),
+        (
+            "fields",
+            "Machine { A { ttl: u64, reason: String } A -> B; }",
+        ),
+        ("edges", "Machine { #[cfg(all())] A & D -> B --> C; }"),
+        ("wildcard_edge", "Machine { A -> B; _ -> C; * -fail-> D; }"),
+        (
+            "documented_arrow",
+            r#"Machine { A -"docs"-> B --"docs"--> C; }"#,
+        ),
+        (
+            "callback_arrow",
+            "Machine { A -on(self.log)-> B -on(log)-> C; }",
+        ),
+        (
+            "ensures_arrow",
+            "Machine { A -ensures(data.len() > 0)-> B; B: Vec<u8>; }",
+        ),
+        (
+            "requires_arrow",
+            "Machine { A -requires(data.len() > 0)-> B; A: Vec<u8>; }",
+        ),
+        ("named_arrow", "Machine { A -approve-> B; }"),
+        ("both_arrow", "Machine { A <-> B <-open/close-> C; }"),
+        (
+            "parameters",
+            "Machine { A -close(reason: String, code: u16)-> B; }",
+        ),
+        ("same_rank", "Machine { A -> B; same_rank(A, B); }"),
+        (
+            "rank_hint",
+            "Machine { A -> B; rank(A = source, B = sink); }",
+        ),
+        ("block_comment", "Machine { /* comment */ A; }"),
+        ("identifier", "r#Loop { r#try -> r#final; }"),
+    ] {
+        assert!(
+            grammar.contains(&format!("    {}: $ =>", rule)),
+            "no rule `{}`",
+            rule
+        );
+        FSMGenerator::parse_dsl_many
+            .parse_str(example)
+            .unwrap_or_else(|e| panic!("`{}` should parse: {}", example, e));
+    }
+    for keyword in [
+        "'extends'",
+        "'remove'",
+        "'on'",
+        "'self'",
+        "'pub'",
+        "'->'",
+        "'same_rank'",
+        "'rank'",
+        "'type'",
+    ] {
+        assert!(grammar.contains(keyword), "no keyword {}", keyword)
+    }
+}
+
+/// Every kind of [`Stmt`](crate::dsl::Stmt) should be a choice of `_statement` in the grammar.
+#[test]
+fn tree_sitter_statements() {
+    use crate::dsl::{Dsl, Stmt};
+    // a new kind of statement doesn't compile until it's given a rule here,
+    // and an example below
+    let rule = |stmt: &Stmt| match stmt {
+        Stmt::Edges(_) => "edges",
+        Stmt::Wildcard(_) => "wildcard_edge",
+        Stmt::Node(_) => "node",
+        Stmt::Extends(_) => "extends",
+        Stmt::Context(_) => "context",
+        Stmt::Type(_) => "type_alias",
+        Stmt::Remove(_) => "remove",
+        Stmt::SameRank(_) => "same_rank",
+        Stmt::Rank(_) => "rank",
+    };
+    let dsl = syn::parse_str::<Dsl>(
+        "Machine {
+            extends Base;
+            context: u8;
+            type Payload = u8;
+            remove A -> B;
+            same_rank(A, C);
+            rank(A = source);
+            A: Payload;
+            A -> C;
+            _ -> D;
+        }",
+    )
+    .unwrap();
+    let mut statements = dsl.stmts.iter().map(rule).collect::<Vec<_>>();
+    statements.sort();
+    let grammar = FSMGenerator::tree_sitter_grammar();
+    let (_, choice) = grammar
+        .split_once("    _statement: $ => choice(")
+        .expect("no rule `_statement`");
+    let (choice, _) = choice.split_once("),").unwrap();
+    let mut choices = choice
+        .split(',')
+        .map(|it| it.trim().trim_start_matches("$."))
+        .filter(|it| !it.is_empty())
+        .collect::<Vec<_>>();
+    choices.sort();
+    assert_eq!(statements, choices);
+}
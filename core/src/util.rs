@@ -102,3 +102,79 @@ impl ToTokens for OuterDocString {
         })
     }
 }
+
+/// Lines of documentation, with blank lines starting a new paragraph.
+pub fn paragraphs(docs: &[OuterDocString]) -> Vec<String> {
+    let lines = docs
+        .iter()
+        .map(|it| it.doc.value().trim().to_owned())
+        .collect::<Vec<_>>();
+    lines
+        .split(String::is_empty)
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join(" "))
+        .collect()
+}
+
+/// A human-readable rendering of `tokens`, for documentation.
+///
+/// [`ToString`] on a [`TokenStream`] puts spaces around all punctuation, e.g `Vec < u8 >`.
+pub fn unparse(tokens: impl ToTokens) -> String {
+    let mut s = tokens.to_token_stream().to_string();
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" < ", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        ("& ", "&"),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+        (" ;", ";"),
+        (" : ", ": "),
+    ] {
+        s = s.replace(from, to)
+    }
+    s
+}
+
+/// A JSON string literal, which is also safe to embed in a `<script>`.
+pub fn json(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '<' | '>' | '&' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[test]
+fn unparse_types() {
+    let cases: [(syn::Type, &str); 5] = [
+        (parse_quote!(String), "String"),
+        (parse_quote!(std::net::IpAddr), "std::net::IpAddr"),
+        (parse_quote!(Vec<u8>), "Vec<u8>"),
+        (parse_quote!(&'static mut [u8; 4]), "&'static mut [u8; 4]"),
+        (
+            parse_quote!(::std::collections::BTreeMap<String, (u8, char)>),
+            "::std::collections::BTreeMap<String, (u8, char)>",
+        ),
+    ];
+    for (ty, expected) in cases {
+        assert_eq!(unparse(ty), expected)
+    }
+    assert_eq!(
+        unparse(quote::quote!({ ttl: u64, reason: String })),
+        "{ ttl: u64, reason: String }"
+    );
+}
@@ -0,0 +1,74 @@
+//! Bindings for running the generator in a browser, like an online playground.
+//!
+//! Requires the `wasm` feature.
+//! Parsing and generation do no IO, so `fsmentry-core` builds for `wasm32-unknown-unknown`,
+//! though [`RenderJob::run`](crate::RenderJob::run) always fails there, having no `dot` to run.
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{CoreError, FSMGenerator};
+
+/// Generate the code for each state machine in `dsl`,
+/// which includes a mermaid diagram as `State::MERMAID`.
+///
+/// Errors are one per line, prefixed with their one-indexed `line:column`
+/// and [code](crate::ErrorKind::code), like `3:5: E0001: expected an identifier`.
+#[wasm_bindgen]
+pub fn generate(dsl: &str) -> Result<String, String> {
+    let code = parse(dsl)?
+        .iter()
+        .map(|generator| {
+            let mut file = generator.codegen();
+            generator.attach_text_diagram(&mut file);
+            prettyplease::unparse(&file)
+        })
+        .collect::<Vec<_>>();
+    Ok(code.join("\n"))
+}
+
+/// A mermaid diagram of each state machine in `dsl`, separated by blank lines.
+///
+/// Errors are as for [`generate`].
+#[wasm_bindgen]
+pub fn mermaid(dsl: &str) -> Result<String, String> {
+    let diagrams = parse(dsl)?
+        .iter()
+        .map(FSMGenerator::mermaid)
+        .collect::<Vec<_>>();
+    Ok(diagrams.join("\n"))
+}
+
+/// Report every syntax error, not just the first.
+fn parse(dsl: &str) -> Result<Vec<FSMGenerator>, String> {
+    FSMGenerator::check_dsl(dsl).map_err(|errors| {
+        errors
+            .into_iter()
+            .flat_map(messages)
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+fn messages(error: CoreError) -> impl Iterator<Item = String> {
+    let code = error.code();
+    syn::Error::from(error).into_iter().map(move |it| {
+        let start = it.span().start();
+        format!("{}:{}: {}: {}", start.line, start.column + 1, code, it)
+    })
+}
+
+#[test]
+fn wasm() {
+    let code = generate("pub Light { Off -> On -> Off; }").unwrap();
+    assert!(code.contains("pub mod light {"));
+    assert!(mermaid("Light { Off -> On; }")
+        .unwrap()
+        .contains("Off --> On"));
+    // on the third line
+    assert!(generate("Light {\n    Off -> On;\n    On -> -> Off;\n}")
+        .unwrap_err()
+        .starts_with("3:"));
+    assert!(generate("Light { Off -> On; Off -> On; }")
+        .unwrap_err()
+        .contains(": E0004: duplicate edge definition"));
+}
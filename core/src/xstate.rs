@@ -0,0 +1,129 @@
+use crate::{
+    util::{json, paragraphs, OuterDocString},
+    EdgeData, FSMGenerator, NodeData,
+};
+
+impl FSMGenerator {
+    /// The state machine as [XState](https://stately.ai/docs/machines) JSON,
+    /// which the [Stately editor](https://stately.ai) can import.
+    ///
+    /// Each transition is an event named after its method,
+    /// and terminal states are `final`.
+    /// States and events are in the order they were written.
+    /// The data in states, and other options, are not included.
+    pub fn xstate(&self) -> String {
+        let mut s = format!("{{\n  \"id\": {},\n", json(&self.ident.to_string()));
+        let docs = self
+            .attributes
+            .iter()
+            .filter_map(|attr| OuterDocString::try_from(attr.clone()).ok())
+            .collect::<Vec<_>>();
+        let docs = paragraphs(&docs).join("\n\n");
+        if !docs.is_empty() {
+            s.push_str(&format!("  \"description\": {},\n", json(&docs)))
+        }
+        if let Some(initial) = &self.initial {
+            s.push_str(&format!(
+                "  \"initial\": {},\n",
+                json(&initial.inner.to_string())
+            ))
+        }
+        s.push_str("  \"states\": {");
+        let (nodes, edges) = &self.order;
+        for (ix, node) in nodes.iter().enumerate() {
+            let NodeData { docs, terminal, .. } = &self.nodes[node];
+            let mut fields = vec![];
+            let docs = paragraphs(docs).join("\n\n");
+            if !docs.is_empty() {
+                fields.push(format!("\"description\": {}", json(&docs)))
+            }
+            if *terminal {
+                fields.push(String::from("\"type\": \"final\""))
+            }
+            let events = edges
+                .iter()
+                .filter(|(from, _, _)| from == node)
+                .map(|key @ (_, to, method)| {
+                    let EdgeData { docs, .. } = &self.edges[key];
+                    let docs = paragraphs(docs).join("\n\n");
+                    let description = match docs.is_empty() {
+                        true => String::new(),
+                        false => format!(", \"description\": {}", json(&docs)),
+                    };
+                    format!(
+                        "\n        {}: {{ \"target\": {}{} }}",
+                        json(&method.to_string()),
+                        json(&to.inner.to_string()),
+                        description
+                    )
+                })
+                .collect::<Vec<_>>();
+            if !events.is_empty() {
+                fields.push(format!("\"on\": {{{}\n      }}", events.join(",")))
+            }
+            if ix != 0 {
+                s.push(',')
+            }
+            match fields.is_empty() {
+                true => s.push_str(&format!("\n    {}: {{}}", json(&node.inner.to_string()))),
+                false => s.push_str(&format!(
+                    "\n    {}: {{\n      {}\n    }}",
+                    json(&node.inner.to_string()),
+                    fields.join(",\n      ")
+                )),
+            }
+        }
+        s.push_str("\n  }\n}\n");
+        s
+    }
+}
+
+#[test]
+fn xstate() {
+    use syn::parse::Parser as _;
+    let generator = FSMGenerator::parse_dsl
+        .parse2(quote::quote! {
+            /// A traffic light.
+            #[fsmentry(initial = Red)]
+            pub TrafficLight {
+                /// Cars must stop
+                Red;
+                #[fsmentry(terminal)]
+                Off;
+                Red -> Green -"slow down"-> Amber -> Red;
+                Amber -fault-> Off;
+            }
+        })
+        .unwrap();
+    assert_eq!(
+        generator.xstate(),
+        r#"{
+  "id": "TrafficLight",
+  "description": "A traffic light.",
+  "initial": "Red",
+  "states": {
+    "Red": {
+      "description": "Cars must stop",
+      "on": {
+        "green": { "target": "Green" }
+      }
+    },
+    "Off": {
+      "type": "final"
+    },
+    "Green": {
+      "on": {
+        "amber": { "target": "Amber", "description": "slow down" }
+      }
+    },
+    "Amber": {
+      "on": {
+        "red": { "target": "Red" },
+        "fault": { "target": "Off" }
+      }
+    }
+  }
+}
+"#
+    );
+}
@@ -2,7 +2,10 @@
 //!
 //! See the [`fsmentry` crate](https://docs.rs/fsmentry).
 
-use fsmentry_core::FSMGenerator;
+#![cfg_attr(feature = "nightly", feature(proc_macro_diagnostic))]
+
+use fsmentry_core::{FSMGenerator, Warning};
+use proc_macro2::Span;
 use quote::ToTokens as _;
 use syn::parse_macro_input;
 
@@ -21,20 +24,108 @@ use syn::parse_macro_input;
 ///         /// This node contains data.
 ///         SweepingHair: usize;
 ///
+///         /// This node contains named fields,
+///         /// which get accessors like `it.bristles()` and `it.bristles_mut()`.
+///         Combing { bristles: usize, tangles: Tangles }
+///
+///         /// Types may be named, for states here and callers through the module.
+///         type Tangles = u8;
+///
+///         // The machine may hold data of its own, which transitions reach
+///         // through `it.context()` and `it.context_mut()`.
+///         context: u64;
+///
+///         /// States may call methods on the generated `Hooks` trait
+///         /// whenever a transition enters or leaves them.
+///         #[fsmentry(on_enter = log_rest, on_exit = log_wake)]
+///         Resting;
+///
 ///         /// These are edge declarations
 ///         /// This documentation will be shared with each edge.
 ///         ShavingYaks -> SweepingHair -"this is edge-specific documentation"-> Resting;
 ///                             // implicit nodes will be created as appropriate ^
+///
+///         /// Several states may share a transition.
+///         Sleeping & Combing -> ShavingYaks;
+///
+///         /// `<->` adds an edge each way, which `<-forward/backward->` names.
+///         Combing <-nap/stir-> Sleeping;
+///
+///         /// Edges may call a method on the generated `Hooks` trait before transitioning,
+///         /// with references to the data of the states either side.
+///         Resting -on(self.log_yaks)-> ShavingYaks;
+///
+///         /// Edges may name their transition method,
+///         /// so there can be several between the same states.
+///         /// Names are kept as written, while other methods are named after their
+///         /// destination in `#[fsmentry(method_case = snake | camel | preserve)]`,
+///         /// or `#[fsmentry(method_format = "to_{snake}")]`.
+///         Resting -wake_early-> ShavingYaks;
+///
+///         /// Named edges may take arguments,
+///         /// which are passed to the `Hooks` method of the same name.
+///         Sleeping -wake(reason: &'static str)-> Resting;
+///
+///         /// Edges may promise something about the data of their destination,
+///         /// which is documented, and checked in debug builds.
+///         Resting -ensures(*data > 0)-> SweepingHair;
+///
+///         /// Or about the data of their source before transitioning, which is bound
+///         /// as `data`, or by field name like here. `State::handle` refuses the event
+///         /// with an `InvalidTransition` when it doesn't hold.
+///         Combing -requires(*bristles > 0)-> Resting;
+///
+///         /// Edges may only exist under a `#[cfg(..)]`.
+///         /// Diagrams draw them dashed, unless `#[fsmentry(mark_cfg = false)]`.
+///         /// Other attributes, like `#[inline]`, are passed to the transition method.
+///         /// `#[fsmentry(on_mismatch = ..)]` may be given for just this method.
+///         #[cfg(any())]
+///         #[inline]
+///         Resting -> Sleeping;
+///
+///         /// `_` (or `*`) adds an edge from every other state,
+///         /// except where there already is one, or the state is terminal.
+///         _ -> GaveUp;
+///
+///         /// Terminal states may not be left, which is checked,
+///         /// and get a `State::is_terminal` method.
+///         /// Diagrams show a state's `label` in place of its name.
+///         #[fsmentry(terminal, label = "gave up")]
+///         GaveUp;
+///
+///         // Diagrams may be given hints for their layout,
+///         // as may their direction, with `#[fsmentry(rankdir = LR)]`.
+///         same_rank(Resting, Sleeping);
+///         rank(ShavingYaks = source);
+///     }
+///
+///     /// Further machines may follow,
+///     /// and start from the states and edges of an earlier one.
+///     ///
+///     /// Machines with `#[fsmentry(registry = true)]` are listed in a generated
+///     /// `fsm_registry` module, for enumerating them at run time.
+///     #[fsmentry(registry = true)]
+///     pub LazyStateMachine {
+///         extends MyStateMachine;
+///         Resting -> Napping -> ShavingYaks;
 ///     }
 /// }
+///
+/// assert_eq!(fsm_registry::MACHINES[0].name, "LazyStateMachine");
 /// ```
+///
+/// Inner attributes and docs at the start, like `#![derive(Debug)]`,
+/// are shared by every machine that follows.
 #[proc_macro]
 pub fn dsl(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let generator = parse_macro_input!(item with FSMGenerator::parse_dsl);
-    let codegen = generator.codegen();
-    #[cfg(feature = "svg")]
-    let codegen = svg::attach(codegen, &generator);
-    codegen.into_token_stream().into()
+    let generators = parse_macro_input!(item with FSMGenerator::parse_dsl_many);
+    let registry = FSMGenerator::registry(&generators);
+    let mut expanded = generators
+        .into_iter()
+        .map(expand)
+        .collect::<proc_macro::TokenStream>();
+    expanded.extend(proc_macro::TokenStream::from(registry.into_token_stream()));
+    expanded
 }
 
 /// Generates a state machine from the [`DOT` graph description language](https://en.wikipedia.org/wiki/DOT_%28graph_description_language%29):
@@ -53,53 +144,128 @@ pub fn dsl(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
 #[proc_macro]
 pub fn dot(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let generator = parse_macro_input!(item with FSMGenerator::parse_dot);
-    let codegen = generator.codegen();
-    #[cfg(feature = "svg")]
-    let codegen = svg::attach(codegen, &generator);
+    let registry = FSMGenerator::registry(std::slice::from_ref(&generator));
+    let mut expanded = expand(generator);
+    expanded.extend(proc_macro::TokenStream::from(registry.into_token_stream()));
+    expanded
+}
+
+/// Generates state machines from a file in the language of [`dsl!`],
+/// given relative to the crate's `Cargo.toml`:
+/// ```
+/// # use fsmentry_macros::include_fsm;
+/// include_fsm!("src/example.fsm");
+///
+/// let mut machine = traffic_light::TrafficLight::new(traffic_light::State::Red);
+/// ```
+///
+/// Like [`include_str!`], the crate is rebuilt when the file changes.
+#[proc_macro]
+pub fn include_fsm(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let path = parse_macro_input!(item as syn::LitStr);
+    match include(&path) {
+        Ok(it) => it,
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn include(path: &syn::LitStr) -> syn::Result<proc_macro::TokenStream> {
+    let error = |message: String| syn::Error::new(path.span(), message);
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .ok_or_else(|| error(String::from("`CARGO_MANIFEST_DIR` is not set")))?;
+    let full = std::path::Path::new(&manifest_dir).join(path.value());
+    let source = std::fs::read_to_string(&full)
+        .map_err(|e| error(format!("couldn't read `{}`: {}", full.display(), e)))?;
+    let generators = syn::parse::Parser::parse_str(FSMGenerator::parse_dsl_many, &source)
+        .map_err(|e| error(format!("in `{}`: {}", path.value(), e)))?;
+    let registry = FSMGenerator::registry(&generators);
+    let mut expanded = generators
+        .into_iter()
+        .map(expand)
+        .collect::<proc_macro::TokenStream>();
+    expanded.extend(proc_macro::TokenStream::from(registry.into_token_stream()));
+    // have rustc track the file, so changes to it are picked up
+    let full = full.to_string_lossy();
+    expanded.extend(proc_macro::TokenStream::from(quote::quote! {
+        const _: &str = include_str!(#full);
+    }));
+    Ok(expanded)
+}
+
+fn expand(generator: FSMGenerator) -> proc_macro::TokenStream {
+    let mut codegen = generator.codegen();
+    if let Err(e) = svg::attach(&mut codegen, &generator) {
+        return e.into_compile_error().into();
+    }
+    for Warning { span, message } in generator.warnings() {
+        warn(&mut codegen, *span, message)
+    }
     codegen.into_token_stream().into()
 }
 
-#[cfg(feature = "svg")]
-mod svg {
-    use quote::ToTokens as _;
-    use std::{
-        io::Write as _,
-        process::{Command, Stdio},
-    };
-    use syn::parse_quote;
+#[cfg(feature = "nightly")]
+fn warn(_: &mut syn::File, span: Span, message: &str) {
+    proc_macro::Diagnostic::spanned(span.unwrap(), proc_macro::Level::Warning, message).emit()
+}
 
-    pub fn attach(mut file: syn::File, generator: &fsmentry_core::FSMGenerator) -> syn::File {
-        let Some(syn::Item::Mod(syn::ItemMod { attrs, .. })) = file.items.first_mut() else {
-            unreachable!("the code generates a module")
+/// There's no stable way to emit a warning from a proc-macro,
+/// so use a deprecated item instead.
+#[cfg(not(feature = "nightly"))]
+fn warn(file: &mut syn::File, span: Span, message: &str) {
+    file.items.push(syn::parse_quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #message)]
+            struct FsmentryWarning;
+            let _ = FsmentryWarning;
         };
-        if let Some(svg) = render_dot(generator) {
-            let svg = format!("<div>{}</div>", svg);
-            if !attrs.is_empty() {
-                attrs.push(parse_quote!(#[doc = ""]))
-            }
-            attrs.push(parse_quote!(#[doc = #svg]))
-        }
-        file
-    }
+    })
+}
 
-    fn render_dot(generator: &fsmentry_core::FSMGenerator) -> Option<String> {
-        let mut child = Command::new("dot")
-            .arg("-Tsvg")
-            .stdin(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .stdout(Stdio::piped())
-            .spawn()
-            .ok()?;
-        child
-            .stdin
-            .take()
-            .unwrap()
-            .write_all(generator.dot().to_token_stream().to_string().as_bytes())
-            .ok()?;
-        let output = child.wait_with_output().ok()?;
-        match output.status.success() {
-            true => String::from_utf8(output.stdout).ok(),
-            false => None,
+mod svg {
+    use fsmentry_core::{FSMGenerator, IncludeSvg, RenderError};
+    use proc_macro2::Span;
+    use std::io;
+
+    pub fn attach(file: &mut syn::File, generator: &FSMGenerator) -> syn::Result<()> {
+        let default = match cfg!(feature = "svg") {
+            true => IncludeSvg::Auto,
+            false => IncludeSvg::Omit,
+        };
+        match generator.include_svg().unwrap_or(default) {
+            IncludeSvg::Omit => generator.attach_text_diagram(file),
+            IncludeSvg::Force => generator
+                .render_svg(None, None)
+                .and_then(|svg| generator.attach_svg(file, &svg))
+                .map_err(|e| {
+                    syn::Error::new(
+                        Span::call_site(),
+                        format!("couldn't render a diagram of the state machine: {}", e),
+                    )
+                })?,
+            IncludeSvg::Auto => match generator
+                .render_svg(None, None)
+                .and_then(|svg| generator.attach_svg(file, &svg))
+            {
+                Ok(()) => {}
+                // graphviz is optional, since the `svg` feature is on by default,
+                // unless the user has pointed us at it, or asked for a diagram of this machine
+                Err(RenderError::Spawn { source, .. })
+                    if source.kind() == io::ErrorKind::NotFound
+                        && generator.dot_path().is_none()
+                        && generator.include_svg().is_none() =>
+                {
+                    generator.attach_text_diagram(file)
+                }
+                Err(e) => {
+                    super::warn(
+                        file,
+                        Span::call_site(),
+                        &format!("couldn't render a diagram of the state machine: {}", e),
+                    );
+                    generator.attach_text_diagram(file)
+                }
+            },
         }
+        Ok(())
     }
 }
@@ -0,0 +1,220 @@
+//! A language server for the DSL, over stdio.
+//!
+//! Provides diagnostics, go-to-definition for states, hover for their data and reachability,
+//! and a code lens on each state machine with its [`mermaid`](https://mermaid.js.org/) diagram.
+//! Editors may handle the `fsmentry.showMermaid` command to preview it.
+
+use std::collections::HashMap;
+
+use anyhow::Context as _;
+use fsmentry_core::ide::{self, Analysis, Severity};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{CodeLensRequest, GotoDefinition, HoverRequest, Request as _},
+    CodeLens, CodeLensOptions, CodeLensParams, Command, Diagnostic, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, NumberOrString, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+
+fn main() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        code_lens_provider: Some(CodeLensOptions {
+            resolve_provider: Some(false),
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    Server {
+        connection: &connection,
+        documents: HashMap::new(),
+    }
+    .run()?;
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    /// Open documents, re-analyzed on every change.
+    documents: HashMap<Url, Analysis>,
+}
+
+impl Server<'_> {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let connection = self.connection;
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.request(request);
+                    connection.sender.send(Message::Response(response))?
+                }
+                Message::Notification(notification) => self.notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&self, Request { id, method, params }: Request) -> Response {
+        let result = match &*method {
+            HoverRequest::METHOD => {
+                serde_json::from_value(params).and_then(|it| serde_json::to_value(self.hover(it)))
+            }
+            GotoDefinition::METHOD => serde_json::from_value(params)
+                .and_then(|it| serde_json::to_value(self.definition(it))),
+            CodeLensRequest::METHOD => serde_json::from_value(params)
+                .and_then(|it| serde_json::to_value(self.code_lenses(it))),
+            _ => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request `{}`", method),
+                )
+            }
+        };
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn notification(
+        &mut self,
+        Notification { method, params }: Notification,
+    ) -> anyhow::Result<()> {
+        let context = || format!("invalid parameters for `{}`", method);
+        match &*method {
+            DidOpenTextDocument::METHOD => {
+                let DidOpenTextDocumentParams { text_document } =
+                    serde_json::from_value(params).with_context(context)?;
+                self.update(text_document.uri, &text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let DidChangeTextDocumentParams {
+                    text_document,
+                    content_changes,
+                } = serde_json::from_value(params).with_context(context)?;
+                // we only ask for full syncs
+                match content_changes.last() {
+                    Some(change) => self.update(text_document.uri, &change.text),
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let DidCloseTextDocumentParams { text_document } =
+                    serde_json::from_value(params).with_context(context)?;
+                self.documents.remove(&text_document.uri);
+                self.publish(text_document.uri, vec![])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn update(&mut self, uri: Url, text: &str) -> anyhow::Result<()> {
+        let analysis = Analysis::new(text);
+        let diagnostics = analysis
+            .diagnostics()
+            .iter()
+            .map(|it| Diagnostic {
+                range: range(it.range),
+                severity: Some(match it.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                code: it.code.map(|it| NumberOrString::String(it.into())),
+                source: Some(String::from("fsmentry")),
+                message: it.message.clone(),
+                ..Default::default()
+            })
+            .collect();
+        self.documents.insert(uri.clone(), analysis);
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> anyhow::Result<()> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.into(),
+                params,
+            )))?;
+        Ok(())
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let params = params.text_document_position_params;
+        let analysis = self.documents.get(&params.text_document.uri)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: analysis.hover(position(params.position))?,
+            }),
+            range: None,
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let params = params.text_document_position_params;
+        let uri = params.text_document.uri;
+        let analysis = self.documents.get(&uri)?;
+        let definition = analysis.definition(position(params.position))?;
+        Some(GotoDefinitionResponse::Scalar(Location {
+            uri,
+            range: range(definition),
+        }))
+    }
+
+    fn code_lenses(&self, params: CodeLensParams) -> Option<Vec<CodeLens>> {
+        let analysis = self.documents.get(&params.text_document.uri)?;
+        let lenses = analysis.code_lenses().into_iter().map(|it| CodeLens {
+            range: range(it.range),
+            command: Some(Command {
+                title: it.title,
+                command: String::from("fsmentry.showMermaid"),
+                arguments: Some(vec![serde_json::Value::String(it.mermaid)]),
+            }),
+            data: None,
+        });
+        Some(lenses.collect())
+    }
+}
+
+// Columns are counted in characters rather than UTF-16 code units,
+// which only differs for text outside the basic multilingual plane.
+
+fn position(position: Position) -> ide::Position {
+    ide::Position {
+        line: position.line as usize,
+        character: position.character as usize,
+    }
+}
+
+fn range(range: ide::Range) -> Range {
+    let position = |it: ide::Position| Position {
+        line: it.line as u32,
+        character: it.character as u32,
+    };
+    Range {
+        start: position(range.start),
+        end: position(range.end),
+    }
+}
@@ -1,31 +1,251 @@
 use std::{
-    io::{Read, Write as _},
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, BufRead as _, BufReader, Read, Write as _},
+    net::{SocketAddr, TcpListener, TcpStream},
     path::{Path, PathBuf},
-    process::Stdio,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use anyhow::{bail, Context as _};
-use clap::{Parser, ValueEnum};
-use fsmentry::FSMGenerator;
+use clap::{Parser, Subcommand, ValueEnum};
+use fsmentry::{FSMGenerator, RenderError, RenderJob};
 use miette::GraphicalReportHandler;
 use quote::ToTokens as _;
-use syn::{parse::Parser as _, parse_quote};
+use syn::parse::Parser as _;
 
 /// Read a file in a DSL or DOT, and generate rust code for a state machine.
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Input file to generate from.
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Input files to generate from, each containing one or more state machines.
     /// If `-` or not supplied, read from stdin.
-    file: Option<PathBuf>,
+    ///
+    /// Files are generated in parallel, and the output for each is printed in order.
+    files: Vec<PathBuf>,
     /// Whether to shell out to `dot` to render an SVG to include in the diagram documentation.
-    #[arg(long, name = "INCLUDE_SVG", default_value = "auto")]
-    svg: IncludeSvg,
+    ///
+    /// Defaults to `#[fsmentry(svg = ...)]` in the input, then `auto`.
+    /// With `auto`, a diagram which can't be rendered is replaced by a text one,
+    /// with a warning including the output of graphviz.
+    #[arg(long, name = "INCLUDE_SVG")]
+    svg: Option<IncludeSvg>,
+    /// The graphviz binary to render the SVG with.
+    ///
+    /// Defaults to `#[fsmentry(dot_path = "...")]` in the input,
+    /// then the `FSMENTRY_DOT_PATH` environment variable,
+    /// then `dot` on the `PATH`.
+    #[arg(long)]
+    dot_path: Option<PathBuf>,
+    /// Kill the graphviz binary if it takes longer than this many seconds.
+    ///
+    /// Defaults to `#[fsmentry(dot_timeout = <seconds>)]` in the input, then 10 seconds.
+    #[arg(long, name = "SECONDS")]
+    dot_timeout: Option<u64>,
+    /// Store rendered SVGs in this directory, and reuse them on subsequent runs.
+    ///
+    /// Diagrams are keyed by their `DOT` and the version of graphviz,
+    /// and are never deleted.
+    #[arg(long, name = "DIR")]
+    svg_cache: Option<PathBuf>,
     /// What language to interpret the input in.
     #[arg(long, alias = "lang", default_value = "dsl")]
     language: Language,
+    /// Fail on DOT that fsmentry would otherwise ignore with a warning,
+    /// like attributes, ports and repeated edges.
+    #[arg(long, global = true)]
+    strict: bool,
+    /// What to output.
+    #[arg(long, default_value = "rust")]
+    emit: Emit,
+    /// Append a test which fails when the output no longer matches the input,
+    /// for when the output is committed.
+    ///
+    /// PATH is where the output will be written, though only its file name is used,
+    /// since the test reads the file it is in.
+    /// The test needs `fsmentry-core` as a dev-dependency, with the `testing` feature.
+    #[arg(long, name = "PATH")]
+    with_test: Option<PathBuf>,
+    /// Print how long each file took to parse, and each state machine to generate.
+    #[arg(long)]
+    timings: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two versions of a state machine.
+    Diff {
+        /// The file containing the state machine before the change.
+        old: PathBuf,
+        /// The file containing the state machine after the change.
+        new: PathBuf,
+        /// What language to interpret the inputs in.
+        #[arg(long, alias = "lang", default_value = "dsl")]
+        language: Language,
+        /// What to output.
+        #[arg(long, default_value = "dot")]
+        emit: DiffEmit,
+    },
+    /// Check that two versions of a state machine have the same states and transitions,
+    /// ignoring documentation and options, and fail if not.
+    Compare {
+        /// The file containing the state machine before the change.
+        old: PathBuf,
+        /// The file containing the state machine after the change.
+        new: PathBuf,
+        /// What language to interpret the inputs in.
+        #[arg(long, alias = "lang", default_value = "dsl")]
+        language: Language,
+        /// Also ignore the names of states, transitions, hooks, fields and arguments,
+        /// so only the types of data and the shape of the graph must match.
+        #[arg(long)]
+        ignore_names: bool,
+    },
+    /// Serve a page for exploring a state machine and its generated code,
+    /// which reloads whenever the file is saved.
+    Serve {
+        /// The file containing the state machine.
+        file: PathBuf,
+        /// What language to interpret the input in.
+        #[arg(long, alias = "lang", default_value = "dsl")]
+        language: Language,
+        /// The address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8000")]
+        addr: SocketAddr,
+    },
+    /// Start a state machine from a template for a common pattern.
+    ///
+    /// Writes the machine to `<module>.fsm`, and a rust file which includes it to `<module>.rs`,
+    /// where `<module>` is the name of the module generated for the machine.
+    New {
+        /// Which pattern to start from.
+        #[arg(long)]
+        template: Template,
+        /// The name of the state machine, like `HttpRequest`.
+        name: String,
+        /// The directory to write the files to.
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Template {
+    /// A request which is sent, and then succeeds, fails or is cancelled.
+    RequestLifecycle,
+    /// A connection to a peer, which reconnects after a backoff.
+    Connection,
+    /// A job which is retried until it is done, or gives up.
+    JobQueue,
+}
+
+impl Template {
+    /// The machine in the DSL, and the state to start in, with `{name}` in place of its name.
+    fn source(self) -> (&'static str, &'static str) {
+        match self {
+            Template::RequestLifecycle => (
+                r#"/// A request, from when it is received until it is answered.
+#[fsmentry(initial = Received)]
+pub {name} {
+    /// The request is waiting to be sent.
+    Received;
+    /// The request has been sent, this many times.
+    InFlight: u32;
+    /// The response.
+    #[fsmentry(terminal)]
+    Succeeded: String;
+    /// Why the request failed.
+    Failed: String;
+    #[fsmentry(terminal)]
+    Cancelled;
+
+    Received -send-> InFlight -succeed-> Succeeded;
+    InFlight -fail-> Failed -retry-> InFlight;
+    Received -cancel-> Cancelled;
+    InFlight -cancel-> Cancelled;
+    Failed -give_up-> Cancelled;
+}
+"#,
+                "State::Received",
+            ),
+            Template::Connection => (
+                r#"/// A connection to a peer, which reconnects when it is lost.
+#[fsmentry(initial = Disconnected)]
+pub {name} {
+    Disconnected;
+    /// Connecting, for this many attempts so far.
+    Connecting: u32;
+    /// The address of the peer.
+    Connected: String;
+    /// Waiting this many milliseconds to reconnect.
+    Backoff: u64;
+
+    Disconnected -connect-> Connecting -establish-> Connected -close-> Disconnected;
+    Connecting -time_out-> Backoff -reconnect-> Connecting;
+    Connected -lose-> Backoff;
+    Backoff -give_up-> Disconnected;
+}
+"#,
+                "State::Disconnected",
+            ),
+            Template::JobQueue => (
+                r#"/// A job in a queue, which is retried when it fails.
+#[fsmentry(initial = Queued)]
+pub {name} {
+    /// The job is waiting for a worker, with its payload.
+    Queued: String;
+    /// The id of the worker running the job.
+    Running: u64;
+    /// The job failed, for this many attempts so far.
+    Retrying: u32;
+    #[fsmentry(terminal)]
+    Done;
+    /// Why the job gave up.
+    #[fsmentry(terminal)]
+    Dead: String;
+
+    Queued -start-> Running -finish-> Done;
+    Running -fail-> Retrying -requeue-> Queued;
+    Retrying -give_up-> Dead;
+}
+"#,
+                "State::Queued(String::new())",
+            ),
+        }
+    }
+}
+
+/// The rust file written by `fsmentry new`.
+const INCLUDE: &str = r#"//! The `{name}` state machine, generated from `{path}` when the crate is built.
+//!
+//! The path is relative to the crate's `Cargo.toml`, which needs `fsmentry` as a dependency.
+
+fsmentry::include_fsm!("{path}");
+
+pub use {module}::{Entry, State, {name}};
+
+/// A new machine, in its first state.
+pub fn start() -> {name} {
+    {name}::new({initial})
 }
+"#;
 
 #[derive(ValueEnum, Clone)]
+enum DiffEmit {
+    /// A single graphviz diagram of both machines,
+    /// with added states and transitions in green, removed in red, and changed in gold.
+    Dot,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
 enum IncludeSvg {
     Force,
     Omit,
@@ -36,54 +256,588 @@ enum IncludeSvg {
 enum Language {
     Dsl,
     Dot,
+    /// XState JSON, as exported by the Stately editor, which is translated to the DSL.
+    ///
+    /// Only flat machines, whose states don't contain other states, are supported.
+    Xstate,
+}
+
+#[derive(ValueEnum, Clone)]
+enum Emit {
+    /// Rust code for the state machine.
+    Rust,
+    /// A documentation page in GitHub-flavored Markdown, with a mermaid diagram.
+    Markdown,
+    /// A self-contained HTML page for exploring the state machine.
+    ///
+    /// The diagram is included according to `--svg`.
+    Html,
+    /// A tree-sitter `grammar.js` for the DSL, for highlighting it in editors.
+    ///
+    /// No input is read.
+    TreeSitter,
+    /// XState JSON, which the Stately editor can import.
+    Xstate,
+    /// How many of each kind of item is generated for each state machine, as JSON,
+    /// for tracking the growth of the generated API.
+    Report,
 }
 
 fn main() -> anyhow::Result<()> {
     let Args {
-        file,
+        command,
+        files,
         svg,
+        dot_path,
+        dot_timeout,
+        svg_cache,
         language,
+        strict,
+        emit,
+        with_test,
+        timings,
     } = Args::parse();
-    let input = match file {
-        Some(path) if path == Path::new("-") => get_stdin()?,
-        Some(path) => std::fs::read_to_string(path).context("error reading file")?,
-        None => get_stdin()?,
+    match command {
+        Some(Command::Diff {
+            old,
+            new,
+            language,
+            emit: DiffEmit::Dot,
+        }) => {
+            let (old, new) = (
+                parse(&old, source(&old, &language)?, &language, strict, true)?,
+                parse(&new, source(&new, &language)?, &language, strict, true)?,
+            );
+            let ([old], [new]) = (&*old, &*new) else {
+                bail!("`diff` needs exactly one state machine in each file")
+            };
+            println!("{}", old.dot_diff(new).into_token_stream());
+            return Ok(());
+        }
+        Some(Command::Compare {
+            old,
+            new,
+            language,
+            ignore_names,
+        }) => {
+            let (old, new) = (
+                parse(&old, source(&old, &language)?, &language, strict, true)?,
+                parse(&new, source(&new, &language)?, &language, strict, true)?,
+            );
+            let ([old], [new]) = (&*old, &*new) else {
+                bail!("`compare` needs exactly one state machine in each file")
+            };
+            match ignore_names {
+                false if !old.semantic_eq(new) => {
+                    bail!("the state machines have different states or transitions")
+                }
+                true if !old.is_isomorphic(new) => {
+                    bail!("the state machines differ, even after renaming")
+                }
+                _ => return Ok(()),
+            }
+        }
+        Some(Command::Serve {
+            file,
+            language,
+            addr,
+        }) => return serve(&file, &language, strict, addr),
+        Some(Command::New {
+            template,
+            name,
+            dir,
+        }) => return new(template, &name, &dir),
+        None => {}
+    }
+    if let Emit::TreeSitter = emit {
+        print!("{}", FSMGenerator::tree_sitter_grammar());
+        return Ok(());
+    }
+    let files = match files.is_empty() {
+        true => vec![PathBuf::from("-")],
+        false => files,
     };
-    let parser = match language {
-        Language::Dsl => FSMGenerator::parse_dsl,
-        Language::Dot => FSMGenerator::parse_dot,
+    let test_file_name = match (&with_test, &emit, &language) {
+        (None, ..) => None,
+        (Some(path), Emit::Rust, Language::Dsl) => {
+            match path.file_name().and_then(|it| it.to_str()) {
+                Some(it) => Some(it),
+                None => {
+                    bail!("`--with-test` needs the name of the file the output will be written to")
+                }
+            }
+        }
+        (Some(_), ..) => bail!("`--with-test` only supports `--emit=rust` and `--language=dsl`"),
+    };
+    let sources = files
+        .iter()
+        .map(|path| source(path, &language))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let name_path = files.len() != 1;
+    let diagram = |generator: &FSMGenerator| {
+        Diagram::new(
+            generator,
+            svg,
+            dot_path.as_deref(),
+            dot_timeout.map(Duration::from_secs),
+        )
     };
-    let generator = match parser.parse_str(&input) {
-        Ok(generator) => generator,
+    if let Emit::Rust = emit {
+        // each file is parsed and generated on its own thread, since `FSMGenerator` can't be
+        // sent between them, and the diagrams for every file are rendered together here
+        let language = &language;
+        let diagram = &diagram;
+        let (svgs, generated) = thread::scope(|scope| {
+            let workers = files
+                .iter()
+                .zip(&sources)
+                .map(|(path, input)| {
+                    let (parsed_tx, parsed_rx) = mpsc::channel();
+                    let (svgs_tx, svgs_rx) = mpsc::channel::<Vec<Option<String>>>();
+                    let handle = scope.spawn(move || -> anyhow::Result<_> {
+                        let start = Instant::now();
+                        let (generators, warnings) =
+                            parse_with_warnings(path, input.clone(), language, strict, name_path)?;
+                        let diagrams = generators.iter().map(diagram).collect::<Vec<_>>();
+                        let _ = parsed_tx.send((warnings, start.elapsed(), diagrams));
+                        // another file failed
+                        let Ok(svgs) = svgs_rx.recv() else {
+                            return Ok(None);
+                        };
+                        let registry = generators
+                            .iter()
+                            .filter_map(FSMGenerator::registry_entry)
+                            .collect::<Vec<_>>();
+                        Ok(Some((generate(&generators, svgs), registry)))
+                    });
+                    (path, handle, parsed_rx, svgs_tx)
+                })
+                .collect::<Vec<_>>();
+            let mut diagrams = vec![];
+            // how many state machines are in each file, if it was parsed
+            let mut counts = vec![];
+            for (path, _, parsed_rx, _) in &workers {
+                let Ok((warnings, elapsed, parsed)) = parsed_rx.recv() else {
+                    counts.push(None);
+                    continue;
+                };
+                for warning in warnings {
+                    eprintln!("{}", warning)
+                }
+                if timings {
+                    eprintln!("{}: parsed in {:?}", path.display(), elapsed)
+                }
+                counts.push(Some(parsed.len()));
+                diagrams.extend(parsed);
+            }
+            let svgs = match counts.iter().all(Option::is_some) {
+                true => render_svgs(&diagrams, svg_cache.as_deref()).map(Some),
+                false => Ok(None),
+            };
+            if let Ok(Some(svgs)) = &svgs {
+                let mut svgs = svgs.iter().cloned();
+                for ((.., svgs_tx), count) in workers.iter().zip(counts.iter().flatten()) {
+                    let _ = svgs_tx.send(svgs.by_ref().take(*count).collect());
+                }
+            }
+            let generated = workers
+                .into_iter()
+                .map(|(_, handle, _, svgs_tx)| {
+                    // so a worker which is still waiting for its diagrams gives up
+                    drop(svgs_tx);
+                    handle.join().expect("generation doesn't panic")
+                })
+                .collect::<Vec<_>>();
+            (svgs, generated)
+        });
+        let generated = generated.into_iter().collect::<anyhow::Result<Vec<_>>>()?;
+        svgs?;
+        let mut registry = vec![];
+        for (machines, entries) in generated.into_iter().flatten() {
+            for (name, code, elapsed) in machines {
+                if timings {
+                    eprintln!("{}: generated in {:?}", name, elapsed)
+                }
+                println!("{}", code);
+            }
+            registry.extend(entries)
+        }
+        if let Some(registry) = FSMGenerator::registry_of(registry) {
+            let file = syn::File {
+                shebang: None,
+                attrs: vec![],
+                items: vec![registry.into()],
+            };
+            println!("{}", prettyplease::unparse(&file));
+        }
+        if let Some(name) = test_file_name {
+            let test = fsmentry_core::testing::up_to_date_test(&sources.join("\n"), name);
+            let file = syn::File {
+                shebang: None,
+                attrs: vec![],
+                items: vec![test.into()],
+            };
+            println!("{}", prettyplease::unparse(&file));
+        }
+        return Ok(());
+    }
+    let mut generators = vec![];
+    for (path, input) in files.iter().zip(sources) {
+        let start = Instant::now();
+        generators.extend(parse(path, input, &language, strict, name_path)?);
+        if timings {
+            eprintln!("{}: parsed in {:?}", path.display(), start.elapsed())
+        }
+    }
+    let svgs = || {
+        let diagrams = generators.iter().map(diagram).collect::<Vec<_>>();
+        render_svgs(&diagrams, svg_cache.as_deref())
+    };
+    match emit {
+        Emit::Rust => unreachable!("generated on a thread for each file"),
+        Emit::Markdown => {
+            for (ix, generator) in generators.iter().enumerate() {
+                if ix != 0 {
+                    println!()
+                }
+                print!("{}", generator.markdown());
+            }
+        }
+        Emit::Html => {
+            let ([generator], [svg]) = (&*generators, &*svgs()?) else {
+                bail!("`--emit=html` only supports a single input")
+            };
+            print!("{}", generator.html(svg.as_deref()));
+        }
+        Emit::Report => {
+            let reports = generators
+                .iter()
+                .map(|generator| {
+                    let fsmentry::Report {
+                        variants,
+                        handle_structs,
+                        other_types,
+                        methods,
+                        impls,
+                        tokens,
+                    } = generator.report();
+                    serde_json::json!({
+                        "name": generator.name().to_string(),
+                        "variants": variants,
+                        "handle_structs": handle_structs,
+                        "other_types": other_types,
+                        "methods": methods,
+                        "impls": impls,
+                        "tokens": tokens,
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+        }
+        Emit::Xstate => {
+            for (ix, generator) in generators.iter().enumerate() {
+                if ix != 0 {
+                    println!()
+                }
+                print!("{}", generator.xstate());
+            }
+        }
+        Emit::TreeSitter => unreachable!("handled before reading input"),
+    }
+    Ok(())
+}
+
+/// Generate the code for each state machine, with its rendered diagram,
+/// returning the name of each state machine, its code, and how long that took to generate.
+fn generate(
+    generators: &[FSMGenerator],
+    svgs: Vec<Option<String>>,
+) -> Vec<(String, String, Duration)> {
+    let mut generated = vec![];
+    for (generator, svg) in generators.iter().zip(svgs) {
+        let start = Instant::now();
+        let mut codegen = generator.codegen();
+        match svg.map(|it| generator.attach_svg(&mut codegen, &it)) {
+            Some(Ok(())) => {}
+            Some(Err(e)) => {
+                eprintln!(
+                    "warning: couldn't include a diagram of the state machine: {}",
+                    e
+                );
+                generator.attach_text_diagram(&mut codegen)
+            }
+            None => generator.attach_text_diagram(&mut codegen),
+        }
+        let code = prettyplease::unparse(&codegen);
+        generated.push((generator.name().to_string(), code, start.elapsed()))
+    }
+    generated
+}
+
+type MachineParser = fn(syn::parse::ParseStream) -> syn::Result<Vec<FSMGenerator>>;
+
+fn parser(language: &Language, strict: bool) -> MachineParser {
+    match (language, strict) {
+        // report every syntax error, not just the first
+        (Language::Dsl | Language::Xstate, _) => |input: syn::parse::ParseStream| {
+            let (machines, errors) = FSMGenerator::parse_dsl_lenient(input)?;
+            let mut errors = errors.into_iter();
+            match errors.next() {
+                None => Ok(machines),
+                Some(mut first) => {
+                    first.extend(errors);
+                    Err(first)
+                }
+            }
+        },
+        (Language::Dot, false) => {
+            |input: syn::parse::ParseStream| Ok(vec![FSMGenerator::parse_dot(input)?])
+        }
+        (Language::Dot, true) => {
+            |input: syn::parse::ParseStream| Ok(vec![FSMGenerator::parse_dot_strict(input)?])
+        }
+    }
+}
+
+/// Parse the state machines in `input`, read from `path`, printing any warnings.
+///
+/// If `name_path`, errors and warnings say which file they are from.
+fn parse(
+    path: &Path,
+    input: String,
+    language: &Language,
+    strict: bool,
+    name_path: bool,
+) -> anyhow::Result<Vec<FSMGenerator>> {
+    let (parsed, warnings) = parse_with_warnings(path, input, language, strict, name_path)?;
+    for warning in warnings {
+        eprintln!("{}", warning)
+    }
+    Ok(parsed)
+}
+
+/// [`parse`], returning the warnings rather than printing them.
+fn parse_with_warnings(
+    path: &Path,
+    input: String,
+    language: &Language,
+    strict: bool,
+    name_path: bool,
+) -> anyhow::Result<(Vec<FSMGenerator>, Vec<String>)> {
+    match parser(language, strict).parse_str(&input) {
+        Ok(parsed) => {
+            let warnings = parsed
+                .iter()
+                .flat_map(FSMGenerator::warnings)
+                .map(|warning| {
+                    let error = syn::Error::new(warning.span, &warning.message);
+                    let mut s = String::new();
+                    GraphicalReportHandler::new()
+                        .render_report(&mut s, &syn_miette::Error::new(error, input.clone()))
+                        .unwrap();
+                    match name_path {
+                        false => format!("warning:\n{}", s),
+                        true => format!("warning in {}:\n{}", path.display(), s),
+                    }
+                })
+                .collect();
+            Ok((parsed, warnings))
+        }
         Err(error) => {
             let mut s = String::new();
             GraphicalReportHandler::new()
                 .render_report(&mut s, &syn_miette::Error::new(error, input))
                 .unwrap();
-            bail!("\n{}", s);
+            match name_path {
+                false => bail!("\n{}", s),
+                true => bail!("in {}:\n{}", path.display(), s),
+            }
         }
-    };
-    let mut codegen = generator.codegen();
-    let svg = match svg {
-        IncludeSvg::Force => Some(render_dot(&generator)?),
-        IncludeSvg::Omit => None,
-        IncludeSvg::Auto => render_dot(&generator).ok(),
-    };
-    let Some(syn::Item::Mod(syn::ItemMod { attrs, .. })) = codegen.items.first_mut() else {
-        unreachable!("the code generates a module")
-    };
-    if let Some(svg) = svg {
-        let svg = format!("<div>{}</div>", svg);
-        if !attrs.is_empty() {
-            attrs.push(parse_quote!(#[doc = ""]))
+    }
+}
+
+/// Read the file at `path`, or stdin if it is `-`.
+fn read(path: &Path) -> anyhow::Result<String> {
+    match path == Path::new("-") {
+        true => get_stdin(),
+        false => fs::read_to_string(path)
+            .with_context(|| format!("error reading file {}", path.display())),
+    }
+}
+
+/// Read the file at `path`, translating it to the DSL if it is in XState JSON.
+fn source(path: &Path, language: &Language) -> anyhow::Result<String> {
+    let input = read(path)?;
+    match language {
+        Language::Xstate => xstate_to_dsl(&input)
+            .with_context(|| format!("couldn't translate {} from XState", path.display())),
+        Language::Dsl | Language::Dot => Ok(input),
+    }
+}
+
+/// Translate a machine in XState JSON to the DSL.
+///
+/// Each event becomes a transition method of the same name,
+/// in `snake_case` only if it isn't a Rust identifier,
+/// and `final` states are terminal.
+/// States and events keep their order, so exporting the machine again gives the same JSON.
+fn xstate_to_dsl(input: &str) -> anyhow::Result<String> {
+    use serde_json::Value;
+    let machine = serde_json::from_str::<Value>(input).context("invalid JSON")?;
+    let name = machine["id"]
+        .as_str()
+        .context("the machine must have an `id`")?;
+    let states = machine["states"]
+        .as_object()
+        .context("the machine must have `states`")?;
+    let mut dsl = doc_comment("", &machine["description"]);
+    if let Some(initial) = machine["initial"].as_str() {
+        dsl.push_str(&format!(
+            "#[fsmentry(initial = {})]\n",
+            identifier(initial)?
+        ))
+    }
+    dsl.push_str(&format!("pub {} {{\n", identifier(name)?));
+    let mut edges = String::new();
+    for (state, body) in states {
+        let from = identifier(state)?;
+        if body.get("states").is_some() {
+            bail!("`{}` contains other states, which aren't supported", state)
+        }
+        dsl.push_str(&doc_comment("    ", &body["description"]));
+        if body["type"] == "final" {
+            dsl.push_str("    #[fsmentry(terminal)]\n")
+        }
+        dsl.push_str(&format!("    {};\n", from));
+        let Some(events) = body.get("on") else {
+            continue;
+        };
+        let events = events
+            .as_object()
+            .with_context(|| format!("the events of `{}` must be an object", state))?;
+        for (event, transition) in events {
+            let transition = match transition {
+                Value::Array(it) if it.len() == 1 => &it[0],
+                Value::Array(_) => bail!(
+                    "`{}` has several transitions for `{}`, but guards aren't supported",
+                    state,
+                    event
+                ),
+                it => it,
+            };
+            let target = match transition {
+                Value::String(it) => it.as_str(),
+                Value::Object(it) => match it.get("target") {
+                    Some(Value::String(it)) => it.as_str(),
+                    _ => bail!(
+                        "`{}` has no single target for `{}`, which isn't supported",
+                        state,
+                        event
+                    ),
+                },
+                _ => bail!("invalid transition for `{}` in `{}`", event, state),
+            };
+            // siblings may be referred to by id, or relative to the machine
+            let target = target
+                .strip_prefix(&format!("#{}.", name))
+                .or_else(|| target.strip_prefix('.'))
+                .unwrap_or(target);
+            edges.push_str(&doc_comment("    ", &transition["description"]));
+            edges.push_str(&format!(
+                "    {} -{}-> {};\n",
+                from,
+                match identifier(event) {
+                    Ok(it) => it.to_owned(),
+                    Err(_) => identifier(&snake_case(event))?.to_owned(),
+                },
+                identifier(target)?
+            ));
         }
-        attrs.push(parse_quote!(#[doc = #svg]))
     }
+    dsl.push_str(&edges);
+    dsl.push_str("}\n");
+    Ok(dsl)
+}
 
-    println!("{}", prettyplease::unparse(&codegen));
+/// Write the files for `fsmentry new`, refusing to overwrite any.
+fn new(template: Template, name: &str, dir: &Path) -> anyhow::Result<()> {
+    let name = identifier(name)?;
+    let (dsl, initial) = template.source();
+    let dsl = dsl.replace("{name}", name);
+    let generators = FSMGenerator::parse_dsl_many
+        .parse_str(&dsl)
+        .with_context(|| format!("`{}` can't be the name of the state machine", name))?;
+    let [generator] = &*generators else {
+        unreachable!("each template has one state machine")
+    };
+    let module = generator
+        .codegen()
+        .items
+        .into_iter()
+        .find_map(|it| match it {
+            syn::Item::Mod(it) => Some(it.ident),
+            _ => None,
+        })
+        .context("no module was generated for the state machine")?;
+    let fsm = dir.join(format!("{}.fsm", module));
+    let rs = dir.join(format!("{}.rs", module));
+    for path in [&fsm, &rs] {
+        if path.exists() {
+            bail!("{} already exists", path.display())
+        }
+    }
+    let include = INCLUDE
+        .replace(
+            "{path}",
+            &fsm.strip_prefix(".").unwrap_or(&fsm).to_string_lossy(),
+        )
+        .replace("{module}", &module.to_string())
+        .replace("{initial}", initial)
+        .replace("{name}", name);
+    fs::write(&fsm, dsl).with_context(|| format!("error writing file {}", fsm.display()))?;
+    fs::write(&rs, include).with_context(|| format!("error writing file {}", rs.display()))?;
+    eprintln!("wrote {} and {}", fsm.display(), rs.display());
     Ok(())
 }
 
+fn identifier(name: &str) -> anyhow::Result<&str> {
+    match syn::parse_str::<syn::Ident>(name) {
+        Ok(_) => Ok(name),
+        Err(_) => bail!("`{}` must be a Rust identifier", name),
+    }
+}
+
+/// `GO_BACK`, `goBack` and `go-back` are all `go_back`.
+fn snake_case(name: &str) -> String {
+    let mut s = String::new();
+    let mut prev = None::<char>;
+    for c in name.chars() {
+        match c {
+            '-' | '.' | ' ' => s.push('_'),
+            c if c.is_uppercase() => {
+                if prev.is_some_and(|it| it.is_lowercase() || it.is_ascii_digit()) {
+                    s.push('_')
+                }
+                s.extend(c.to_lowercase())
+            }
+            c => s.push(c),
+        }
+        prev = Some(c);
+    }
+    s
+}
+
+/// A `///` comment for each line of `description`, if it is a string.
+fn doc_comment(indent: &str, description: &serde_json::Value) -> String {
+    let Some(description) = description.as_str() else {
+        return String::new();
+    };
+    description
+        .lines()
+        .map(|line| format!("{}/// {}\n", indent, line))
+        .collect()
+}
+
 fn get_stdin() -> anyhow::Result<String> {
     let mut s = String::new();
     std::io::stdin()
@@ -92,24 +846,298 @@ fn get_stdin() -> anyhow::Result<String> {
     Ok(s)
 }
 
-fn render_dot(generator: &fsmentry_core::FSMGenerator) -> anyhow::Result<String> {
-    let mut child = std::process::Command::new("dot")
-        .arg("-Tsvg")
-        .stdin(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .stdout(Stdio::piped())
-        .spawn()
-        .context("could not exec `dot` - is it installed and on the PATH?")?;
-    child
-        .stdin
-        .take()
-        .unwrap()
-        .write_all(generator.dot().into_token_stream().to_string().as_bytes())
-        .context("couldn't pipe to `dot`")?;
-    let output = child.wait_with_output().context("couldn't join `dot`")?;
-    match output.status.code() {
-        Some(0) => String::from_utf8(output.stdout).context("`dot` returned a non-utf8 svg"),
-        Some(n) => bail!("`dot` exited with code {}", n),
-        None => bail!("`dot` exited abnormally"),
+/// How to render the diagram of a state machine.
+///
+/// Unlike [`FSMGenerator`], this may be sent to other threads.
+struct Diagram {
+    svg: IncludeSvg,
+    /// Whether graphviz may be missing without a warning.
+    optional: bool,
+    job: RenderJob,
+}
+
+impl Diagram {
+    fn new(
+        generator: &FSMGenerator,
+        svg: Option<IncludeSvg>,
+        dot_path: Option<&Path>,
+        dot_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            // as for the macros, unless the user has pointed us at graphviz, or asked for a diagram
+            optional: svg.is_none()
+                && generator.include_svg().is_none()
+                && dot_path.is_none()
+                && generator.dot_path().is_none(),
+            svg: svg.unwrap_or(match generator.include_svg() {
+                Some(fsmentry::IncludeSvg::Auto) | None => IncludeSvg::Auto,
+                Some(fsmentry::IncludeSvg::Force) => IncludeSvg::Force,
+                Some(fsmentry::IncludeSvg::Omit) => IncludeSvg::Omit,
+            }),
+            job: generator.render_job(dot_path, dot_timeout),
+        }
+    }
+}
+
+/// Render the diagrams concurrently, since graphviz dominates generation time.
+///
+/// Identical diagrams are only rendered once.
+fn render_svgs(diagrams: &[Diagram], cache: Option<&Path>) -> anyhow::Result<Vec<Option<String>>> {
+    let unique = diagrams
+        .iter()
+        .filter(|it| !matches!(it.svg, IncludeSvg::Omit))
+        .map(|it| &it.job)
+        .collect::<BTreeSet<_>>();
+    let mut rendered = thread::scope(|scope| {
+        let handles = unique
+            .into_iter()
+            .map(|job| (job, scope.spawn(move || render_cached(job, cache))))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|(job, handle)| (job, handle.join().expect("rendering doesn't panic")))
+            .collect::<BTreeMap<_, _>>()
+    });
+    let mut svgs = vec![];
+    for Diagram { svg, optional, job } in diagrams {
+        match (svg, rendered.get(job)) {
+            (IncludeSvg::Omit, _) | (_, None) => svgs.push(None),
+            (_, Some(Ok(it))) => svgs.push(Some(it.clone())),
+            (IncludeSvg::Force, Some(Err(_))) => {
+                return Err(rendered.remove(job).unwrap().unwrap_err())
+            }
+            (IncludeSvg::Auto, Some(Err(e))) => {
+                let missing = matches!(
+                    e.downcast_ref::<RenderError>(),
+                    Some(RenderError::Spawn { source, .. }) if source.kind() == io::ErrorKind::NotFound
+                );
+                if !(missing && *optional) {
+                    eprintln!(
+                        "warning: couldn't include a diagram of the state machine: {:#}",
+                        e
+                    )
+                }
+                svgs.push(None)
+            }
+        }
+    }
+    Ok(svgs)
+}
+
+fn render_cached(job: &RenderJob, cache: Option<&Path>) -> anyhow::Result<String> {
+    let Some(cache) = cache else {
+        return job.run().context("couldn't render the diagram");
+    };
+    // upgrading graphviz shouldn't reuse the diagrams of the old version
+    let version = process::Command::new(&job.dot)
+        .arg("-V")
+        .output()
+        .map(|it| it.stderr)
+        .unwrap_or_default();
+    let key = fnv1a([&*version, "\0".as_bytes(), job.input.as_bytes()]);
+    let path = cache.join(format!("{:016x}.svg", key));
+    if let Ok(svg) = fs::read_to_string(&path) {
+        return Ok(svg);
+    }
+    let svg = job.run().context("couldn't render the diagram")?;
+    fs::create_dir_all(cache).context("couldn't create the svg cache")?;
+    // so an interrupted or concurrent run can't leave part of a diagram in the cache
+    static TEMPS: AtomicUsize = AtomicUsize::new(0);
+    let temp = cache.join(format!(
+        "{:016x}.{}.{}.tmp",
+        key,
+        process::id(),
+        TEMPS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temp, &svg).context("couldn't write to the svg cache")?;
+    fs::rename(&temp, &path).context("couldn't write to the svg cache")?;
+    Ok(svg)
+}
+
+/// The 64-bit FNV-1a hash, since cache keys must be the same between builds,
+/// which isn't guaranteed for [`DefaultHasher`](std::collections::hash_map::DefaultHasher).
+fn fnv1a<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in parts.into_iter().flatten() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Serve the page for the state machine in `path`, rendering it again for each request.
+///
+/// The page polls `/version`, the modification time of the file, and reloads when it changes.
+fn serve(path: &Path, language: &Language, strict: bool, addr: SocketAddr) -> anyhow::Result<()> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("couldn't listen on {}", addr))?;
+    eprintln!(
+        "serving {} on http://{}",
+        path.display(),
+        listener.local_addr()?
+    );
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        // read the whole request, so the connection closes cleanly
+        let mut lines = BufReader::new(&stream).lines();
+        let Some(Ok(request)) = lines.next() else {
+            continue;
+        };
+        for line in lines.by_ref() {
+            match line {
+                Ok(line) if !line.is_empty() => {}
+                _ => break,
+            }
+        }
+        let version = version(path);
+        let response = match request.split_whitespace().nth(1) {
+            Some("/") => respond(
+                stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                &page(path, language, strict, &version),
+            ),
+            Some("/version") => respond(stream, "200 OK", "text/plain", &version),
+            _ => respond(stream, "404 Not Found", "text/plain", "not found"),
+        };
+        if let Err(e) = response {
+            eprintln!("warning: couldn't respond to a request: {}", e)
+        }
+    }
+    Ok(())
+}
+
+fn respond(
+    mut stream: TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Changes whenever the file at `path` is saved.
+fn version(path: &Path) -> String {
+    match fs::metadata(path).and_then(|it| it.modified()) {
+        Ok(it) => format!("{:?}", it),
+        Err(_) => String::new(),
     }
 }
+
+/// The `--emit=html` page with the generated code, or the error if the file is invalid.
+fn page(path: &Path, language: &Language, strict: bool, version: &str) -> String {
+    let reload = RELOAD.replace("{version}", &serde_json::Value::from(version).to_string());
+    match render_page(path, language, strict) {
+        Ok(page) => {
+            let ix = page.rfind("</body>").unwrap_or(page.len());
+            format!("{}{}{}", &page[..ix], reload, &page[ix..])
+        }
+        Err(e) => format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<pre>{}</pre>\n{}</body>\n</html>\n",
+            escape(&path.display().to_string()),
+            escape(&format!("{:?}", e)),
+            reload
+        ),
+    }
+}
+
+fn render_page(path: &Path, language: &Language, strict: bool) -> anyhow::Result<String> {
+    let input = source(path, language)?;
+    let generators = parse(path, input.clone(), language, strict, false)?;
+    let [generator] = &*generators else {
+        bail!("`serve` only supports a single state machine")
+    };
+    let [svg] = &*render_svgs(&generators, None, None, None, None)? else {
+        unreachable!("one diagram for each machine")
+    };
+    // the diagram is already on the page
+    let [(_, code, _)] = &*generate(&input, language, strict, vec![None])? else {
+        unreachable!("one output for each machine")
+    };
+    let page = generator.html(svg.as_deref());
+    let ix = page.find("</main>").unwrap_or(page.len());
+    Ok(format!(
+        "{}<h2>Generated code</h2>\n<pre id=\"code\"><code>{}</code></pre>\n{}",
+        &page[..ix],
+        highlight(code),
+        &page[ix..]
+    ))
+}
+
+const RELOAD: &str = r#"<style>
+#code .keyword { color: #a626a4; }
+#code .string { color: #50a14f; }
+#code .comment { color: #a0a1a7; }
+</style>
+<script>
+const VERSION = {version};
+setInterval(async () => {
+  const version = await fetch("/version").then((it) => it.text()).catch(() => VERSION);
+  if (version !== VERSION) location.reload();
+}, 500);
+</script>
+"#;
+
+/// Wrap the comments, strings and keywords in `code` in `<span>`s, escaping the rest.
+fn highlight(code: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "const", "crate", "dyn", "else", "enum", "fn", "for", "if", "impl", "in", "let",
+        "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct",
+        "super", "trait", "type", "unsafe", "use", "where", "while",
+    ];
+    let mut out = String::new();
+    let mut rest = code;
+    while let Some(first) = rest.chars().next() {
+        let (len, class) = match first {
+            '/' if rest.starts_with("//") => {
+                (rest.find('\n').unwrap_or(rest.len()), Some("comment"))
+            }
+            '"' => {
+                let mut escaped = false;
+                let end = rest.char_indices().skip(1).find(|(_, c)| {
+                    let end = *c == '"' && !escaped;
+                    escaped = *c == '\\' && !escaped;
+                    end
+                });
+                (
+                    end.map(|(ix, _)| ix + 1).unwrap_or(rest.len()),
+                    Some("string"),
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (len, KEYWORDS.contains(&&rest[..len]).then_some("keyword"))
+            }
+            c => (c.len_utf8(), None),
+        };
+        let (token, after) = rest.split_at(len);
+        match class {
+            Some(class) => out.push_str(&format!(
+                "<span class=\"{}\">{}</span>",
+                class,
+                escape(token)
+            )),
+            None => out.push_str(&escape(token)),
+        }
+        rest = after;
+    }
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,115 +1,7 @@
 /// This machine exercises all vertex types, with and without data.
 ///
-/**<div><?xml version="1.0" encoding="UTF-8" standalone="no"?>
-<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN"
- "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
-<!-- Generated by graphviz version 2.43.0 (0)
- -->
-<!-- Title: ExampleMachine Pages: 1 -->
-<svg width="617pt" height="188pt"
- viewBox="0.00 0.00 616.63 188.00" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
-<g id="graph0" class="graph" transform="scale(1 1) rotate(0) translate(4 184)">
-<title>ExampleMachine</title>
-<polygon fill="white" stroke="transparent" points="-4,4 -4,-184 612.63,-184 612.63,4 -4,4"/>
-<!-- BeautifulBridge -->
-<g id="node1" class="node">
-<title>BeautifulBridge</title>
-<ellipse fill="none" stroke="black" cx="84.49" cy="-90" rx="84.49" ry="18"/>
-<text text-anchor="middle" x="84.49" y="-86.3" font-family="Times,serif" font-size="14.00">BeautifulBridge</text>
-</g>
-<!-- Tombstone -->
-<g id="node7" class="node">
-<title>Tombstone</title>
-<ellipse fill="none" stroke="black" cx="70.49" cy="-18" rx="61.99" ry="18"/>
-<text text-anchor="middle" x="70.49" y="-14.3" font-family="Times,serif" font-size="14.00">Tombstone</text>
-</g>
-<!-- BeautifulBridge&#45;&gt;Tombstone -->
-<g id="edge1" class="edge">
-<title>BeautifulBridge&#45;&gt;Tombstone</title>
-<path fill="none" stroke="black" d="M81.03,-71.7C79.49,-63.98 77.63,-54.71 75.91,-46.11"/>
-<polygon fill="black" stroke="black" points="79.31,-45.22 73.91,-36.1 72.44,-46.6 79.31,-45.22"/>
-</g>
-<!-- UnmarkedGrave -->
-<g id="node8" class="node">
-<title>UnmarkedGrave</title>
-<ellipse fill="none" stroke="black" cx="237.49" cy="-18" rx="87.18" ry="18"/>
-<text text-anchor="middle" x="237.49" y="-14.3" font-family="Times,serif" font-size="14.00">UnmarkedGrave</text>
-</g>
-<!-- BeautifulBridge&#45;&gt;UnmarkedGrave -->
-<g id="edge2" class="edge">
-<title>BeautifulBridge&#45;&gt;UnmarkedGrave</title>
-<path fill="none" stroke="black" d="M118.45,-73.46C140.75,-63.26 170.14,-49.82 194.13,-38.84"/>
-<polygon fill="black" stroke="black" points="195.73,-41.96 203.37,-34.61 192.82,-35.59 195.73,-41.96"/>
-</g>
-<!-- DesertIsland -->
-<g id="node2" class="node">
-<title>DesertIsland</title>
-<ellipse fill="none" stroke="black" cx="350.49" cy="-162" rx="70.39" ry="18"/>
-<text text-anchor="middle" x="350.49" y="-158.3" font-family="Times,serif" font-size="14.00">DesertIsland</text>
-</g>
-<!-- Fountain -->
-<g id="node3" class="node">
-<title>Fountain</title>
-<ellipse fill="none" stroke="black" cx="96.49" cy="-162" rx="51.99" ry="18"/>
-<text text-anchor="middle" x="96.49" y="-158.3" font-family="Times,serif" font-size="14.00">Fountain</text>
-</g>
-<!-- Fountain&#45;&gt;BeautifulBridge -->
-<g id="edge3" class="edge">
-<title>Fountain&#45;&gt;BeautifulBridge</title>
-<path fill="none" stroke="black" d="M93.53,-143.7C92.2,-135.98 90.61,-126.71 89.14,-118.11"/>
-<polygon fill="black" stroke="black" points="92.56,-117.37 87.42,-108.1 85.66,-118.55 92.56,-117.37"/>
-</g>
-<!-- Plank -->
-<g id="node4" class="node">
-<title>Plank</title>
-<ellipse fill="none" stroke="black" cx="223.49" cy="-90" rx="36.29" ry="18"/>
-<text text-anchor="middle" x="223.49" y="-86.3" font-family="Times,serif" font-size="14.00">Plank</text>
-</g>
-<!-- Fountain&#45;&gt;Plank -->
-<g id="edge4" class="edge">
-<title>Fountain&#45;&gt;Plank</title>
-<path fill="none" stroke="black" d="M123.12,-146.33C142.88,-135.43 169.95,-120.51 190.99,-108.91"/>
-<polygon fill="black" stroke="black" points="192.83,-111.9 199.9,-104 189.45,-105.77 192.83,-111.9"/>
-</g>
-<!-- Plank&#45;&gt;Tombstone -->
-<g id="edge5" class="edge">
-<title>Plank&#45;&gt;Tombstone</title>
-<path fill="none" stroke="black" d="M197.54,-77.13C173.97,-66.34 138.82,-50.26 111.47,-37.75"/>
-<polygon fill="black" stroke="black" points="112.7,-34.46 102.15,-33.48 109.78,-40.83 112.7,-34.46"/>
-</g>
-<!-- Plank&#45;&gt;UnmarkedGrave -->
-<g id="edge6" class="edge">
-<title>Plank&#45;&gt;UnmarkedGrave</title>
-<path fill="none" stroke="black" d="M226.88,-72.05C228.42,-64.35 230.29,-55.03 232.02,-46.36"/>
-<polygon fill="black" stroke="black" points="235.51,-46.77 234.04,-36.28 228.64,-45.39 235.51,-46.77"/>
-</g>
-<!-- PopulatedIsland -->
-<g id="node5" class="node">
-<title>PopulatedIsland</title>
-<ellipse fill="none" stroke="black" cx="523.49" cy="-162" rx="85.29" ry="18"/>
-<text text-anchor="middle" x="523.49" y="-158.3" font-family="Times,serif" font-size="14.00">PopulatedIsland</text>
-</g>
-<!-- Stream -->
-<g id="node6" class="node">
-<title>Stream</title>
-<ellipse fill="none" stroke="black" cx="217.49" cy="-162" rx="44.69" ry="18"/>
-<text text-anchor="middle" x="217.49" y="-158.3" font-family="Times,serif" font-size="14.00">Stream</text>
-</g>
-<!-- Stream&#45;&gt;BeautifulBridge -->
-<g id="edge7" class="edge">
-<title>Stream&#45;&gt;BeautifulBridge</title>
-<path fill="none" stroke="black" d="M191.22,-147.17C172.01,-137.06 145.62,-123.17 123.9,-111.74"/>
-<polygon fill="black" stroke="black" points="125.38,-108.57 114.9,-107.01 122.12,-114.76 125.38,-108.57"/>
-</g>
-<!-- Stream&#45;&gt;Plank -->
-<g id="edge8" class="edge">
-<title>Stream&#45;&gt;Plank</title>
-<path fill="none" stroke="black" d="M218.98,-143.7C219.64,-135.98 220.43,-126.71 221.17,-118.11"/>
-<polygon fill="black" stroke="black" points="224.66,-118.37 222.03,-108.1 217.69,-117.77 224.66,-118.37"/>
-</g>
-</g>
-</svg>
-</div>*/
+/**<div><svg width="617pt" height="188pt"
+ viewBox="0.00 0.00 616.63 188.00" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" role="img" aria-label="State machine ExampleMachine with 8 states and 8 transitions."><title>State machine ExampleMachine with 8 states and 8 transitions.</title><desc>BeautifulBridge goes to Tombstone, UnmarkedGrave. DesertIsland has no transitions. Fountain goes to BeautifulBridge, Plank. Plank goes to Tombstone, UnmarkedGrave. PopulatedIsland has no transitions. Stream goes to BeautifulBridge, Plank. Tombstone has no transitions. UnmarkedGrave has no transitions.</desc><g id="graph0" class="graph" transform="scale(1 1) rotate(0) translate(4 184)"><title>ExampleMachine</title><polygon fill="white" stroke="transparent" points="-4,4 -4,-184 612.63,-184 612.63,4 -4,4"/><g id="diagram.BeautifulBridge" class="node"><title>BeautifulBridge</title><a href="enum.State.html#variant.BeautifulBridge" xlink:href="enum.State.html#variant.BeautifulBridge"><ellipse fill="none" stroke="black" cx="84.49" cy="-90" rx="84.49" ry="18"/><text text-anchor="middle" x="84.49" y="-86.3" font-family="Times,serif" font-size="14.00">BeautifulBridge</text></a></g><g id="diagram.Tombstone" class="node"><title>Tombstone</title><a href="enum.State.html#variant.Tombstone" xlink:href="enum.State.html#variant.Tombstone"><ellipse fill="none" stroke="black" cx="70.49" cy="-18" rx="61.99" ry="18"/><text text-anchor="middle" x="70.49" y="-14.3" font-family="Times,serif" font-size="14.00">Tombstone</text></a></g><g id="edge1" class="edge"><title>BeautifulBridge&#45;&gt;Tombstone</title><a href="struct.BeautifulBridge.html#method.tombstone" xlink:href="struct.BeautifulBridge.html#method.tombstone"><path fill="none" stroke="black" d="M81.03,-71.7C79.49,-63.98 77.63,-54.71 75.91,-46.11"/><polygon fill="black" stroke="black" points="79.31,-45.22 73.91,-36.1 72.44,-46.6 79.31,-45.22"/></a></g><g id="diagram.UnmarkedGrave" class="node"><title>UnmarkedGrave</title><a href="enum.State.html#variant.UnmarkedGrave" xlink:href="enum.State.html#variant.UnmarkedGrave"><ellipse fill="none" stroke="black" cx="237.49" cy="-18" rx="87.18" ry="18"/><text text-anchor="middle" x="237.49" y="-14.3" font-family="Times,serif" font-size="14.00">UnmarkedGrave</text></a></g><g id="edge2" class="edge"><title>BeautifulBridge&#45;&gt;UnmarkedGrave</title><a href="struct.BeautifulBridge.html#method.unmarked_grave" xlink:href="struct.BeautifulBridge.html#method.unmarked_grave"><path fill="none" stroke="black" d="M118.45,-73.46C140.75,-63.26 170.14,-49.82 194.13,-38.84"/><polygon fill="black" stroke="black" points="195.73,-41.96 203.37,-34.61 192.82,-35.59 195.73,-41.96"/></a></g><g id="diagram.DesertIsland" class="node"><title>DesertIsland</title><a href="enum.State.html#variant.DesertIsland" xlink:href="enum.State.html#variant.DesertIsland"><ellipse fill="none" stroke="black" cx="350.49" cy="-162" rx="70.39" ry="18"/><text text-anchor="middle" x="350.49" y="-158.3" font-family="Times,serif" font-size="14.00">DesertIsland</text></a></g><g id="diagram.Fountain" class="node"><title>Fountain</title><a href="enum.State.html#variant.Fountain" xlink:href="enum.State.html#variant.Fountain"><ellipse fill="none" stroke="black" cx="96.49" cy="-162" rx="51.99" ry="18"/><text text-anchor="middle" x="96.49" y="-158.3" font-family="Times,serif" font-size="14.00">Fountain</text></a></g><g id="edge3" class="edge"><title>Fountain&#45;&gt;BeautifulBridge</title><a href="struct.Fountain.html#method.beautiful_bridge" xlink:href="struct.Fountain.html#method.beautiful_bridge"><path fill="none" stroke="black" d="M93.53,-143.7C92.2,-135.98 90.61,-126.71 89.14,-118.11"/><polygon fill="black" stroke="black" points="92.56,-117.37 87.42,-108.1 85.66,-118.55 92.56,-117.37"/></a></g><g id="diagram.Plank" class="node"><title>Plank</title><a href="enum.State.html#variant.Plank" xlink:href="enum.State.html#variant.Plank"><ellipse fill="none" stroke="black" cx="223.49" cy="-90" rx="36.29" ry="18"/><text text-anchor="middle" x="223.49" y="-86.3" font-family="Times,serif" font-size="14.00">Plank</text></a></g><g id="edge4" class="edge"><title>Fountain&#45;&gt;Plank</title><a href="struct.Fountain.html#method.plank" xlink:href="struct.Fountain.html#method.plank"><path fill="none" stroke="black" d="M123.12,-146.33C142.88,-135.43 169.95,-120.51 190.99,-108.91"/><polygon fill="black" stroke="black" points="192.83,-111.9 199.9,-104 189.45,-105.77 192.83,-111.9"/></a></g><g id="edge5" class="edge"><title>Plank&#45;&gt;Tombstone</title><a href="struct.Plank.html#method.tombstone" xlink:href="struct.Plank.html#method.tombstone"><path fill="none" stroke="black" d="M197.54,-77.13C173.97,-66.34 138.82,-50.26 111.47,-37.75"/><polygon fill="black" stroke="black" points="112.7,-34.46 102.15,-33.48 109.78,-40.83 112.7,-34.46"/></a></g><g id="edge6" class="edge"><title>Plank&#45;&gt;UnmarkedGrave</title><a href="struct.Plank.html#method.unmarked_grave" xlink:href="struct.Plank.html#method.unmarked_grave"><path fill="none" stroke="black" d="M226.88,-72.05C228.42,-64.35 230.29,-55.03 232.02,-46.36"/><polygon fill="black" stroke="black" points="235.51,-46.77 234.04,-36.28 228.64,-45.39 235.51,-46.77"/></a></g><g id="diagram.PopulatedIsland" class="node"><title>PopulatedIsland</title><a href="enum.State.html#variant.PopulatedIsland" xlink:href="enum.State.html#variant.PopulatedIsland"><ellipse fill="none" stroke="black" cx="523.49" cy="-162" rx="85.29" ry="18"/><text text-anchor="middle" x="523.49" y="-158.3" font-family="Times,serif" font-size="14.00">PopulatedIsland</text></a></g><g id="diagram.Stream" class="node"><title>Stream</title><a href="enum.State.html#variant.Stream" xlink:href="enum.State.html#variant.Stream"><ellipse fill="none" stroke="black" cx="217.49" cy="-162" rx="44.69" ry="18"/><text text-anchor="middle" x="217.49" y="-158.3" font-family="Times,serif" font-size="14.00">Stream</text></a></g><g id="edge7" class="edge"><title>Stream&#45;&gt;BeautifulBridge</title><a href="struct.Stream.html#method.beautiful_bridge" xlink:href="struct.Stream.html#method.beautiful_bridge"><path fill="none" stroke="black" d="M191.22,-147.17C172.01,-137.06 145.62,-123.17 123.9,-111.74"/><polygon fill="black" stroke="black" points="125.38,-108.57 114.9,-107.01 122.12,-114.76 125.38,-108.57"/></a></g><g id="edge8" class="edge"><title>Stream&#45;&gt;Plank</title><a href="struct.Stream.html#method.plank" xlink:href="struct.Stream.html#method.plank"><path fill="none" stroke="black" d="M218.98,-143.7C219.64,-135.98 220.43,-126.71 221.17,-118.11"/><polygon fill="black" stroke="black" points="224.66,-118.37 222.03,-108.1 217.69,-117.77 224.66,-118.37"/></a></g></g></svg></div>*/
 pub mod example_machine {
     /// This machine exercises all vertex types, with and without data.
     #[derive(Clone, Debug)]
@@ -146,9 +38,12 @@ pub mod example_machine {
                 State::PopulatedIsland(_) => {
                     match &mut self.state {
                         State::PopulatedIsland(data) => Entry::PopulatedIsland(data),
-                        _ => {
-                            ::core::unreachable!(
-                                "state cannot change underneath us while we hold a mutable reference"
+                        found => {
+                            let found = found.kind();
+                            ::core::panic!(
+                                "`{}` was in `{}` when a handle for `{}` expected it",
+                                "ExampleMachine", found.as_str(), StateKind::PopulatedIsland
+                                .as_str(),
                             )
                         }
                     }
@@ -157,9 +52,12 @@ pub mod example_machine {
                 State::Tombstone(_) => {
                     match &mut self.state {
                         State::Tombstone(data) => Entry::Tombstone(data),
-                        _ => {
-                            ::core::unreachable!(
-                                "state cannot change underneath us while we hold a mutable reference"
+                        found => {
+                            let found = found.kind();
+                            ::core::panic!(
+                                "`{}` was in `{}` when a handle for `{}` expected it",
+                                "ExampleMachine", found.as_str(), StateKind::Tombstone
+                                .as_str(),
                             )
                         }
                     }
@@ -173,125 +71,242 @@ pub mod example_machine {
     pub enum State {
         /// A vertex with nonzero indegree and outdegree, with associated data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::Fountain`]
-        ///- [`State::Stream`]
+        /**This node is reachable from the following states:
+- [`State::Fountain`]
+- [`State::Stream`]
+
+This node can reach the following states:
+- [`State::Tombstone`]
+- [`State::UnmarkedGrave`]*/
         ///
-        ///This node can reach the following states:
-        ///- [`State::Tombstone`]
-        ///- [`State::UnmarkedGrave`]
+        ///See it in the [diagram](index.html#diagram.BeautifulBridge).
         BeautifulBridge(Vec<u8>),
         /// An isolated vertex with no data
+        ///
+        ///See it in the [diagram](index.html#diagram.DesertIsland).
         DesertIsland,
         /// A source with data
         ///
-        ///This node can reach the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        /**This node can reach the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.Fountain).
         Fountain(std::net::IpAddr),
         /// A vertex with nonzero indegree and outdegree, with no data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::Fountain`]
-        ///- [`State::Stream`]
+        /**This node is reachable from the following states:
+- [`State::Fountain`]
+- [`State::Stream`]
+
+This node can reach the following states:
+- [`State::Tombstone`]
+- [`State::UnmarkedGrave`]*/
         ///
-        ///This node can reach the following states:
-        ///- [`State::Tombstone`]
-        ///- [`State::UnmarkedGrave`]
+        ///See it in the [diagram](index.html#diagram.Plank).
         Plank,
         /// An isolated vertex with associated data
+        ///
+        ///See it in the [diagram](index.html#diagram.PopulatedIsland).
         PopulatedIsland(String),
         /// A source with no data
         ///
-        ///This node can reach the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        /**This node can reach the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.Stream).
         Stream,
         /// A sink with data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        /**This node is reachable from the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.Tombstone).
         Tombstone(char),
         /// A sink with no data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        /**This node is reachable from the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.UnmarkedGrave).
+        UnmarkedGrave,
+    }
+    impl State {
+        /// A [`mermaid`](https://mermaid.js.org/) state diagram of the state machine.
+        pub const MERMAID: &'static str = "stateDiagram-v2\n    accTitle: State machine ExampleMachine with 8 states and 8 transitions.\n    accDescr {\n        BeautifulBridge goes to Tombstone, UnmarkedGrave.\n        DesertIsland has no transitions.\n        Fountain goes to BeautifulBridge, Plank.\n        Plank goes to Tombstone, UnmarkedGrave.\n        PopulatedIsland has no transitions.\n        Stream goes to BeautifulBridge, Plank.\n        Tombstone has no transitions.\n        UnmarkedGrave has no transitions.\n    }\n    BeautifulBridge\n    DesertIsland\n    Fountain\n    Plank\n    PopulatedIsland\n    Stream\n    Tombstone\n    UnmarkedGrave\n    BeautifulBridge --> Tombstone\n    BeautifulBridge --> UnmarkedGrave\n    Fountain --> BeautifulBridge\n    Fountain --> Plank\n    Plank --> Tombstone\n    Plank --> UnmarkedGrave\n    Stream --> BeautifulBridge\n    Stream --> Plank\n";
+        /// A diagram of the state machine in [`DOT`](https://en.wikipedia.org/wiki/DOT_%28graph_description_language%29).
+        pub const DOT: &'static str = "strict digraph ExampleMachine { BeautifulBridge ; DesertIsland ; Fountain ; Plank ; PopulatedIsland ; Stream ; Tombstone ; UnmarkedGrave ; BeautifulBridge -> Tombstone ; BeautifulBridge -> UnmarkedGrave ; Fountain -> BeautifulBridge ; Fountain -> Plank ; Plank -> Tombstone ; Plank -> UnmarkedGrave ; Stream -> BeautifulBridge ; Stream -> Plank ; }";
+        /// Which state this is, without any data.
+        pub fn kind(&self) -> StateKind {
+            match self {
+                State::BeautifulBridge { .. } => StateKind::BeautifulBridge,
+                State::DesertIsland { .. } => StateKind::DesertIsland,
+                State::Fountain { .. } => StateKind::Fountain,
+                State::Plank { .. } => StateKind::Plank,
+                State::PopulatedIsland { .. } => StateKind::PopulatedIsland,
+                State::Stream { .. } => StateKind::Stream,
+                State::Tombstone { .. } => StateKind::Tombstone,
+                State::UnmarkedGrave { .. } => StateKind::UnmarkedGrave,
+            }
+        }
+        /// Call the method on `visitor` for the current state,
+        ///as an alternative to matching on [`ExampleMachine::entry`].
+        pub fn visit(&mut self, visitor: &mut impl Visitor) {
+            match &mut *self {
+                State::BeautifulBridge { .. } => {
+                    visitor.visit_beautiful_bridge(BeautifulBridge { inner: self })
+                }
+                State::DesertIsland => visitor.visit_desert_island(),
+                State::Fountain { .. } => {
+                    visitor.visit_fountain(Fountain { inner: self })
+                }
+                State::Plank { .. } => visitor.visit_plank(Plank { inner: self }),
+                State::PopulatedIsland(data) => visitor.visit_populated_island(data),
+                State::Stream { .. } => visitor.visit_stream(Stream { inner: self }),
+                State::Tombstone(data) => visitor.visit_tombstone(data),
+                State::UnmarkedGrave => visitor.visit_unmarked_grave(),
+            }
+        }
+    }
+    ///The variants of [`State`], without their data.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum StateKind {
+        ///See [`State::BeautifulBridge`].
+        BeautifulBridge,
+        ///See [`State::DesertIsland`].
+        DesertIsland,
+        ///See [`State::Fountain`].
+        Fountain,
+        ///See [`State::Plank`].
+        Plank,
+        ///See [`State::PopulatedIsland`].
+        PopulatedIsland,
+        ///See [`State::Stream`].
+        Stream,
+        ///See [`State::Tombstone`].
+        Tombstone,
+        ///See [`State::UnmarkedGrave`].
         UnmarkedGrave,
     }
+    impl StateKind {
+        /// The name of this state, as written in the definition.
+        pub const fn as_str(self) -> &'static str {
+            match self {
+                StateKind::BeautifulBridge => "BeautifulBridge",
+                StateKind::DesertIsland => "DesertIsland",
+                StateKind::Fountain => "Fountain",
+                StateKind::Plank => "Plank",
+                StateKind::PopulatedIsland => "PopulatedIsland",
+                StateKind::Stream => "Stream",
+                StateKind::Tombstone => "Tombstone",
+                StateKind::UnmarkedGrave => "UnmarkedGrave",
+            }
+        }
+    }
+    impl ::core::fmt::Display for StateKind {
+        fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
     /// Access to the current state with valid transitions for the state machine.
     ///
     ///See [`ExampleMachine::entry`].
     pub enum Entry<'a> {
         /// A vertex with nonzero indegree and outdegree, with associated data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::Fountain`]
-        ///- [`State::Stream`]
+        ///Holds a `Vec<u8>`.
+        ///
+        /**This node is reachable from the following states:
+- [`State::Fountain`]
+- [`State::Stream`]
+
+This node can reach the following states:
+- [`State::Tombstone`]
+- [`State::UnmarkedGrave`]*/
         ///
-        ///This node can reach the following states:
-        ///- [`State::Tombstone`]
-        ///- [`State::UnmarkedGrave`]
+        ///See it in the [diagram](index.html#diagram.BeautifulBridge).
         BeautifulBridge(BeautifulBridge<'a>),
         /// An isolated vertex with no data
+        ///
+        ///See it in the [diagram](index.html#diagram.DesertIsland).
         DesertIsland,
         /// A source with data
         ///
-        ///This node can reach the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        ///Holds a `std::net::IpAddr`.
+        ///
+        /**This node can reach the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.Fountain).
         Fountain(Fountain<'a>),
         /// A vertex with nonzero indegree and outdegree, with no data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::Fountain`]
-        ///- [`State::Stream`]
+        /**This node is reachable from the following states:
+- [`State::Fountain`]
+- [`State::Stream`]
+
+This node can reach the following states:
+- [`State::Tombstone`]
+- [`State::UnmarkedGrave`]*/
         ///
-        ///This node can reach the following states:
-        ///- [`State::Tombstone`]
-        ///- [`State::UnmarkedGrave`]
+        ///See it in the [diagram](index.html#diagram.Plank).
         Plank(Plank<'a>),
         /// An isolated vertex with associated data
+        ///
+        ///Holds a `String`.
+        ///
+        ///See it in the [diagram](index.html#diagram.PopulatedIsland).
         PopulatedIsland(&'a mut String),
         /// A source with no data
         ///
-        ///This node can reach the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        /**This node can reach the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.Stream).
         Stream(Stream<'a>),
         /// A sink with data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        ///Holds a `char`.
+        ///
+        /**This node is reachable from the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.Tombstone).
         Tombstone(&'a mut char),
         /// A sink with no data
         ///
-        ///This node is reachable from the following states:
-        ///- [`State::BeautifulBridge`]
-        ///- [`State::Plank`]
+        /**This node is reachable from the following states:
+- [`State::BeautifulBridge`]
+- [`State::Plank`]*/
+        ///
+        ///See it in the [diagram](index.html#diagram.UnmarkedGrave).
         UnmarkedGrave,
     }
-    /// Transition the state machine by calling the following methods:
+    ///Transition the state machine by calling the following methods:
     ///- [`BeautifulBridge::tombstone`]
     ///- [`BeautifulBridge::unmarked_grave`]
     pub struct BeautifulBridge<'a> {
         inner: &'a mut State,
     }
-    /// Transition the state machine by calling the following methods:
+    ///Transition the state machine by calling the following methods:
     ///- [`Fountain::beautiful_bridge`]
     ///- [`Fountain::plank`]
     pub struct Fountain<'a> {
         inner: &'a mut State,
     }
-    /// Transition the state machine by calling the following methods:
+    ///Transition the state machine by calling the following methods:
     ///- [`Plank::tombstone`]
     ///- [`Plank::unmarked_grave`]
     pub struct Plank<'a> {
         inner: &'a mut State,
     }
-    /// Transition the state machine by calling the following methods:
+    ///Transition the state machine by calling the following methods:
     ///- [`Stream::beautiful_bridge`]
     ///- [`Stream::plank`]
     pub struct Stream<'a> {
@@ -302,9 +317,12 @@ pub mod example_machine {
         pub fn get(&self) -> &Vec<u8> {
             match &self.inner {
                 State::BeautifulBridge(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::BeautifulBridge
+                        .as_str(),
                     )
                 }
             }
@@ -313,9 +331,12 @@ pub mod example_machine {
         pub fn get_mut(&mut self) -> &mut Vec<u8> {
             match self.inner {
                 State::BeautifulBridge(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                ref found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::BeautifulBridge
+                        .as_str(),
                     )
                 }
             }
@@ -327,9 +348,12 @@ pub mod example_machine {
             let prev = ::core::mem::replace(self.inner, State::Tombstone(next));
             match prev {
                 State::BeautifulBridge(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::BeautifulBridge
+                        .as_str(),
                     )
                 }
             }
@@ -340,9 +364,12 @@ pub mod example_machine {
             let prev = ::core::mem::replace(self.inner, State::UnmarkedGrave);
             match prev {
                 State::BeautifulBridge(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::BeautifulBridge
+                        .as_str(),
                     )
                 }
             }
@@ -353,9 +380,11 @@ pub mod example_machine {
         pub fn get(&self) -> &std::net::IpAddr {
             match &self.inner {
                 State::Fountain(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::Fountain.as_str(),
                     )
                 }
             }
@@ -364,9 +393,11 @@ pub mod example_machine {
         pub fn get_mut(&mut self) -> &mut std::net::IpAddr {
             match self.inner {
                 State::Fountain(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                ref found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::Fountain.as_str(),
                     )
                 }
             }
@@ -378,9 +409,11 @@ pub mod example_machine {
             let prev = ::core::mem::replace(self.inner, State::BeautifulBridge(next));
             match prev {
                 State::Fountain(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::Fountain.as_str(),
                     )
                 }
             }
@@ -394,9 +427,11 @@ pub mod example_machine {
             let prev = ::core::mem::replace(self.inner, State::Plank);
             match prev {
                 State::Fountain(data) => data,
-                _ => {
-                    ::core::unreachable!(
-                        "this variant is only created when state is known to match, and we hold a mutable reference to state"
+                found => {
+                    let found = found.kind();
+                    ::core::panic!(
+                        "`{}` was in `{}` when a handle for `{}` expected it",
+                        "ExampleMachine", found.as_str(), StateKind::Fountain.as_str(),
                     )
                 }
             }
@@ -449,5 +484,27 @@ pub mod example_machine {
             f.debug_struct(::core::stringify!(Stream)).finish_non_exhaustive()
         }
     }
+    ///Handle each state of the state machine in a separate method, see [`State::visit`].
+    ///
+    /// Every method does nothing by default,
+    /// so implementations continue to compile when states are added.
+    pub trait Visitor {
+        ///Called by [`State::visit`] in [`State::BeautifulBridge`].
+        fn visit_beautiful_bridge(&mut self, _transition: BeautifulBridge<'_>) {}
+        ///Called by [`State::visit`] in [`State::DesertIsland`].
+        fn visit_desert_island(&mut self) {}
+        ///Called by [`State::visit`] in [`State::Fountain`].
+        fn visit_fountain(&mut self, _transition: Fountain<'_>) {}
+        ///Called by [`State::visit`] in [`State::Plank`].
+        fn visit_plank(&mut self, _transition: Plank<'_>) {}
+        ///Called by [`State::visit`] in [`State::PopulatedIsland`].
+        fn visit_populated_island(&mut self, _data: &mut String) {}
+        ///Called by [`State::visit`] in [`State::Stream`].
+        fn visit_stream(&mut self, _transition: Stream<'_>) {}
+        ///Called by [`State::visit`] in [`State::Tombstone`].
+        fn visit_tombstone(&mut self, _data: &mut char) {}
+        ///Called by [`State::visit`] in [`State::UnmarkedGrave`].
+        fn visit_unmarked_grave(&mut self) {}
+    }
 }
 
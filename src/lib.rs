@@ -54,15 +54,46 @@
 //!
 //! # Cargo features
 //!
-//! - `macros` (default): Include the [`dot`] and [`dsl`] macros.
+//! - `macros` (default): Include the [`dot`], [`dsl`] and [`include_fsm`] macros.
 //! - `svg` (default): The macros will shell out to `dot`, if available, and
 //!   generate a diagram of the state machine for documentation.
+//!   This may be overridden for each machine with `#[fsmentry(svg = auto | force | omit)]`,
+//!   whether or not the feature is enabled.
+//!   If `dot` isn't installed, the macros only warn about it for machines with
+//!   `#[fsmentry(svg = auto)]` or a configured binary,
+//!   since the feature is enabled by default.
+//!   Clicking on a state or transition in the diagram navigates to its documentation.
+//!   Use `#[fsmentry(dot_path = "...")]` or the `FSMENTRY_DOT_PATH` environment variable
+//!   to choose a graphviz binary other than `dot` on the `PATH`.
+//!   Graphviz is killed if it takes longer than 10 seconds,
+//!   which may be changed with `#[fsmentry(dot_timeout = <seconds>)]`.
+//!   Diagrams are minified, and left out with a warning if they are larger than 128 KiB,
+//!   which may be changed with `#[fsmentry(svg_max_size = <bytes>)]`.
+//!   Without a diagram, the transitions are listed in plain text instead,
+//!   unless `#[fsmentry(diagram_fallback = none)]`.
 //! - `std` (default): Includes the [`FSMGenerator`], for custom codegen tools.
+//! - `nightly`: The macros emit warnings (like [`FSMGenerator::warnings`]) as native
+//!   compiler diagnostics. Without it, they appear as uses of a deprecated item.
 //! - `cli`: This does not affect the library, but if you
 //!   ```console
 //!   cargo install fsmentry --features=cli
 //!   ```
-//!   You will get an `fsmentry` binary that you can use to generate code.
+//!   You will get an `fsmentry` binary that you can use to generate code,
+//!   and an `fsmentry-lsp` language server for editing the DSL in standalone files.
+//!   `fsmentry --emit tree-sitter` prints a tree-sitter grammar for highlighting them.
+//!   When the generated code is committed, `fsmentry --with-test <file>` appends a test
+//!   which fails once it no longer matches its definition.
+//!   This is the way to go for very large machines, which are slow to expand on every build:
+//!   the macros warn about more than 256 states or 1024 transitions,
+//!   which may be changed with `#[fsmentry(max_states = <n>, max_edges = <n>)]`.
+//!   `fsmentry --emit report` prints how many items each machine generates, as JSON,
+//!   to catch unexpected growth of the generated API.
+//!   Machines designed in the [Stately](https://stately.ai) editor may be read with
+//!   `fsmentry --language xstate`, and written back with `fsmentry --emit xstate`.
+//!   While designing a machine, `fsmentry serve <file>` serves a page with its diagram
+//!   and generated code, which reloads whenever the file is saved.
+//!   `fsmentry new --template <template> <name>` starts a machine from a template,
+//!   like `request-lifecycle`, `connection` or `job-queue`, with a rust file to include it.
 //!
 //! # Advanced usage
 //!
@@ -182,12 +213,14 @@ pub mod example;
 #[cfg(feature = "std")]
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 #[doc(inline)]
-pub use fsmentry_core::FSMGenerator;
+pub use fsmentry_core::{
+    FSMGenerator, IncludeSvg, RegistryEntry, RenderError, RenderJob, Report, Warning,
+};
 
 #[cfg(feature = "macros")]
 #[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
 #[doc(inline)]
-pub use fsmentry_macros::{dot, dsl};
+pub use fsmentry_macros::{dot, dsl, include_fsm};
 
 #[cfg(test)]
 mod tests {
@@ -210,6 +243,102 @@ mod tests {
             .stdout(include_str!("example.rs"));
     }
 
+    /// Prints how long `dsl!` for ever larger machines takes to expand and check,
+    /// which `max_states` and `max_edges` warn about.
+    #[test]
+    #[ignore = "slow, and only prints measurements"]
+    fn compile_time() {
+        let dir = std::env::temp_dir().join("fsmentry-compile-time");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"compile-time\"\nversion = \"0.0.0\"\nedition = \"2021\"\n\n\
+                 [dependencies]\nfsmentry = {{ path = {:?} }}\n\n[workspace]\n",
+                env!("CARGO_MANIFEST_DIR")
+            ),
+        )
+        .unwrap();
+        let check = |source: String| {
+            std::fs::write(dir.join("src/lib.rs"), source).unwrap();
+            let start = std::time::Instant::now();
+            let status = std::process::Command::new(env!("CARGO"))
+                .args(["check", "--quiet"])
+                .current_dir(&dir)
+                .env("CARGO_TARGET_DIR", dir.join("target"))
+                .status()
+                .unwrap();
+            assert!(status.success());
+            start.elapsed()
+        };
+        // so only the machine is timed, not building fsmentry
+        check(String::new());
+        for len in [64, 256, 1024] {
+            // a chain, where every state may also give up
+            let mut dsl =
+                String::from("fsmentry::dsl! {\n#[fsmentry(svg = omit)]\npub Machine {\n");
+            for i in 1..len {
+                dsl.push_str(&format!("    S{} -> S{};\n", i - 1, i));
+            }
+            dsl.push_str("    _ -> GaveUp;\n}\n}\n");
+            println!(
+                "{} states, {} transitions: {:?}",
+                len + 1,
+                2 * len - 1,
+                check(dsl)
+            );
+        }
+    }
+
+    #[test]
+    fn xstate() {
+        // the names and order of states and events survive a round trip
+        let exported = r#"{
+  "id": "TrafficLight",
+  "initial": "Red",
+  "states": {
+    "Red": {
+      "on": {
+        "GO": { "target": "Green" }
+      }
+    },
+    "Green": {
+      "on": {
+        "SLOW_DOWN": { "target": "Amber" }
+      }
+    },
+    "Amber": {
+      "on": {
+        "stop": { "target": "Red", "description": "Halt" }
+      }
+    }
+  }
+}
+"#;
+        let round_trip = |input: &str| {
+            assert_cmd::Command::cargo_bin("fsmentry")
+                .unwrap()
+                .write_stdin(input)
+                .args(["--language=xstate", "--emit=xstate"])
+                .assert()
+                .success()
+                .stdout(exported);
+        };
+        round_trip(exported);
+        // other ways of writing transitions are read as the same machine
+        round_trip(
+            r##"{
+                "id": "TrafficLight",
+                "initial": "Red",
+                "states": {
+                    "Red": { "on": { "GO": "Green" } },
+                    "Green": { "on": { "SLOW_DOWN": { "target": "#TrafficLight.Amber" } } },
+                    "Amber": { "on": { "stop": [{ "target": "Red", "description": "Halt" }] } }
+                }
+            }"##,
+        );
+    }
+
     #[test]
     fn readme() {
         assert!(
@@ -0,0 +1,11 @@
+#![allow(dead_code)]
+// only the state is generated, so there's no machine to import
+fsmentry::dsl! {
+    #[fsmentry(emit = state_only)]
+    pub Light {
+        Red -> Green -> Red;
+    }
+}
+use light::Light;
+
+fn main() {}
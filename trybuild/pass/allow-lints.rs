@@ -0,0 +1,20 @@
+// `Locked` is never constructed
+#![deny(dead_code)]
+
+fsmentry::dsl! {
+    #[fsmentry(allow(dead_code))]
+    Door {
+        Open -> Closed -> Open;
+        Closed -> Locked;
+    }
+}
+use door::{Door, Entry, State};
+
+fn main() {
+    let mut door = Door::new(State::Open);
+    match door.entry() {
+        Entry::Open(it) => it.closed(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(door.state(), State::Closed));
+}
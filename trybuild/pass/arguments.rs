@@ -0,0 +1,37 @@
+fsmentry::dsl! {
+    pub Ticket {
+        Open: u32;
+        Open -close(reason: String, by: &'static str)-> Closed -reopen()-> Open;
+    }
+}
+use ticket::{Entry, Hooks, State, Ticket};
+
+#[derive(Default)]
+struct Audit(Vec<String>);
+
+impl Hooks for Audit {
+    fn close(&mut self, from: &u32, reason: String, by: &'static str) {
+        self.0
+            .push(format!("{} closed by {}: {}", from, by, reason))
+    }
+    fn reopen(&mut self, to: &u32) {
+        self.0.push(format!("reopened as {}", to))
+    }
+}
+
+fn main() {
+    let mut audit = Audit::default();
+    let mut ticket = Ticket::new(State::Open(1));
+    match ticket.entry() {
+        Entry::Open(it) => {
+            let id = it.close(String::from("duplicate"), "alice", &mut audit);
+            assert_eq!(id, 1);
+        }
+        Entry::Closed(_) => unreachable!(),
+    }
+    match ticket.entry() {
+        Entry::Closed(it) => it.reopen(2, &mut audit),
+        Entry::Open(_) => unreachable!(),
+    }
+    assert_eq!(audit.0, ["1 closed by alice: duplicate", "reopened as 2"]);
+}
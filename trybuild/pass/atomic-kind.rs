@@ -0,0 +1,23 @@
+use core::sync::atomic::Ordering;
+
+fsmentry::dsl! {
+    #[fsmentry(atomic_kind = crate::LIGHT)]
+    pub TrafficLight {
+        Red -> Green -> Red;
+        Green: u8;
+    }
+}
+use traffic_light::{AtomicStateKind, Entry, State, StateKind, TrafficLight};
+
+static LIGHT: AtomicStateKind = AtomicStateKind::new(StateKind::Red);
+
+fn main() {
+    let mut light = TrafficLight::new(State::Green(1));
+    assert_eq!(LIGHT.load(Ordering::Acquire), StateKind::Green);
+    match light.entry() {
+        Entry::Green(it) => assert_eq!(it.red(), 1),
+        Entry::Red(_) => unreachable!(),
+    }
+    assert_eq!(LIGHT.load(Ordering::Acquire), StateKind::Red);
+    assert_eq!(light.state().kind(), StateKind::Red);
+}
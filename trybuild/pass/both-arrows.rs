@@ -0,0 +1,28 @@
+fsmentry::dsl! {
+    pub Door {
+        Closed <-open/close-> Open;
+        Closed <-> Locked;
+    }
+}
+use door::{Door, Entry, State};
+
+fn main() {
+    let mut door = Door::new(State::Closed);
+    match door.entry() {
+        Entry::Closed(it) => it.locked(),
+        _ => unreachable!(),
+    }
+    match door.entry() {
+        Entry::Locked(it) => it.closed(),
+        _ => unreachable!(),
+    }
+    match door.entry() {
+        Entry::Closed(it) => it.open(),
+        _ => unreachable!(),
+    }
+    match door.entry() {
+        Entry::Open(it) => it.close(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(door.state(), State::Closed));
+}
@@ -0,0 +1,39 @@
+fsmentry::dsl! {
+    pub Bridge {
+        Approaching: u8;
+        Crossing: u8;
+        Approaching -on(self.log_crossing)-> Crossing -on(log_left)-> Departed;
+        Crossing -> Approaching;
+    }
+}
+use bridge::{Bridge, Entry, Hooks, State};
+
+#[derive(Default)]
+struct Log(Vec<String>);
+
+impl Hooks for Log {
+    fn log_crossing(&mut self, from: &u8, to: &u8) {
+        self.0.push(format!("crossing {} -> {}", from, to))
+    }
+    fn log_left(&mut self, from: &u8) {
+        self.0.push(format!("departed {}", from))
+    }
+}
+
+fn main() {
+    let mut log = Log::default();
+    let mut bridge = Bridge::new(State::Approaching(1));
+    loop {
+        match bridge.entry() {
+            Entry::Approaching(it) => {
+                let before = *it.get();
+                it.crossing(before + 1, &mut log);
+            }
+            Entry::Crossing(it) => {
+                it.departed(&mut log);
+            }
+            Entry::Departed => break,
+        }
+    }
+    assert_eq!(log.0, ["crossing 1 -> 2", "departed 2"]);
+}
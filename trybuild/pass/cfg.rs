@@ -0,0 +1,19 @@
+fsmentry::dsl! {
+    pub Document {
+        Draft -> Published;
+        #[cfg(any())]
+        Draft -> ForceApproved;
+        #[cfg(all())]
+        Published -> Archived;
+    }
+}
+use document::{Document, Entry, State};
+
+fn main() {
+    let mut document = Document::new(State::Published);
+    match document.entry() {
+        Entry::Published(it) => it.archived(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(document.state(), State::Archived));
+}
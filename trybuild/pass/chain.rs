@@ -0,0 +1,42 @@
+fsmentry::dsl! {
+    #[fsmentry(chain = true, pool = true)]
+    pub Order {
+        Placed: String;
+        Packed: u32;
+        Delivered: bool;
+        Placed -pack-> Packed -ship-> Shipped -deliver-> Delivered;
+    }
+}
+use order::{Entry, MachinePool, Order, State, StateKind};
+
+fn main() {
+    let mut order = Order::new(State::Placed(String::from("books")));
+    let Entry::Placed(it) = order.entry() else {
+        unreachable!()
+    };
+    // data from the old state comes first
+    let (item, Entry::Packed(it)) = it.pack(3) else {
+        unreachable!()
+    };
+    assert_eq!(item, "books");
+    let (boxes, Entry::Shipped(it)) = it.ship() else {
+        unreachable!()
+    };
+    assert_eq!(boxes, 3);
+    let Entry::Delivered(on_time) = it.deliver(false) else {
+        unreachable!()
+    };
+    *on_time = true;
+    assert!(matches!(order.state(), State::Delivered(true)));
+
+    // handles from a pool keep its index up to date
+    let mut pool = MachinePool::new();
+    let id = pool.insert(Order::new(State::Placed(String::from("tea"))));
+    if let Some(Entry::Placed(it)) = pool.entry(id) {
+        let (_, Entry::Packed(it)) = it.pack(1) else {
+            unreachable!()
+        };
+        it.ship();
+    }
+    assert_eq!(pool.count_in_state(StateKind::Shipped), 1);
+}
@@ -0,0 +1,41 @@
+#[derive(Debug, Default)]
+pub struct Stats {
+    opened: u32,
+}
+
+fsmentry::dsl! {
+    #[fsmentry(events = true)]
+    pub Door {
+        context: super::Stats;
+        Open -close-> Closed -open-> Open;
+    }
+}
+use door::{Door, Entry, Event, State, Visitor};
+
+struct Opener;
+
+impl Visitor for Opener {
+    fn visit_closed(&mut self, mut transition: door::Closed<'_>) {
+        transition.context_mut().opened += 1;
+        transition.open()
+    }
+}
+
+fn main() {
+    let mut door = Door::new(State::Closed, Stats::default());
+    match door.entry() {
+        Entry::Closed(mut it) => {
+            it.context_mut().opened += 1;
+            it.open()
+        }
+        _ => unreachable!(),
+    }
+    assert_eq!(door.context().opened, 1);
+
+    let (state, stats) = (&mut State::Closed, &mut Stats::default());
+    state.visit(&mut Opener, stats);
+    state.handle(Event::Close, stats).unwrap();
+    state.handle(Event::Open, stats).unwrap();
+    assert!(matches!(state, State::Open));
+    assert_eq!(stats.opened, 1);
+}
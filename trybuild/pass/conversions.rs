@@ -0,0 +1,25 @@
+use std::borrow::Borrow;
+
+fsmentry::dsl! {
+    #[fsmentry(conversions = [AsRef, BorrowMut, DerefMut])]
+    pub Counter {
+        Counting: u32;
+        Counting -> Done;
+    }
+}
+use counter::{Counter, Entry, State};
+
+fn main() {
+    let mut counter = Counter::new(State::Counting(1));
+    match counter.entry() {
+        Entry::Counting(mut it) => {
+            *it += 1;
+            let by_ref: &u32 = it.as_ref();
+            let by_borrow: &u32 = it.borrow();
+            assert_eq!((*by_ref, *by_borrow), (2, 2));
+            assert_eq!(it.pow(2), 4);
+        }
+        Entry::Done => unreachable!(),
+    }
+    assert!(matches!(counter.state(), State::Counting(2)));
+}
@@ -0,0 +1,15 @@
+fsmentry::dsl! {
+    #[derive(Debug)]
+    #[fsmentry(derive(state: [Clone, PartialEq], entry: [Debug]))]
+    pub TrafficLight {
+        Red -> Green -> Red;
+        Green: u8;
+    }
+}
+use traffic_light::{State, TrafficLight};
+
+fn main() {
+    let mut light = TrafficLight::new(State::Green(1));
+    assert_eq!(light.state().clone(), State::Green(1));
+    assert_eq!(format!("{:?}", light.entry()), "Green(Green { .. })");
+}
@@ -0,0 +1,14 @@
+fsmentry::dsl! {
+    pub Door {
+        Open -> Closed -> Open;
+    }
+}
+use door::State;
+
+fn main() {
+    assert_eq!(
+        State::MERMAID,
+        "stateDiagram-v2\n    Closed\n    Open\n    Closed --> Open\n    Open --> Closed\n"
+    );
+    assert!(State::DOT.starts_with("strict digraph Door {"));
+}
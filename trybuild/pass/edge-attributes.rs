@@ -0,0 +1,23 @@
+fsmentry::dsl! {
+    #[fsmentry(events = true)]
+    pub Switch {
+        On -off-> Off;
+        #[deprecated = "use `Switch::entry` and `on` instead"]
+        #[inline]
+        Off -toggle-> On;
+        Off -on-> On;
+    }
+}
+use switch::{Entry, Event, State, Switch};
+
+fn main() {
+    let mut switch = Switch::new(State::Off);
+    match switch.entry() {
+        Entry::Off(it) => it.on(),
+        Entry::On(it) => it.off(),
+    }
+    let mut state = State::On;
+    state.handle(Event::Off).unwrap();
+    state.handle(Event::Toggle).unwrap();
+    assert!(matches!(state, State::On));
+}
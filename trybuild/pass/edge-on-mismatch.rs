@@ -0,0 +1,24 @@
+fsmentry::dsl! {
+    pub Packet {
+        Header: [u8; 4];
+        Body: Vec<u8>;
+        Header -> Body;
+        // the hot path
+        #[fsmentry(on_mismatch = unchecked)]
+        Body -> Header;
+    }
+}
+use packet::{Entry, Packet, State};
+
+fn main() {
+    let mut packet = Packet::new(State::Header([0; 4]));
+    match packet.entry() {
+        Entry::Header(it) => assert_eq!(it.body(vec![1, 2]), [0; 4]),
+        _ => unreachable!(),
+    }
+    match packet.entry() {
+        Entry::Body(it) => assert_eq!(it.header([1; 4]), [1, 2]),
+        _ => unreachable!(),
+    }
+    assert!(matches!(packet.state(), State::Header([1, 1, 1, 1])));
+}
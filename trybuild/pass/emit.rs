@@ -0,0 +1,48 @@
+mod light {
+    fsmentry::dsl! {
+        #[derive(Debug)]
+        #[fsmentry(emit = state_only)]
+        pub Light {
+            Green: u8;
+            Red -> Green -> Red;
+        }
+    }
+    use self::light::State;
+
+    // hand-written transitions
+    pub fn next(state: &mut State) {
+        *state = match state {
+            State::Red => State::Green(0),
+            State::Green(_) => State::Red,
+        }
+    }
+}
+
+mod door {
+    // a hand-written state enum, matching the definition
+    #[derive(Debug)]
+    pub enum State {
+        Open,
+        Closed,
+    }
+
+    fsmentry::dsl! {
+        #[fsmentry(emit = entry_only)]
+        pub Door {
+            Open -> Closed -> Open;
+        }
+    }
+}
+
+fn main() {
+    let mut state = light::light::State::Red;
+    light::next(&mut state);
+    assert_eq!(state.kind(), light::light::StateKind::Green);
+
+    let mut door = door::door::Door::new(door::State::Open);
+    match door.entry() {
+        door::door::Entry::Open(it) => it.closed(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(door.state(), door::State::Closed));
+}
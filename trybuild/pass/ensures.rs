@@ -0,0 +1,30 @@
+fsmentry::dsl! {
+    pub Upload {
+        Empty;
+        Buffered: Vec<u8>;
+        Sent { bytes: usize }
+        Empty -ensures(!data.is_empty())-> Buffered -ensures(*bytes > 0)-> Sent;
+    }
+}
+use upload::{Entry, State, Upload};
+
+fn main() {
+    let mut upload = Upload::new(State::Empty);
+    match upload.entry() {
+        Entry::Empty(it) => it.buffered(vec![1, 2, 3]),
+        _ => unreachable!(),
+    }
+    match upload.entry() {
+        Entry::Buffered(it) => assert_eq!(it.sent(3), vec![1, 2, 3]),
+        _ => unreachable!(),
+    }
+
+    std::panic::set_hook(Box::new(|_| {}));
+    let panicked = std::panic::catch_unwind(|| {
+        let mut upload = Upload::new(State::Empty);
+        if let Entry::Empty(it) = upload.entry() {
+            it.buffered(vec![])
+        }
+    });
+    assert_eq!(panicked.is_err(), cfg!(debug_assertions));
+}
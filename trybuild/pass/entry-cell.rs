@@ -0,0 +1,42 @@
+use std::{cell::RefCell, rc::Rc};
+
+fsmentry::dsl! {
+    #[fsmentry(entry_cell = true)]
+    pub Light {
+        Off -> On -> Off;
+    }
+    #[fsmentry(entry_cell = true, entry_fn = "transition", entry_mod = pub entry)]
+    pub Switch {
+        Up -> Down -> Up;
+    }
+}
+use light::{Entry, Light, State};
+
+fn main() {
+    let light = Rc::new(RefCell::new(Light::new(State::Off)));
+    // e.g. held by a button's click handler
+    let toggle = {
+        let light = light.clone();
+        move || {
+            let mut guard = Light::entry_cell(&light);
+            match guard.entry() {
+                Entry::Off(it) => it.on(),
+                Entry::On(it) => it.off(),
+            }
+        }
+    };
+    toggle();
+    assert!(matches!(light.borrow().state(), State::On));
+    toggle();
+    assert!(matches!(Light::entry_cell(&light).state(), State::Off));
+
+    // named after `entry_fn`
+    let switch = RefCell::new(switch::Switch::new(switch::State::Up));
+    let mut guard: entry::EntryGuard = switch::Switch::transition_cell(&switch);
+    match guard.transition() {
+        entry::Entry::Up(it) => it.down(),
+        entry::Entry::Down(it) => it.up(),
+    }
+    drop(guard);
+    assert!(matches!(switch.borrow().state(), switch::State::Down));
+}
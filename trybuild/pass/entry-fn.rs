@@ -0,0 +1,23 @@
+fsmentry::dsl! {
+    #[fsmentry(entry_fn = "drive")]
+    pub Car {
+        Parked -> Moving -> Parked;
+    }
+}
+use car::{Car, Entry, State};
+
+impl Car {
+    #[allow(dead_code)]
+    fn entry(&self) -> &'static str {
+        "a method of our own"
+    }
+}
+
+fn main() {
+    let mut car = Car::new(State::Parked);
+    match car.drive() {
+        Entry::Parked(it) => it.moving(),
+        Entry::Moving(_) => unreachable!(),
+    }
+    assert!(matches!(car.state(), State::Moving));
+}
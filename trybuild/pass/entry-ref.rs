@@ -0,0 +1,46 @@
+fsmentry::dsl! {
+    #[fsmentry(entry_ref = true, state_mod = pub state)]
+    pub Download {
+        Queued;
+        Running: u64;
+        Failed { reason: String, retries: u8 }
+        Queued -> Running -> Done;
+        Running -> Failed -retry-> Queued;
+        #[cfg(test)]
+        Queued -> Done;
+    }
+}
+use state::{Download, EntryRef, State, StateKind};
+
+/// Inspection only needs a shared reference.
+fn describe(download: &Download) -> String {
+    match download.state().entry_ref() {
+        EntryRef::Queued | EntryRef::Done => String::new(),
+        EntryRef::Running(bytes) => format!("{} bytes", bytes),
+        EntryRef::Failed { reason, retries } => format!("{} ({} retries)", reason, retries),
+    }
+}
+
+fn main() {
+    let download = Download::new(State::Running(1024));
+    assert_eq!(describe(&download), "1024 bytes");
+    let entry = download.state().entry_ref();
+    assert_eq!(entry.kind(), StateKind::Running);
+    assert_eq!(entry.successors(), [StateKind::Done, StateKind::Failed]);
+
+    let download = Download::new(State::Failed {
+        reason: String::from("timed out"),
+        retries: 2,
+    });
+    assert_eq!(describe(&download), "timed out (2 retries)");
+    assert_eq!(
+        download.state().entry_ref().successors(),
+        [StateKind::Queued]
+    );
+    // edges which are configured out aren't successors
+    let download = Download::new(State::Queued);
+    assert_eq!(
+        download.state().entry_ref().successors(),
+        [StateKind::Running]
+    );
+}
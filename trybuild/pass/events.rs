@@ -0,0 +1,45 @@
+fsmentry::dsl! {
+    #[fsmentry(events = true, derive(event: [Debug, Clone]))]
+    pub Door {
+        Locked: u32;
+        Open -close-> Closed -open-> Open;
+        Closed -lock(code: u32)-> Locked -on(self.check)-> Closed;
+    }
+}
+use door::{Door, Event, Hooks, State, StateKind};
+
+struct Checker;
+
+impl Hooks for Checker {
+    fn check(&mut self, from: &u32) {
+        assert_eq!(*from, 1234)
+    }
+    fn lock(&mut self, to: &u32, code: u32) {
+        assert_eq!((*to, code), (1234, 1))
+    }
+}
+
+fn main() {
+    let mut door = Door::new(State::Open);
+    let door = door.state_mut();
+    door.handle(Event::Close, &mut Checker).unwrap();
+    door.handle(
+        Event::Lock {
+            next: 1234,
+            code: 1,
+        },
+        &mut Checker,
+    )
+    .unwrap();
+    assert!(matches!(door, State::Locked(1234)));
+
+    let e = door.handle(Event::Open, &mut Checker).unwrap_err();
+    assert_eq!(e.state, StateKind::Locked);
+    assert_eq!(e.event.as_str(), "open");
+    assert!(!e.precondition);
+    assert_eq!(e.to_string(), "`Locked` has no transition `open`");
+
+    door.handle(Event::Closed, &mut Checker).unwrap();
+    door.handle(e.event, &mut Checker).unwrap();
+    assert!(matches!(door, State::Open));
+}
@@ -0,0 +1,29 @@
+fsmentry::dsl! {
+    #[fsmentry(expose_handle_field = pub(crate))]
+    pub Door {
+        Open: u8;
+        Open -> Closed -> Open;
+    }
+}
+use door::{Closed, Door, Entry, Open, State};
+
+/// Reopen a closed door, without going through the entry api.
+fn reopen(state: &mut State) {
+    if let State::Closed = state {
+        // SAFETY: we just checked the state
+        let closed = unsafe { Closed::from_raw(state) };
+        closed.open(1)
+    }
+}
+
+fn main() {
+    let mut door = Door::new(State::Closed);
+    reopen(door.state_mut());
+    match door.entry() {
+        Entry::Open(it) => {
+            let it: Open = it;
+            assert!(matches!(it.inner, State::Open(1)));
+        }
+        Entry::Closed(_) => unreachable!(),
+    }
+}
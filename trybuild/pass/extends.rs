@@ -0,0 +1,21 @@
+fsmentry::dsl! {
+    pub Door {
+        Closed -> Open -> Closed;
+    }
+    pub LockableDoor {
+        extends Door;
+        /// The code to unlock with.
+        Locked: u16;
+        Closed -> Locked -> Closed;
+    }
+}
+
+fn main() {
+    let mut door = lockable_door::LockableDoor::new(lockable_door::State::Closed);
+    match door.entry() {
+        lockable_door::Entry::Closed(it) => it.locked(1234),
+        _ => unreachable!(),
+    }
+    assert!(matches!(door.state(), lockable_door::State::Locked(1234)));
+    let _ = door::Door::new(door::State::Open);
+}
@@ -0,0 +1,20 @@
+fsmentry::dsl! {
+    #[fsmentry(fmt = false)]
+    pub Lean {
+        Idle -> Busy -> Idle;
+    }
+}
+fsmentry::dsl! {
+    pub Chatty {
+        Idle -> Busy -> Idle;
+    }
+}
+
+const IDLE: &str = lean::StateKind::Idle.as_str();
+
+fn main() {
+    assert_eq!(IDLE, "Idle");
+    assert_eq!(lean::State::Busy.kind().as_str(), "Busy");
+    assert_eq!(chatty::StateKind::Busy.to_string(), "Busy");
+    assert_eq!(format!("{:?}", chatty::StateKind::Idle), "Idle");
+}
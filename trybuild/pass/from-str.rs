@@ -0,0 +1,24 @@
+fsmentry::dsl! {
+    #[fsmentry(from_str = true, state_mod = pub state)]
+    pub Light {
+        Broken: String;
+        Off -> On -> Broken;
+    }
+}
+use state::{Light, ParseStateError, State, StateKind};
+
+fn main() {
+    let mut light = Light::new("Off".parse().unwrap());
+    assert_eq!(light.state().to_string(), "Off");
+    if let light::Entry::Off(it) = light.entry() {
+        it.on()
+    }
+    assert_eq!(light.state().to_string(), "On");
+
+    assert_eq!("Broken".parse::<StateKind>(), Ok(StateKind::Broken));
+    // the data in `Broken` can't come from its name
+    let e: ParseStateError = "Broken".parse::<State>().err().unwrap();
+    assert_eq!(e.expected(), ["Off", "On"]);
+    assert_eq!(e.to_string(), "expected one of `Off`, `On`");
+    assert!("off".parse::<StateKind>().is_err());
+}
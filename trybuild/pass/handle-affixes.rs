@@ -0,0 +1,23 @@
+fsmentry::dsl! {
+    #[fsmentry(handle_suffix = "Handle")]
+    pub Flow {
+        Entry -> Visitor -> Done;
+    }
+}
+use flow::{Entry, EntryHandle, Flow, State};
+
+fn main() {
+    let mut flow = Flow::new(State::Entry);
+    match flow.entry() {
+        Entry::Entry(it) => {
+            let it: EntryHandle = it;
+            it.visitor()
+        }
+        _ => unreachable!(),
+    }
+    match flow.entry() {
+        Entry::Visitor(it) => it.done(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(flow.state(), State::Done));
+}
@@ -0,0 +1,20 @@
+mod door {
+    fsmentry::dsl! {
+        #[fsmentry(handle_vis = pub(crate), entry_mod = pub entry)]
+        pub Door {
+            Open -close-> Closed -open-> Open;
+        }
+    }
+}
+
+use door::{door::Door, entry::Entry};
+
+fn main() {
+    let mut door = Door::new(door::door::State::Open);
+    match door.entry() {
+        // the handle's methods are public, even though its type isn't
+        Entry::Open(it) => it.close(),
+        Entry::Closed(it) => it.open(),
+    }
+    assert_eq!(door.state().kind(), door::door::StateKind::Closed);
+}
@@ -0,0 +1,39 @@
+fsmentry::dsl! {
+    #[fsmentry(initial = Red)]
+    pub Light {
+        Red -> Green -> Red;
+    }
+    #[fsmentry(initial = Connecting)]
+    pub Socket {
+        Connecting { address: &'static str, attempts: u8 }
+        Connected: u16;
+        Connecting -> Connected;
+    }
+    #[fsmentry(initial = Waiting)]
+    pub Timer {
+        Waiting: u8;
+        Waiting -> Expired;
+    }
+}
+
+fn main() {
+    assert!(matches!(light::State::default(), light::State::Red));
+    let mut light = light::Light::new(light::State::new());
+    match light.entry() {
+        light::Entry::Red(it) => it.green(),
+        _ => unreachable!(),
+    }
+
+    let mut socket = socket::Socket::new(socket::State::new("localhost", 0));
+    match socket.entry() {
+        socket::Entry::Connecting(it) => {
+            assert_eq!(*it.address(), "localhost");
+            it.connected(8080);
+        }
+        _ => unreachable!(),
+    }
+    assert!(matches!(socket.state(), socket::State::Connected(8080)));
+
+    let timer = timer::Timer::new(timer::State::new(30));
+    assert!(matches!(timer.state(), timer::State::Waiting(30)));
+}
@@ -0,0 +1,35 @@
+mod door {
+    fsmentry::dsl! {
+        #[fsmentry(state_mod = pub state, entry_mod = pub(super) internal)]
+        Door {
+            Open -close-> Closed -lock-> Locked;
+        }
+    }
+
+    pub fn lock(door: &mut state::Door) {
+        if let internal::Entry::Closed(it) = door.entry() {
+            lock_closed(it)
+        }
+    }
+
+    // the handles are re-exported alongside `Entry`
+    fn lock_closed(door: internal::Closed) {
+        door.lock()
+    }
+}
+
+use door::state::{Door, State, StateKind};
+
+fn main() {
+    let mut door = Door::new(State::Open);
+    match door.entry() {
+        door::internal::Entry::Open(it) => it.close(),
+        _ => unreachable!(),
+    }
+    door::lock(&mut door);
+    assert_eq!(door.state().kind(), StateKind::Locked);
+    match door.entry() {
+        door::internal::Entry::Locked => {}
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,33 @@
+fsmentry::dsl! {
+    #[fsmentry(method_case = preserve)]
+    pub Wire {
+        Idle -> AwaitingAck -onTimeout-> Idle;
+    }
+    #[fsmentry(method_case = camel)]
+    pub Camel {
+        Idle -> AwaitingAck -> Idle;
+    }
+}
+
+fn main() {
+    let mut machine = wire::Wire::new(wire::State::Idle);
+    match machine.entry() {
+        wire::Entry::Idle(it) => it.AwaitingAck(),
+        _ => unreachable!(),
+    }
+    match machine.entry() {
+        wire::Entry::AwaitingAck(it) => it.onTimeout(),
+        _ => unreachable!(),
+    }
+
+    let mut machine = camel::Camel::new(camel::State::Idle);
+    match machine.entry() {
+        camel::Entry::Idle(it) => it.awaitingAck(),
+        _ => unreachable!(),
+    }
+    match machine.entry() {
+        camel::Entry::AwaitingAck(it) => it.idle(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(machine.state(), camel::State::Idle));
+}
@@ -0,0 +1,28 @@
+fsmentry::dsl! {
+    #[fsmentry(method_format = "to_{snake}")]
+    pub Wire {
+        Idle -> AwaitingAck -timeout-> Idle;
+        AwaitingAck -> Idle;
+    }
+}
+
+fn main() {
+    let mut machine = wire::Wire::new(wire::State::Idle);
+    match machine.entry() {
+        wire::Entry::Idle(it) => it.to_awaiting_ack(),
+        _ => unreachable!(),
+    }
+    match machine.entry() {
+        wire::Entry::AwaitingAck(it) => it.timeout(),
+        _ => unreachable!(),
+    }
+    match machine.entry() {
+        wire::Entry::Idle(it) => it.to_awaiting_ack(),
+        _ => unreachable!(),
+    }
+    match machine.entry() {
+        wire::Entry::AwaitingAck(it) => it.to_idle(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(machine.state(), wire::State::Idle));
+}
@@ -0,0 +1,37 @@
+use std::sync::Mutex;
+
+static REPORTED: Mutex<Vec<(&str, door::StateKind, door::StateKind)>> = Mutex::new(Vec::new());
+
+fn report(machine: &'static str, expected: door::StateKind, found: door::StateKind) {
+    REPORTED.lock().unwrap().push((machine, expected, found))
+}
+
+fsmentry::dsl! {
+    #[fsmentry(expose_handle_field = pub(crate), mismatch_hook = crate::report)]
+    pub Door {
+        Open: u8;
+        Open -> Closed -> Open;
+    }
+}
+use door::{Door, Entry, State, StateKind};
+
+fn main() {
+    let mut door = Door::new(State::Open(1));
+    let mut elsewhere = State::Closed;
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| match door.entry() {
+        Entry::Open(mut it) => {
+            it.inner = &mut elsewhere;
+            *it.get()
+        }
+        _ => unreachable!(),
+    }))
+    .unwrap_err();
+    assert_eq!(
+        panic.downcast_ref::<String>().unwrap(),
+        "`Door` was in `Closed` when a handle for `Open` expected it"
+    );
+    assert_eq!(
+        *REPORTED.lock().unwrap(),
+        [("Door", StateKind::Open, StateKind::Closed)]
+    );
+}
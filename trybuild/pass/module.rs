@@ -0,0 +1,15 @@
+fsmentry::dsl! {
+    #[fsmentry(module = lights)]
+    pub TrafficLight {
+        Red -> Green -> Red;
+    }
+}
+
+fn main() {
+    let mut machine = lights::TrafficLight::new(lights::State::Red);
+    match machine.entry() {
+        lights::Entry::Red(it) => it.green(),
+        lights::Entry::Green(it) => it.red(),
+    }
+    assert!(matches!(machine.state(), lights::State::Green));
+}
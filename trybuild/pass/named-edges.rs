@@ -0,0 +1,25 @@
+fsmentry::dsl! {
+    pub Document {
+        Draft -> Approved;
+        /// Skip review for trusted authors.
+        Draft -auto_approve-> Approved;
+        Draft -reject-> Rejected;
+    }
+}
+use document::{Document, Entry, State};
+
+fn main() {
+    let mut document = Document::new(State::Draft);
+    match document.entry() {
+        Entry::Draft(it) => it.auto_approve(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(document.state(), State::Approved));
+
+    let mut document = Document::new(State::Draft);
+    match document.entry() {
+        Entry::Draft(it) => it.approved(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(document.state(), State::Approved));
+}
@@ -0,0 +1,34 @@
+fsmentry::dsl! {
+    #[fsmentry(on_mismatch = debug_unchecked)]
+    pub Door {
+        Open: u8;
+        Closed { code: u16 }
+        Open -> Closed -> Open;
+    }
+    #[fsmentry(on_mismatch = { ::std::process::abort() })]
+    pub Gate {
+        Up: u8;
+        Up -> Down -> Up;
+    }
+}
+
+fn main() {
+    let mut door = door::Door::new(door::State::Open(1));
+    match door.entry() {
+        door::Entry::Open(mut it) => {
+            *it.get_mut() += 1;
+            assert_eq!(it.closed(7), 2)
+        }
+        _ => unreachable!(),
+    }
+    match door.entry() {
+        door::Entry::Closed(it) => assert_eq!(*it.code(), 7),
+        _ => unreachable!(),
+    }
+
+    let mut gate = gate::Gate::new(gate::State::Up(3));
+    match gate.entry() {
+        gate::Entry::Up(it) => assert_eq!(it.down(), 3),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::mpsc;
+
+fsmentry::dsl! {
+    #[fsmentry(owned = true)]
+    pub Job {
+        Pending: String;
+        Running { attempt: u8 }
+        Done: bool;
+        Pending -start-> Running -finish-> Done;
+        Running -retry-> Running;
+    }
+}
+use job::{OwnedEntry, State};
+
+/// States are sent between threads by value, with no machine to borrow.
+fn step(state: State) -> State {
+    match state.into_entry() {
+        OwnedEntry::Pending(it) => it.start(1).1,
+        OwnedEntry::Running(it) => it.finish(true).1,
+        OwnedEntry::Done(ok) => State::Done(ok),
+    }
+}
+
+fn main() {
+    let (tx, rx) = mpsc::channel();
+    tx.send(State::Pending(String::from("build"))).unwrap();
+    let state = step(rx.recv().unwrap());
+    let OwnedEntry::Running(it) = state.into_entry() else {
+        unreachable!()
+    };
+    let ((attempt,), state) = it.retry(2);
+    assert_eq!(attempt, 1);
+    assert!(matches!(state, State::Running { attempt: 2 }));
+    assert!(matches!(step(state), State::Done(true)));
+
+    // a state may be given back without transitioning
+    let OwnedEntry::Pending(it) = State::Pending(String::from("test")).into_entry() else {
+        unreachable!()
+    };
+    assert!(matches!(it.into_state(), State::Pending(name) if name == "test"));
+}
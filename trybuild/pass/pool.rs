@@ -0,0 +1,52 @@
+fsmentry::dsl! {
+    #[fsmentry(pool = true)]
+    pub Worker {
+        Idle -> Busy -> Idle;
+        Busy -> Failed;
+    }
+}
+use worker::{Entry, MachinePool, State, StateKind, Worker};
+
+fn main() {
+    let mut pool = MachinePool::new();
+    let ids = (0..4)
+        .map(|_| pool.insert(Worker::new(State::Idle)))
+        .collect::<Vec<_>>();
+    for id in &ids[..3] {
+        match pool.entry(*id).unwrap() {
+            Entry::Idle(it) => it.busy(),
+            _ => unreachable!(),
+        }
+    }
+    assert_eq!(pool.count_in_state(StateKind::Idle), 1);
+    assert_eq!(pool.count_in_state(StateKind::Busy), 3);
+
+    let busy = pool
+        .in_state(StateKind::Busy)
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    for id in busy.into_iter().take(2) {
+        match pool.entry(id).unwrap() {
+            Entry::Busy(it) => it.failed(),
+            _ => unreachable!(),
+        }
+    }
+    assert_eq!(pool.count_in_state(StateKind::Busy), 1);
+    assert!(pool
+        .in_state(StateKind::Failed)
+        .all(|(_, it)| matches!(it.state(), State::Failed)));
+
+    // ids aren't reused once removed
+    let failed = pool.in_state(StateKind::Failed).next().unwrap().0;
+    assert!(pool.remove(failed).is_some());
+    assert!(pool.remove(failed).is_none());
+    assert!(pool.entry(failed).is_none());
+    assert_eq!(pool.count_in_state(StateKind::Failed), 1);
+    let id = pool.insert(Worker::new(State::Idle));
+    assert_ne!(id, failed);
+    assert!(pool.get(failed).is_none());
+    assert!(pool.get(id).is_some());
+    assert_eq!(pool.count_in_state(StateKind::Idle), 2);
+    assert_eq!(pool.len(), 4);
+    assert_eq!(pool.iter().count(), 4);
+}
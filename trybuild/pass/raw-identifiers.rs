@@ -0,0 +1,48 @@
+fsmentry::dsl! {
+    #[fsmentry(events = true)]
+    pub Loop {
+        Super;
+        r#final { r#type: u8 }
+        r#try -> r#final -r#loop-> r#try -> Super;
+    }
+    #[fsmentry(method_case = preserve)]
+    pub Preserved {
+        A -> r#try;
+    }
+}
+// `loop` is a keyword, so the module is raw
+use r#loop::{Entry, Event, Loop, State};
+
+fn main() {
+    let mut machine = Loop::new(State::Try);
+    match machine.entry() {
+        Entry::Try(it) => it.r#final(1),
+        _ => unreachable!(),
+    }
+    match machine.entry() {
+        Entry::Final(mut it) => {
+            *it.type_mut() += 1;
+            assert_eq!(*it.r#type(), 2);
+            assert_eq!(it.r#loop(), (2,));
+        }
+        _ => unreachable!(),
+    }
+    let mut state = State::Try;
+    state.handle(Event::Super).unwrap();
+    assert!(matches!(state, State::Super));
+    assert_eq!(State::Try.kind().as_str(), "Try");
+
+    let mut machine = Loop::new(State::Try);
+    match machine.entry() {
+        Entry::Try(it) => it.super_(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(machine.state(), State::Super));
+
+    let mut machine = preserved::Preserved::new(preserved::State::A);
+    match machine.entry() {
+        preserved::Entry::A(it) => it.r#try(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(machine.state(), preserved::State::Try));
+}
@@ -0,0 +1,38 @@
+fsmentry::dsl! {
+    pub Light {
+        Red { ttl: u64, reason: &'static str }
+        Green: u64;
+        Broken { code: u8 }
+        Red -> Green -> Red -> Broken;
+    }
+}
+use light::{Entry, Light, State};
+
+fn main() {
+    let mut light = Light::new(State::Red {
+        ttl: 30,
+        reason: "stop",
+    });
+    match light.entry() {
+        Entry::Red(mut it) => {
+            *it.ttl_mut() += 1;
+            assert_eq!((*it.ttl(), *it.reason()), (31, "stop"));
+            let (ttl, _reason) = it.green(10);
+            assert_eq!(ttl, 31);
+        }
+        _ => unreachable!(),
+    }
+    match light.entry() {
+        Entry::Green(it) => assert_eq!(it.red(5, "again"), 10),
+        _ => unreachable!(),
+    }
+    match light.entry() {
+        Entry::Red(it) => assert_eq!(it.broken(7), (5, "again")),
+        _ => unreachable!(),
+    }
+    match light.entry() {
+        Entry::Broken { code } => *code += 1,
+        _ => unreachable!(),
+    }
+    assert!(matches!(light.state(), State::Broken { code: 8 }));
+}
@@ -0,0 +1,43 @@
+fsmentry::dsl! {
+    #[fsmentry(events = true)]
+    pub Upload {
+        Empty;
+        Buffered: Vec<u8>;
+        Sent { bytes: usize }
+        Buffered -requires(!data.is_empty())-> Sent -requires(*bytes > 0)-> Empty;
+    }
+}
+use upload::{Entry, Event, State, StateKind, Upload};
+
+fn main() {
+    let mut upload = Upload::new(State::Buffered(vec![1, 2, 3]));
+    match upload.entry() {
+        Entry::Buffered(it) => assert_eq!(it.sent(3), vec![1, 2, 3]),
+        _ => unreachable!(),
+    }
+    match upload.entry() {
+        Entry::Sent(it) => assert_eq!(it.empty(), (3,)),
+        _ => unreachable!(),
+    }
+
+    // events are refused, rather than asserted
+    let mut state = State::Sent { bytes: 0 };
+    let e = state.handle(Event::Empty).unwrap_err();
+    assert_eq!(e.state, StateKind::Sent);
+    assert!(e.precondition);
+    assert_eq!(e.to_string(), "the precondition of `empty` from `Sent` does not hold");
+    assert!(matches!(state, State::Sent { bytes: 0 }));
+    let mut state = State::Buffered(vec![1]);
+    state.handle(Event::Sent { bytes: 1 }).unwrap();
+    state.handle(Event::Empty).unwrap();
+    assert!(matches!(state, State::Empty));
+
+    std::panic::set_hook(Box::new(|_| {}));
+    let panicked = std::panic::catch_unwind(|| {
+        let mut upload = Upload::new(State::Buffered(vec![]));
+        if let Entry::Buffered(it) = upload.entry() {
+            it.sent(0);
+        }
+    });
+    assert_eq!(panicked.is_err(), cfg!(debug_assertions));
+}
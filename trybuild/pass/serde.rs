@@ -0,0 +1,38 @@
+fsmentry::dsl! {
+    #[fsmentry(serde = true, serde_tag = "state", serde_content = "data")]
+    pub Upload {
+        Pending;
+        /// How many bytes have been sent.
+        #[serde(rename = "in_progress")]
+        Sending: u64;
+        Failed { reason: String, retries: u8 }
+        Pending -> Sending -> Done;
+        Sending -> Failed -> Pending;
+    }
+}
+use upload::{Entry, State, StateKind, Upload};
+
+fn main() {
+    let mut upload = Upload::new(State::Pending);
+    if let Entry::Pending(it) = upload.entry() {
+        it.sending(512)
+    }
+    let json = serde_json::to_string(upload.state()).unwrap();
+    assert_eq!(json, r#"{"state":"in_progress","data":512}"#);
+
+    // a machine may be restored from its saved state
+    let state = serde_json::from_str::<State>(
+        r#"{"state":"Failed","data":{"reason":"timeout","retries":2}}"#,
+    )
+    .unwrap();
+    let mut upload = Upload::new(state);
+    let Entry::Failed(it) = upload.entry() else {
+        unreachable!()
+    };
+    assert_eq!(it.pending(), (String::from("timeout"), 2));
+
+    assert_eq!(
+        serde_json::to_string(&StateKind::Done).unwrap(),
+        r#""Done""#
+    );
+}
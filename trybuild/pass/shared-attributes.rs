@@ -0,0 +1,23 @@
+fsmentry::dsl! {
+    #![derive(Debug, PartialEq)]
+    //! Machines at the crossing.
+    pub Traffic {
+        Red -> Green -> Red;
+    }
+    #[derive(Clone)]
+    pub Pedestrian {
+        Wait -> Walk -> Wait;
+    }
+}
+
+fn main() {
+    let mut traffic = traffic::Traffic::new(traffic::State::Red);
+    match traffic.entry() {
+        traffic::Entry::Red(it) => it.green(),
+        _ => unreachable!(),
+    }
+    assert_eq!(traffic.state(), &traffic::State::Green);
+
+    let pedestrian = pedestrian::State::Wait;
+    assert_eq!(pedestrian.clone(), pedestrian::State::Wait);
+}
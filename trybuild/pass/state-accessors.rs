@@ -0,0 +1,34 @@
+fsmentry::dsl! {
+    #[fsmentry(state_accessors = true)]
+    pub TrafficLight {
+        Green: String;
+        Amber { ttl: u8, flashing: bool }
+        Red -> Green -> Amber -> Red;
+    }
+}
+use traffic_light::State;
+
+fn main() {
+    let mut state = State::Green(String::from("go"));
+    assert!(state.is_green());
+    assert!(!state.is_red());
+    assert_eq!(state.as_green().map(String::as_str), Some("go"));
+    state.as_green_mut().unwrap().push('!');
+    assert!(state.as_amber().is_none());
+    let state = match state.try_into_amber() {
+        Ok(_) => unreachable!(),
+        Err(state) => state,
+    };
+    assert_eq!(state.try_into_green().ok().as_deref(), Some("go!"));
+
+    let mut state = State::Amber {
+        ttl: 3,
+        flashing: false,
+    };
+    if let Some((ttl, flashing)) = state.as_amber_mut() {
+        *ttl -= 1;
+        *flashing = true;
+    }
+    assert_eq!(state.as_amber(), Some((&2, &true)));
+    assert_eq!(state.try_into_amber().ok(), Some((2, true)));
+}
@@ -0,0 +1,48 @@
+fsmentry::dsl! {
+    #[derive(Debug)]
+    pub Light {
+        #[fsmentry(on_enter = log_enter)]
+        Red;
+        #[fsmentry(on_enter = log_enter, on_exit = log_exit)]
+        Green: u8;
+        Red -on(self.log_change)-> Green -> Red;
+    }
+}
+use light::{Entry, Hooks, Light, State};
+
+#[derive(Default)]
+struct Log(Vec<String>);
+
+impl Hooks for Log {
+    fn log_change(&mut self, to: &u8) {
+        self.0.push(format!("change to {}", to))
+    }
+    fn log_enter(&mut self, state: &State) {
+        self.0.push(format!("enter {:?}", state))
+    }
+    fn log_exit(&mut self, state: &State) {
+        self.0.push(format!("exit {:?}", state))
+    }
+}
+
+fn main() {
+    let mut log = Log::default();
+    let mut light = Light::new(State::Red);
+    match light.entry() {
+        Entry::Red(it) => it.green(1, &mut log),
+        _ => unreachable!(),
+    }
+    match light.entry() {
+        Entry::Green(it) => assert_eq!(it.red(&mut log), 1),
+        _ => unreachable!(),
+    }
+    assert_eq!(
+        log.0,
+        [
+            "change to 1",
+            "enter Green(1)",
+            "exit Green(1)",
+            "enter Red",
+        ]
+    );
+}
@@ -0,0 +1,35 @@
+fsmentry::dsl! {
+    pub Job {
+        #[fsmentry(terminal)]
+        Finished: u32;
+        Queued -> Running -> Finished;
+        _ -> Cancelled;
+    }
+}
+use job::{Entry, Job, State};
+
+fn main() {
+    let mut job = Job::new(State::Queued);
+    match job.entry() {
+        Entry::Queued(it) => it.running(),
+        _ => unreachable!(),
+    }
+    assert!(!job.state().is_terminal());
+    match job.entry() {
+        Entry::Running(it) => it.finished(7),
+        _ => unreachable!(),
+    }
+    assert!(job.state().is_terminal());
+    // `Finished` has no transitions, not even to `Cancelled`
+    match job.entry() {
+        Entry::Finished(code) => assert_eq!(*code, 7),
+        _ => unreachable!(),
+    }
+
+    let mut job = Job::new(State::Queued);
+    match job.entry() {
+        Entry::Queued(it) => it.cancelled(),
+        _ => unreachable!(),
+    }
+    assert!(matches!(job.state(), State::Cancelled));
+}
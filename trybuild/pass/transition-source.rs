@@ -0,0 +1,40 @@
+fsmentry::dsl! {
+    #[fsmentry(transition_source = true, entry_mod = pub entry)]
+    pub Job {
+        Pending: String;
+        Pending -start-> Running -finish-> Done;
+        Running -fail-> Pending;
+        #[cfg(test)]
+        Pending -skip-> Done;
+    }
+}
+use entry::{Entry, TransitionSource};
+use job::{Job, State, StateKind};
+
+/// Written once, for the transition type of any state.
+fn describe<'a>(handle: impl TransitionSource<'a>) -> (StateKind, usize, Entry<'a>) {
+    (handle.kind(), handle.available().len(), handle.into_entry())
+}
+
+fn main() {
+    let mut job = Job::new(State::Pending(String::from("build")));
+    let (kind, available, entry) = match job.entry() {
+        Entry::Pending(it) => describe(it),
+        Entry::Running(it) => describe(it),
+        Entry::Done => unreachable!(),
+    };
+    assert_eq!(kind, StateKind::Pending);
+    // `skip` is configured out
+    assert_eq!(available, 1);
+    // the entry may still be used to transition
+    match entry {
+        Entry::Pending(it) => assert_eq!(it.start(), "build"),
+        _ => unreachable!(),
+    }
+    let Entry::Running(it) = job.entry() else {
+        unreachable!()
+    };
+    assert_eq!(it.kind(), StateKind::Running);
+    assert!(it.available().contains(&StateKind::Done));
+    assert!(it.available().contains(&StateKind::Pending));
+}
@@ -0,0 +1,34 @@
+fsmentry::dsl! {
+    pub Upload {
+        /// The bytes sent so far.
+        type Payload = Vec<u8>;
+        Sending: Payload;
+        Sent: Payload;
+        Sending -> Sent;
+    }
+    pub Log {
+        extends Upload;
+        /// The lines sent so far.
+        type Payload = String;
+    }
+}
+use upload::{Entry, Payload, State, Upload};
+
+fn main() {
+    let payload: Payload = vec![1, 2, 3];
+    let mut upload = Upload::new(State::Sending(payload));
+    match upload.entry() {
+        Entry::Sending(it) => {
+            let payload = it.get().clone();
+            let sending: Payload = it.sent(payload);
+            assert_eq!(sending, [1, 2, 3]);
+        }
+        Entry::Sent(_) => unreachable!(),
+    }
+    assert!(matches!(upload.state(), State::Sent(it) if it.len() == 3));
+
+    // the states are inherited, but with the new alias
+    let payload: log::Payload = String::from("started");
+    let log = log::Log::new(log::State::Sending(payload));
+    assert!(matches!(log.state(), log::State::Sending(it) if it == "started"));
+}
@@ -0,0 +1,44 @@
+fsmentry::dsl! {
+    pub TrafficLight {
+        Red -> Green -> Amber -> Red;
+        Green: u8;
+        Amber -> Broken;
+        Broken: String;
+    }
+}
+use traffic_light::{Amber, Green, State, TrafficLight, Visitor};
+
+#[derive(Default)]
+struct Log(Vec<&'static str>);
+
+impl Visitor for Log {
+    fn visit_green(&mut self, mut transition: Green<'_>) {
+        *transition.get_mut() += 1;
+        self.0.push("green")
+    }
+    fn visit_amber(&mut self, transition: Amber<'_>) {
+        transition.broken(String::from("bulb"));
+        self.0.push("amber")
+    }
+    fn visit_broken(&mut self, data: &mut String) {
+        data.push_str(" out");
+        self.0.push("broken")
+    }
+}
+
+fn main() {
+    let mut log = Log::default();
+    let mut light = TrafficLight::new(State::Red);
+    light.state_mut().visit(&mut log); // `visit_red` does nothing by default
+    assert!(matches!(light.state(), State::Red));
+
+    *light.state_mut() = State::Green(1);
+    light.state_mut().visit(&mut log);
+    assert!(matches!(light.state(), State::Green(2)));
+
+    *light.state_mut() = State::Amber;
+    light.state_mut().visit(&mut log);
+    light.state_mut().visit(&mut log);
+    assert!(matches!(light.state(), State::Broken(it) if it == "bulb out"));
+    assert_eq!(log.0, ["green", "amber", "broken"]);
+}